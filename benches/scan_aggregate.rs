@@ -0,0 +1,92 @@
+//! End-to-end timings of the engine binary over a generated CSV.
+//!
+//! `cargo bench` writes `BENCH_ROWS` rows (default 2,000,000) to
+//! `target/bench-data/` on first use, then reports the best of three runs
+//! per query.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const QUERIES: &[(&str, &str)] = &[
+    (
+        "filter + global aggregate",
+        "SELECT count(*), sum(amount * 2) FROM '{csv}' \
+         WHERE amount > 250 AND category = 'food'",
+    ),
+    (
+        "group by 10k keys",
+        "SELECT user_id, count(*), sum(amount), max(amount) FROM '{csv}' GROUP BY user_id",
+    ),
+    (
+        "project + limit",
+        "SELECT user_id, amount % 7 AS bucket FROM '{csv}' WHERE city != 'SF' LIMIT 10",
+    ),
+];
+
+fn main() {
+    let rows: usize = std::env::var("BENCH_ROWS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2_000_000);
+    let csv = generate(rows);
+    println!("{rows} rows, {}", csv.display());
+
+    for (name, sql) in QUERIES {
+        let sql = sql.replace("{csv}", &csv.to_string_lossy());
+        let best = (0..3).map(|_| run(&sql)).min().unwrap();
+        let per_sec = rows as f64 / best.as_secs_f64();
+        println!(
+            "{name:<28} {:>8.1} ms  {:>6.2} M rows/s",
+            best.as_secs_f64() * 1e3,
+            per_sec / 1e6
+        );
+    }
+}
+
+fn run(sql: &str) -> Duration {
+    let start = Instant::now();
+    let out = Command::new(env!("CARGO_BIN_EXE_mini_query_engine"))
+        .args(["--sql", sql])
+        .output()
+        .expect("failed to run engine");
+    let elapsed = start.elapsed();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    elapsed
+}
+
+// Deterministic transactions table; reused across runs once written.
+fn generate(rows: usize) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/bench-data");
+    let path = dir.join(format!("transactions_{rows}.csv"));
+    if path.exists() {
+        return path;
+    }
+    fs::create_dir_all(&dir).unwrap();
+
+    let categories = ["food", "shopping", "travel", "rent", "fun"];
+    let cities = ["SF", "NY", "LA", "SEA", "CHI"];
+    let mut w = BufWriter::new(File::create(&path).unwrap());
+    writeln!(w, "user_id,amount,category,city").unwrap();
+
+    // xorshift keeps the data reproducible without extra dependencies
+    let mut x: u64 = 0x9E37_79B9_7F4A_7C15;
+    for _ in 0..rows {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        let user = x % 10_000;
+        let amount = (x >> 16) % 500 + 1;
+        let category = categories[((x >> 32) % 5) as usize];
+        let city = cities[((x >> 40) % 5) as usize];
+        writeln!(w, "u{user},{amount},{category},{city}").unwrap();
+    }
+    w.flush().unwrap();
+    path
+}
@@ -0,0 +1,8 @@
+-- Spend per user outside NY, largest first
+SELECT user_id, count(*) AS n, sum(amount) AS total
+FROM 'data/transactions.csv'
+WHERE NOT city = 'NY' AND (amount >= 50 OR category = 'food')
+GROUP BY user_id
+HAVING sum(amount) > 50
+ORDER BY total DESC NULLS LAST
+LIMIT 2;
@@ -0,0 +1,4 @@
+SELECT t.user_id, u.user_id, u.name, t.amount
+FROM 'data/transactions.csv' t
+FULL OUTER JOIN 'data/users.csv' AS u ON u.user_id = t.user_id
+ORDER BY t.amount NULLS FIRST
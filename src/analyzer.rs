@@ -0,0 +1,173 @@
+use anyhow::{Result, bail};
+
+use crate::logical::LogicalPlan;
+
+/// Columns visible above a plan node.
+struct Scope {
+    columns: Vec<String>,
+    /// Input columns an Aggregate dropped because they are neither grouped
+    /// nor aggregated; kept to explain why a reference to one fails.
+    ungrouped: Vec<String>,
+}
+
+/// Check that every column the plan reads exists in the output of the node
+/// below it, resolving names against the scans' schemas. Runs on the plan
+/// `build_logical_plan` produces, before any optimizer rewrite.
+pub fn analyze(plan: &LogicalPlan) -> Result<()> {
+    bind(plan).map(|_| ())
+}
+
+fn bind(plan: &LogicalPlan) -> Result<Scope> {
+    Ok(match plan {
+        LogicalPlan::Scan { alias, schema, .. } => Scope {
+            columns: schema
+                .fields
+                .iter()
+                .map(|f| match alias {
+                    Some(a) => format!("{a}.{}", f.name),
+                    None => f.name.clone(),
+                })
+                .collect(),
+            ungrouped: Vec::new(),
+        },
+        LogicalPlan::Join {
+            left, right, on, ..
+        } => {
+            let left = bind(left)?;
+            let right = bind(right)?;
+            for (l, r) in on {
+                resolve(l, &left, "join condition")?;
+                resolve(r, &right, "join condition")?;
+            }
+
+            let mut columns = left.columns;
+            columns.extend(right.columns);
+            Scope {
+                columns,
+                ungrouped: Vec::new(),
+            }
+        }
+        LogicalPlan::Filter { input, pred } => {
+            let clause = match **input {
+                LogicalPlan::Aggregate { .. } => "HAVING",
+                _ => "WHERE",
+            };
+            let scope = bind(input)?;
+            for c in pred.columns() {
+                resolve(c, &scope, clause)?;
+            }
+            scope
+        }
+        LogicalPlan::Aggregate {
+            input,
+            group_keys,
+            aggs,
+        } => {
+            let scope = bind(input)?;
+            for k in group_keys {
+                resolve(k, &scope, "GROUP BY")?;
+            }
+            for a in aggs {
+                if let Some(arg) = &a.arg {
+                    for c in arg.columns() {
+                        resolve(c, &scope, &format!("`{}`", a.alias))?;
+                    }
+                }
+            }
+
+            let mut columns = group_keys.clone();
+            columns.extend(aggs.iter().map(|a| a.alias.clone()));
+            let ungrouped = scope
+                .columns
+                .into_iter()
+                .filter(|c| !group_keys.contains(c))
+                .collect();
+            Scope { columns, ungrouped }
+        }
+        LogicalPlan::Project { input, exprs } => {
+            let scope = bind(input)?;
+            for e in exprs {
+                for c in e.expr.columns() {
+                    resolve(c, &scope, "the select list")?;
+                }
+            }
+            Scope {
+                columns: exprs.iter().map(|e| e.name.clone()).collect(),
+                ungrouped: Vec::new(),
+            }
+        }
+        LogicalPlan::Sort { input, keys } | LogicalPlan::TopK { input, keys, .. } => {
+            let scope = bind(input)?;
+            for k in keys {
+                resolve(&k.col, &scope, "ORDER BY")?;
+            }
+            scope
+        }
+        LogicalPlan::Limit { input, .. } => bind(input)?,
+        LogicalPlan::Empty { schema } => Scope {
+            columns: schema.fields.iter().map(|f| f.name.clone()).collect(),
+            ungrouped: Vec::new(),
+        },
+    })
+}
+
+fn resolve(name: &str, scope: &Scope, clause: &str) -> Result<()> {
+    if scope.columns.iter().any(|c| c == name) {
+        return Ok(());
+    }
+
+    if scope.ungrouped.iter().any(|c| c == name) {
+        bail!(
+            "column `{name}` in {clause} must appear in group_by or be used in an aggregate function"
+        );
+    }
+
+    match suggest(name, &scope.columns) {
+        Some(s) => bail!("unknown column `{name}` in {clause}; did you mean `{s}`?"),
+        None => bail!(
+            "unknown column `{name}` in {clause}; available columns: {}",
+            scope
+                .columns
+                .iter()
+                .map(|c| format!("`{c}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Closest candidate to a misspelled column: a qualified column whose
+/// unqualified name matches, otherwise the one with the smallest edit
+/// distance if that is small relative to the name's length.
+fn suggest<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let lower = name.to_ascii_lowercase();
+    let unqualified = |c: &str| c.rsplit('.').next().unwrap_or(c).to_ascii_lowercase();
+    if let Some(c) = candidates.iter().find(|c| unqualified(c) == lower) {
+        return Some(c);
+    }
+
+    let max_distance = lower.len().div_ceil(3).max(1);
+    candidates
+        .iter()
+        .map(|c| (edit_distance(&lower, &c.to_ascii_lowercase()), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c.as_str())
+}
+
+// Levenshtein distance over chars, one row at a time.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+
+    prev[b.len()]
+}
@@ -1,23 +1,336 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::expr::{Expr, SelectItem};
+use crate::schema::DataType;
 
 #[derive(Debug, Deserialize)]
 pub struct Query {
     pub from: String,
-    pub select: Vec<String>,
 
+    /// Qualifier for `from` columns; defaults to the file stem when joining.
+    #[serde(default, rename = "as")]
+    pub alias: Option<String>,
+
+    /// Explicit column types for `from`; other columns are inferred.
     #[serde(default)]
-    pub r#where: Vec<Predicate>,
+    pub schema: HashMap<String, DataType>,
+
+    #[serde(default)]
+    pub join: Vec<JoinClause>,
+
+    pub select: Vec<SelectItem>,
+
+    #[serde(default, deserialize_with = "deserialize_where")]
+    pub r#where: Option<PredExpr>,
 
     #[serde(default)]
     pub group_by: Vec<String>,
 
+    /// Filter over grouped rows; may reference group keys and aggregates.
+    #[serde(default, deserialize_with = "deserialize_where")]
+    pub having: Option<PredExpr>,
+
+    #[serde(default)]
+    pub order_by: Vec<OrderKey>,
+
     #[serde(default)]
     pub limit: Option<usize>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
+pub struct JoinClause {
+    pub from: String,
+
+    #[serde(default, rename = "as")]
+    pub alias: Option<String>,
+
+    #[serde(default)]
+    pub schema: HashMap<String, DataType>,
+
+    #[serde(default, rename = "type")]
+    pub kind: JoinKind,
+
+    /// Equi-join key pairs, e.g. `{"left": "t.user_id", "right": "u.user_id"}`.
+    pub on: Vec<JoinOn>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct JoinOn {
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinKind {
+    #[default]
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+impl fmt::Display for JoinKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinKind::Inner => write!(f, "inner"),
+            JoinKind::Left => write!(f, "left"),
+            JoinKind::Right => write!(f, "right"),
+            JoinKind::Full => write!(f, "full"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct Predicate {
     pub col: String,
     pub op: String,
     pub val: serde_json::Value,
 }
+
+/// Comparison of two expressions, as written before simplification turns
+/// it into a `Predicate` where it can. In JSON, `{"left", "op", "right"}`
+/// with each side given as expression text.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Comparison {
+    #[serde(deserialize_with = "deserialize_expr")]
+    pub left: Expr,
+    pub op: String,
+    #[serde(deserialize_with = "deserialize_expr")]
+    pub right: Expr,
+}
+
+/// The operator that gives the same result with its operands swapped.
+pub fn flip(op: &str) -> &str {
+    match op {
+        "<" => ">",
+        "<=" => ">=",
+        ">" => "<",
+        ">=" => "<=",
+        other => other,
+    }
+}
+
+fn deserialize_expr<'de, D>(de: D) -> Result<Expr, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(de)?;
+    Expr::parse(&text).map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct OrderKey {
+    pub col: String,
+
+    #[serde(default)]
+    pub dir: SortDir,
+
+    /// Defaults to NULLS LAST for ascending and NULLS FIRST for descending keys.
+    #[serde(default)]
+    pub nulls: Option<NullsOrder>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDir {
+    #[default]
+    #[serde(alias = "ASC")]
+    Asc,
+    #[serde(alias = "DESC")]
+    Desc,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NullsOrder {
+    #[serde(alias = "FIRST")]
+    First,
+    #[serde(alias = "LAST")]
+    Last,
+}
+
+impl OrderKey {
+    pub fn asc(col: &str) -> Self {
+        Self {
+            col: col.to_string(),
+            dir: SortDir::Asc,
+            nulls: None,
+        }
+    }
+
+    pub fn nulls_first(&self) -> bool {
+        match self.nulls {
+            Some(n) => n == NullsOrder::First,
+            None => self.dir == SortDir::Desc,
+        }
+    }
+}
+
+/// Boolean predicate tree. In JSON a node is either a leaf comparison
+/// (`{"col", "op", "val"}` or `{"left", "op", "right"}`) or one of
+/// `{"and": [..]}`, `{"or": [..]}`, `{"not": ..}`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PredExpr {
+    And { and: Vec<PredExpr> },
+    Or { or: Vec<PredExpr> },
+    Not { not: Box<PredExpr> },
+    Cmp(Predicate),
+    Compare(Comparison),
+}
+
+// `where` and `having` accept either a single predicate tree or a flat list,
+// which is treated as an implicit AND.
+fn deserialize_where<'de, D>(de: D) -> Result<Option<PredExpr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum WhereRepr {
+        List(Vec<PredExpr>),
+        Expr(PredExpr),
+    }
+
+    Ok(match WhereRepr::deserialize(de)? {
+        WhereRepr::List(mut preds) => match preds.len() {
+            0 => None,
+            1 => preds.pop(),
+            _ => Some(PredExpr::And { and: preds }),
+        },
+        WhereRepr::Expr(e) => Some(e),
+    })
+}
+
+impl PredExpr {
+    /// Whether every leaf compares a column with a literal, the only form a
+    /// scan can check before parsing a record.
+    pub fn is_column_literal(&self) -> bool {
+        match self {
+            PredExpr::And { and: items } | PredExpr::Or { or: items } => {
+                items.iter().all(PredExpr::is_column_literal)
+            }
+            PredExpr::Not { not } => not.is_column_literal(),
+            PredExpr::Cmp(_) => true,
+            PredExpr::Compare(_) => false,
+        }
+    }
+
+    /// Every column name referenced by a leaf comparison.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut out = Vec::new();
+        self.collect_columns(&mut out);
+        out
+    }
+
+    /// Rebuild the tree with every leaf comparison rewritten by `f`.
+    pub fn try_map_leaves<F>(&self, f: &mut F) -> anyhow::Result<PredExpr>
+    where
+        F: FnMut(&PredExpr) -> anyhow::Result<PredExpr>,
+    {
+        let map_all = |items: &[PredExpr], f: &mut F| {
+            items
+                .iter()
+                .map(|p| p.try_map_leaves(f))
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+
+        Ok(match self {
+            PredExpr::And { and } => PredExpr::And {
+                and: map_all(and, f)?,
+            },
+            PredExpr::Or { or } => PredExpr::Or {
+                or: map_all(or, f)?,
+            },
+            PredExpr::Not { not } => PredExpr::Not {
+                not: Box::new(not.try_map_leaves(f)?),
+            },
+            leaf => f(leaf)?,
+        })
+    }
+
+    fn collect_columns<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            PredExpr::And { and: items } | PredExpr::Or { or: items } => {
+                for p in items {
+                    p.collect_columns(out);
+                }
+            }
+            PredExpr::Not { not } => not.collect_columns(out),
+            PredExpr::Cmp(p) => out.push(&p.col),
+            PredExpr::Compare(c) => {
+                out.extend(c.left.columns());
+                out.extend(c.right.columns());
+            }
+        }
+    }
+}
+
+impl fmt::Display for OrderKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dir = match self.dir {
+            SortDir::Asc => "ASC",
+            SortDir::Desc => "DESC",
+        };
+        let nulls = if self.nulls_first() { "FIRST" } else { "LAST" };
+        write!(f, "{} {dir} NULLS {nulls}", self.col)
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.col, self.op, self.val)
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.left, self.op, self.right)
+    }
+}
+
+impl fmt::Display for PredExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Parenthesize compound children so the tree shape stays visible.
+        fn child(e: &PredExpr, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match e {
+                PredExpr::Cmp(_) | PredExpr::Compare(_) | PredExpr::Not { .. } => {
+                    write!(f, "{e}")
+                }
+                _ => write!(f, "({e})"),
+            }
+        }
+
+        fn join(
+            items: &[PredExpr],
+            sep: &str,
+            empty: &str,
+            f: &mut fmt::Formatter<'_>,
+        ) -> fmt::Result {
+            if items.is_empty() {
+                return write!(f, "{empty}");
+            }
+            for (i, e) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {sep} ")?;
+                }
+                child(e, f)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            PredExpr::And { and } => join(and, "AND", "true", f),
+            PredExpr::Or { or } => join(or, "OR", "false", f),
+            PredExpr::Not { not } => {
+                write!(f, "NOT ")?;
+                child(not, f)
+            }
+            PredExpr::Cmp(p) => write!(f, "{p}"),
+            PredExpr::Compare(c) => write!(f, "{c}"),
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use anyhow::{Result, bail};
+use serde_json::Value as JsonValue;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use crate::schema::{DataType, SchemaRef};
+use crate::value::Row;
+
+/// Rows per batch produced by scans and row-buffering operators.
+pub const BATCH_SIZE: usize = 4096;
+
+/// One bit per row; a set bit marks a non-null value.
+#[derive(Debug, Clone, Default)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    pub fn with_capacity(bits: usize) -> Self {
+        Self {
+            words: Vec::with_capacity(bits.div_ceil(64)),
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        if bit {
+            self.words[self.len / 64] |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        self.words.truncate(len.div_ceil(64));
+        if !len.is_multiple_of(64) {
+            let last = self.words.len() - 1;
+            self.words[last] &= (1 << (len % 64)) - 1;
+        }
+        self.len = len;
+    }
+}
+
+/// Values of one column. Null slots hold a default value and are marked in
+/// the column's validity bitmap.
+#[derive(Debug, Clone)]
+pub enum ColumnData {
+    Int64(Vec<i64>),
+    Float64(Vec<f64>),
+    Bool(Vec<bool>),
+    Utf8(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub data: ColumnData,
+    pub validity: Bitmap,
+}
+
+/// A single value, used where operators work value by value (group keys,
+/// min/max, row conversion).
+#[derive(Debug, Clone)]
+pub enum Scalar {
+    Null,
+    Int64(i64),
+    Float64(f64),
+    Bool(bool),
+    Utf8(String),
+}
+
+// Floats compare by bit pattern so scalars can key a hash map.
+impl PartialEq for Scalar {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Scalar::Null, Scalar::Null) => true,
+            (Scalar::Int64(a), Scalar::Int64(b)) => a == b,
+            (Scalar::Float64(a), Scalar::Float64(b)) => a.to_bits() == b.to_bits(),
+            (Scalar::Bool(a), Scalar::Bool(b)) => a == b,
+            (Scalar::Utf8(a), Scalar::Utf8(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Scalar {}
+
+impl Hash for Scalar {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Scalar::Null => {}
+            Scalar::Int64(v) => v.hash(state),
+            Scalar::Float64(v) => v.to_bits().hash(state),
+            Scalar::Bool(v) => v.hash(state),
+            Scalar::Utf8(v) => v.hash(state),
+        }
+    }
+}
+
+impl Scalar {
+    pub fn from_json(v: &JsonValue) -> Scalar {
+        match v {
+            JsonValue::Null => Scalar::Null,
+            JsonValue::Bool(b) => Scalar::Bool(*b),
+            JsonValue::Number(n) => match n.as_i64() {
+                Some(i) => Scalar::Int64(i),
+                None => Scalar::Float64(n.as_f64().unwrap_or(0.0)),
+            },
+            JsonValue::String(s) => Scalar::Utf8(s.clone()),
+            other => Scalar::Utf8(other.to_string()),
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            Scalar::Null => JsonValue::Null,
+            Scalar::Int64(v) => JsonValue::from(*v),
+            Scalar::Float64(v) => JsonValue::from(*v),
+            Scalar::Bool(v) => JsonValue::from(*v),
+            Scalar::Utf8(v) => JsonValue::from(v.as_str()),
+        }
+    }
+
+    /// Type of a non-null scalar.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Scalar::Null => None,
+            Scalar::Int64(_) => Some(DataType::Int64),
+            Scalar::Float64(_) => Some(DataType::Float64),
+            Scalar::Bool(_) => Some(DataType::Bool),
+            Scalar::Utf8(_) => Some(DataType::Utf8),
+        }
+    }
+
+    /// Order of two non-null values: numbers numerically, strings lexically,
+    /// anything else by its JSON text.
+    pub fn cmp_value(&self, other: &Scalar) -> Ordering {
+        match (self, other) {
+            (Scalar::Utf8(a), Scalar::Utf8(b)) => a.cmp(b),
+            (Scalar::Bool(a), Scalar::Bool(b)) => a.cmp(b),
+            (Scalar::Int64(a), Scalar::Int64(b)) => a.cmp(b),
+            (a, b) => match (a.as_f64(), b.as_f64()) {
+                (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                _ => a.to_json().to_string().cmp(&b.to_json().to_string()),
+            },
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Scalar::Int64(v) => Some(*v as f64),
+            Scalar::Float64(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl Column {
+    pub fn with_capacity(dtype: DataType, capacity: usize) -> Self {
+        let data = match dtype {
+            DataType::Int64 => ColumnData::Int64(Vec::with_capacity(capacity)),
+            DataType::Float64 => ColumnData::Float64(Vec::with_capacity(capacity)),
+            DataType::Bool => ColumnData::Bool(Vec::with_capacity(capacity)),
+            DataType::Utf8 => ColumnData::Utf8(Vec::with_capacity(capacity)),
+        };
+        Self {
+            data,
+            validity: Bitmap::with_capacity(capacity),
+        }
+    }
+
+    /// `n` copies of one value.
+    pub fn repeat(value: &Scalar, dtype: DataType, n: usize) -> Result<Self> {
+        let mut col = Column::with_capacity(dtype, n);
+        for _ in 0..n {
+            col.push(value.clone())?;
+        }
+        Ok(col)
+    }
+
+    pub fn data_type(&self) -> DataType {
+        match &self.data {
+            ColumnData::Int64(_) => DataType::Int64,
+            ColumnData::Float64(_) => DataType::Float64,
+            ColumnData::Bool(_) => DataType::Bool,
+            ColumnData::Utf8(_) => DataType::Utf8,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.validity.len()
+    }
+
+    pub fn is_valid(&self, i: usize) -> bool {
+        self.validity.get(i)
+    }
+
+    pub fn value(&self, i: usize) -> Scalar {
+        if !self.is_valid(i) {
+            return Scalar::Null;
+        }
+        match &self.data {
+            ColumnData::Int64(v) => Scalar::Int64(v[i]),
+            ColumnData::Float64(v) => Scalar::Float64(v[i]),
+            ColumnData::Bool(v) => Scalar::Bool(v[i]),
+            ColumnData::Utf8(v) => Scalar::Utf8(v[i].clone()),
+        }
+    }
+
+    /// Numeric value at `i`; `None` for nulls and non-numeric columns.
+    pub fn f64_at(&self, i: usize) -> Option<f64> {
+        if !self.is_valid(i) {
+            return None;
+        }
+        match &self.data {
+            ColumnData::Int64(v) => Some(v[i] as f64),
+            ColumnData::Float64(v) => Some(v[i]),
+            _ => None,
+        }
+    }
+
+    pub fn push_null(&mut self) {
+        match &mut self.data {
+            ColumnData::Int64(v) => v.push(0),
+            ColumnData::Float64(v) => v.push(0.0),
+            ColumnData::Bool(v) => v.push(false),
+            ColumnData::Utf8(v) => v.push(String::new()),
+        }
+        self.validity.push(false);
+    }
+
+    /// Append a value of the column's type; integers also fit float columns.
+    pub fn push(&mut self, value: Scalar) -> Result<()> {
+        match (&mut self.data, value) {
+            (_, Scalar::Null) => {
+                self.push_null();
+                return Ok(());
+            }
+            (ColumnData::Int64(v), Scalar::Int64(x)) => v.push(x),
+            (ColumnData::Float64(v), Scalar::Float64(x)) => v.push(x),
+            (ColumnData::Float64(v), Scalar::Int64(x)) => v.push(x as f64),
+            (ColumnData::Bool(v), Scalar::Bool(x)) => v.push(x),
+            (ColumnData::Utf8(v), Scalar::Utf8(x)) => v.push(x),
+            (_, other) => bail!(
+                "cannot store {} in a {} column",
+                other.to_json(),
+                self.data_type()
+            ),
+        }
+        self.validity.push(true);
+        Ok(())
+    }
+
+    /// Parse a raw CSV cell into the column. Empty cells are null; returns
+    /// false, appending nothing, if the cell does not fit the column type.
+    pub fn push_cell(&mut self, cell: &str) -> bool {
+        let t = cell.trim();
+        if t.is_empty() {
+            self.push_null();
+            return true;
+        }
+
+        let ok = match &mut self.data {
+            ColumnData::Int64(v) => t.parse().map(|x| v.push(x)).is_ok(),
+            ColumnData::Float64(v) => t.parse().map(|x| v.push(x)).is_ok(),
+            ColumnData::Bool(v) => {
+                if t.eq_ignore_ascii_case("true") {
+                    v.push(true);
+                    true
+                } else if t.eq_ignore_ascii_case("false") {
+                    v.push(false);
+                    true
+                } else {
+                    false
+                }
+            }
+            ColumnData::Utf8(v) => {
+                v.push(t.to_string());
+                true
+            }
+        };
+        if ok {
+            self.validity.push(true);
+        }
+        ok
+    }
+
+    /// Rows at `indices`, in that order.
+    pub fn take(&self, indices: &[usize]) -> Column {
+        fn pick<T: Clone>(v: &[T], indices: &[usize]) -> Vec<T> {
+            indices.iter().map(|&i| v[i].clone()).collect()
+        }
+
+        let data = match &self.data {
+            ColumnData::Int64(v) => ColumnData::Int64(pick(v, indices)),
+            ColumnData::Float64(v) => ColumnData::Float64(pick(v, indices)),
+            ColumnData::Bool(v) => ColumnData::Bool(pick(v, indices)),
+            ColumnData::Utf8(v) => ColumnData::Utf8(pick(v, indices)),
+        };
+        let mut validity = Bitmap::with_capacity(indices.len());
+        for &i in indices {
+            validity.push(self.is_valid(i));
+        }
+        Column { data, validity }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        match &mut self.data {
+            ColumnData::Int64(v) => v.truncate(len),
+            ColumnData::Float64(v) => v.truncate(len),
+            ColumnData::Bool(v) => v.truncate(len),
+            ColumnData::Utf8(v) => v.truncate(len),
+        }
+        self.validity.truncate(len);
+    }
+}
+
+/// A horizontal slice of a result: one typed column per schema field, all
+/// of the same length.
+#[derive(Debug, Clone)]
+pub struct RecordBatch {
+    schema: SchemaRef,
+    columns: Vec<Column>,
+    num_rows: usize,
+}
+
+impl RecordBatch {
+    pub fn new(schema: SchemaRef, columns: Vec<Column>, num_rows: usize) -> Self {
+        debug_assert_eq!(schema.fields.len(), columns.len());
+        debug_assert!(columns.iter().all(|c| c.len() == num_rows));
+        Self {
+            schema,
+            columns,
+            num_rows,
+        }
+    }
+
+    /// Build a batch from rows keyed by column name; missing keys are null.
+    pub fn from_rows(schema: SchemaRef, rows: &[Row]) -> Result<Self> {
+        let mut columns = Vec::with_capacity(schema.fields.len());
+        for f in &schema.fields {
+            let mut col = Column::with_capacity(f.dtype, rows.len());
+            for r in rows {
+                col.push(r.get(&f.name).map_or(Scalar::Null, Scalar::from_json))?;
+            }
+            columns.push(col);
+        }
+        Ok(Self::new(schema, columns, rows.len()))
+    }
+
+    pub fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn column_by_name(&self, name: &str) -> Option<&Column> {
+        self.schema.index_of(name).map(|i| &self.columns[i])
+    }
+
+    pub fn take(&self, indices: &[usize]) -> RecordBatch {
+        RecordBatch {
+            schema: self.schema.clone(),
+            columns: self.columns.iter().map(|c| c.take(indices)).collect(),
+            num_rows: indices.len(),
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.num_rows {
+            self.columns.iter_mut().for_each(|c| c.truncate(len));
+            self.num_rows = len;
+        }
+    }
+
+    pub fn row(&self, i: usize) -> Row {
+        self.schema
+            .fields
+            .iter()
+            .zip(&self.columns)
+            .map(|(f, c)| (f.name.clone(), c.value(i).to_json()))
+            .collect()
+    }
+
+    pub fn to_rows(&self) -> Vec<Row> {
+        (0..self.num_rows).map(|i| self.row(i)).collect()
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::Result;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+use crate::ast::{JoinKind, PredExpr, Predicate};
+use crate::expr::Expr;
+use crate::logical::LogicalPlan;
+use crate::stats::{ColumnStats, TableStats};
+use crate::value::cmp_json;
+
+// Selectivity guesses for comparisons the statistics cannot judge.
+const EQ_GUESS: f64 = 0.1;
+const RANGE_GUESS: f64 = 1.0 / 3.0;
+
+/// Row and selectivity estimates from the saved statistics of the files a
+/// plan scans. Plans over a file without statistics get no estimate.
+#[derive(Debug, Default)]
+pub struct CostModel {
+    tables: HashMap<String, TableStats>,
+}
+
+impl CostModel {
+    /// Load the statistics of every file `plan` scans that has them.
+    pub fn load(plan: &LogicalPlan) -> Result<CostModel> {
+        let mut tables = HashMap::new();
+        let mut paths = Vec::new();
+        scan_paths(plan, &mut paths);
+        for path in paths {
+            if !tables.contains_key(path)
+                && let Some(stats) = TableStats::load(path)?
+            {
+                tables.insert(path.to_string(), stats);
+            }
+        }
+        Ok(CostModel { tables })
+    }
+
+    /// Estimated number of rows `plan` outputs.
+    pub fn rows(&self, plan: &LogicalPlan) -> Option<f64> {
+        Some(match plan {
+            LogicalPlan::Scan {
+                path,
+                filter,
+                limit,
+                ..
+            } => {
+                let mut rows = self.tables.get(path)?.rows as f64;
+                if let Some(f) = filter {
+                    rows *= self.selectivity(plan, f);
+                }
+                limit.map_or(rows, |n| rows.min(n as f64))
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                ..
+            } => {
+                let (l, r) = (self.rows(left)?, self.rows(right)?);
+                // Each key pair matches a row to the other side's rows with
+                // the same value, assuming the side with fewer distinct
+                // values has all its values on the other side too.
+                let mut matched = l * r;
+                for (lk, rk) in on {
+                    let distinct = [self.distinct(left, lk), self.distinct(right, rk)]
+                        .into_iter()
+                        .flatten()
+                        .reduce(f64::max)
+                        .unwrap_or(l.max(r));
+                    matched /= distinct.max(1.0);
+                }
+                match kind {
+                    JoinKind::Inner => matched,
+                    JoinKind::Left => matched.max(l),
+                    JoinKind::Right => matched.max(r),
+                    JoinKind::Full => matched.max(l).max(r),
+                }
+            }
+            LogicalPlan::Filter { input, pred } => {
+                self.rows(input)? * self.selectivity(input, pred)
+            }
+            LogicalPlan::Aggregate {
+                input, group_keys, ..
+            } => {
+                let rows = self.rows(input)?;
+                if group_keys.is_empty() {
+                    return Some(1.0);
+                }
+                group_keys
+                    .iter()
+                    .map(|k| self.distinct(input, k).unwrap_or(rows))
+                    .product::<f64>()
+                    .min(rows)
+            }
+            LogicalPlan::Project { input, .. } | LogicalPlan::Sort { input, .. } => {
+                self.rows(input)?
+            }
+            LogicalPlan::Limit { input, n } | LogicalPlan::TopK { input, n, .. } => {
+                self.rows(input)?.min(*n as f64)
+            }
+            LogicalPlan::Empty { .. } => 0.0,
+        })
+    }
+
+    /// Estimated fraction of the rows of `input` that pass `pred`.
+    pub fn selectivity(&self, input: &LogicalPlan, pred: &PredExpr) -> f64 {
+        match pred {
+            PredExpr::And { and } => and.iter().map(|p| self.selectivity(input, p)).product(),
+            PredExpr::Or { or } => {
+                1.0 - or
+                    .iter()
+                    .map(|p| 1.0 - self.selectivity(input, p))
+                    .product::<f64>()
+            }
+            PredExpr::Not { not } => 1.0 - self.selectivity(input, not),
+            PredExpr::Cmp(p) => match self.column(input, &p.col) {
+                Some((table, col)) => cmp_selectivity(table, col, p),
+                None => guess(&p.op),
+            },
+            PredExpr::Compare(c) => guess(&c.op),
+        }
+    }
+
+    /// `pred` with the operands of each AND ordered most selective first,
+    /// and of each OR most likely to pass first, so evaluation can stop
+    /// early. Operands are only reordered when statistics cover all of them.
+    pub fn order_predicate(&self, input: &LogicalPlan, pred: PredExpr) -> PredExpr {
+        let order = |items: Vec<PredExpr>, ascending: bool| {
+            let mut items: Vec<_> = items
+                .into_iter()
+                .map(|p| self.order_predicate(input, p))
+                .collect();
+            let judged = items
+                .iter()
+                .all(|p| p.columns().iter().all(|c| self.column(input, c).is_some()));
+            if judged {
+                let sel = |p: &PredExpr| self.selectivity(input, p);
+                items.sort_by(|a, b| {
+                    let ord = sel(a).total_cmp(&sel(b));
+                    if ascending { ord } else { ord.reverse() }
+                });
+            }
+            items
+        };
+
+        match pred {
+            PredExpr::And { and } => PredExpr::And {
+                and: order(and, true),
+            },
+            PredExpr::Or { or } => PredExpr::Or {
+                or: order(or, false),
+            },
+            PredExpr::Not { not } => PredExpr::Not {
+                not: Box::new(self.order_predicate(input, *not)),
+            },
+            leaf @ (PredExpr::Cmp(_) | PredExpr::Compare(_)) => leaf,
+        }
+    }
+
+    /// Estimated distinct values of output column `col` of `plan`.
+    fn distinct(&self, plan: &LogicalPlan, col: &str) -> Option<f64> {
+        let (_, stats) = self.column(plan, col)?;
+        let distinct = stats.distinct as f64;
+        Some(self.rows(plan).map_or(distinct, |rows| distinct.min(rows)))
+    }
+
+    /// Statistics of the file column behind output column `col` of `plan`,
+    /// with those of its file.
+    fn column(&self, plan: &LogicalPlan, col: &str) -> Option<(&TableStats, &ColumnStats)> {
+        match plan {
+            LogicalPlan::Scan { path, alias, .. } => {
+                let name = match alias {
+                    Some(a) => col
+                        .strip_prefix(a.as_str())
+                        .and_then(|c| c.strip_prefix('.'))?,
+                    None => col,
+                };
+                let table = self.tables.get(path)?;
+                Some((table, table.column(name)?))
+            }
+            LogicalPlan::Join { left, right, .. } => {
+                self.column(left, col).or_else(|| self.column(right, col))
+            }
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. }
+            | LogicalPlan::TopK { input, .. } => self.column(input, col),
+            LogicalPlan::Project { input, exprs } => match exprs.iter().find(|e| e.name == col) {
+                Some(e) => match &e.expr {
+                    Expr::Column(c) => self.column(input, c),
+                    _ => None,
+                },
+                None => None,
+            },
+            LogicalPlan::Aggregate {
+                input, group_keys, ..
+            } if group_keys.iter().any(|k| k == col) => self.column(input, col),
+            _ => None,
+        }
+    }
+}
+
+fn scan_paths<'a>(plan: &'a LogicalPlan, out: &mut Vec<&'a str>) {
+    match plan {
+        LogicalPlan::Scan { path, .. } => out.push(path),
+        LogicalPlan::Join { left, right, .. } => {
+            scan_paths(left, out);
+            scan_paths(right, out);
+        }
+        LogicalPlan::Filter { input, .. }
+        | LogicalPlan::Aggregate { input, .. }
+        | LogicalPlan::Project { input, .. }
+        | LogicalPlan::Sort { input, .. }
+        | LogicalPlan::Limit { input, .. }
+        | LogicalPlan::TopK { input, .. } => scan_paths(input, out),
+        LogicalPlan::Empty { .. } => {}
+    }
+}
+
+// Selectivity of a comparison the statistics cannot judge.
+fn guess(op: &str) -> f64 {
+    match op {
+        "==" => EQ_GUESS,
+        "!=" => 1.0 - EQ_GUESS,
+        _ => RANGE_GUESS,
+    }
+}
+
+/// Fraction of a file's rows for which `col op val` holds. Null cells get
+/// the fixed result execution gives them.
+fn cmp_selectivity(table: &TableStats, col: &ColumnStats, p: &Predicate) -> f64 {
+    let rows = table.rows.max(1) as f64;
+    let null_frac = col.nulls as f64 / rows;
+    let if_null = cmp_json(&JsonValue::Null, &p.op, &p.val).unwrap_or(false);
+
+    let outside = before(&p.val, &col.min) || before(&col.max, &p.val);
+    let eq = if outside || col.distinct == 0 {
+        0.0
+    } else {
+        1.0 / col.distinct as f64
+    };
+    let below = if before(&p.val, &col.min) {
+        0.0
+    } else if before(&col.max, &p.val) {
+        1.0
+    } else {
+        fraction_below(col, &p.val).unwrap_or(RANGE_GUESS)
+    };
+
+    let matched = match p.op.as_str() {
+        "==" => eq,
+        "!=" => 1.0 - eq,
+        "<" => below,
+        "<=" => below + eq,
+        ">" => 1.0 - below - eq,
+        ">=" => 1.0 - below,
+        _ => 1.0,
+    };
+    let non_null = 1.0 - null_frac;
+    non_null * matched.clamp(0.0, 1.0) + if if_null { null_frac } else { 0.0 }
+}
+
+/// Whether `a` sorts strictly before `b`, comparing numbers as numbers and
+/// anything else as text; `false` when either is null.
+fn before(a: &JsonValue, b: &JsonValue) -> bool {
+    if a.is_null() || b.is_null() {
+        return false;
+    }
+    cmp_json(a, "<", b).unwrap_or(false)
+}
+
+/// Fraction of non-null values strictly below the number `val`, read off
+/// the column's histogram assuming values spread evenly within each bucket.
+/// A bucket ending at `val` holds the rows equal to it, an even share of the
+/// distinct values, which are not below.
+fn fraction_below(col: &ColumnStats, val: &JsonValue) -> Option<f64> {
+    let v = val.as_f64()?;
+    let total: u64 = col.histogram.iter().map(|b| b.rows).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut lower = col.min.as_f64()?;
+    let mut below = 0.0;
+    for b in &col.histogram {
+        if v > b.upper {
+            below += b.rows as f64;
+        } else {
+            if v == b.upper && v > lower {
+                let equal = total as f64 / col.distinct.max(1) as f64;
+                below += (b.rows as f64 - equal).max(0.0);
+            } else if v > lower {
+                below += b.rows as f64 * (v - lower) / (b.upper - lower);
+            }
+            break;
+        }
+        lower = b.upper;
+    }
+    Some(below / total as f64)
+}
@@ -1,130 +1,1105 @@
-use anyhow::{Result, anyhow};
-use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use anyhow::{Context, Result, anyhow, bail};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use crate::exec::ExecNode;
-use crate::value::Row;
+use crate::batch::{BATCH_SIZE, Column, ColumnData, RecordBatch, Scalar};
+use crate::exec::spill::{
+    Decoder, RowReader, RowWriter, SpillDir, put_f64, put_scalar, put_str, put_u64,
+};
+use crate::exec::{Detail, ExecNode, PartitionFn};
+use crate::explain;
+use crate::expr::Expr;
+use crate::schema::{DataType, Field, Schema, SchemaRef};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AggFunc {
     Sum,
     Count,
+    Avg,
+    Min,
+    Max,
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for AggFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AggFunc::Sum => write!(f, "sum"),
+            AggFunc::Count => write!(f, "count"),
+            AggFunc::Avg => write!(f, "avg"),
+            AggFunc::Min => write!(f, "min"),
+            AggFunc::Max => write!(f, "max"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct AggSpec {
     pub func: AggFunc,
-    pub col: String,   // column to aggregate
-    pub alias: String, // output column name
+    pub arg: Option<Expr>, // expression to aggregate, None for count(*)
+    pub distinct: bool,    // count(DISTINCT ..)
+    pub alias: String,     // output column name
+}
+
+impl AggSpec {
+    /// Output type given the aggregate's input columns.
+    fn data_type(&self, input: &Schema) -> Result<DataType> {
+        Ok(match (self.func, &self.arg) {
+            (AggFunc::Count, _) => DataType::Int64,
+            (AggFunc::Sum | AggFunc::Avg, _) => DataType::Float64,
+            (AggFunc::Min | AggFunc::Max, Some(e)) => e.data_type(input)?,
+            (AggFunc::Min | AggFunc::Max, None) => bail!("{} needs an argument", self.func),
+        })
+    }
+}
+
+/// Exact running sum: integers in an `i128`, floats as non-overlapping
+/// partials (Shewchuk's algorithm), so the result does not depend on the
+/// order values arrive in or how partial sums are merged.
+#[derive(Default)]
+struct ExactSum {
+    ints: i128,
+    partials: Vec<f64>,
+    // inf/nan inputs, which the partials cannot represent
+    nonfinite: f64,
+}
+
+impl ExactSum {
+    fn add_int(&mut self, v: i64) {
+        self.ints += v as i128;
+    }
+
+    fn add_float(&mut self, mut x: f64) {
+        if !x.is_finite() {
+            self.nonfinite += x;
+            return;
+        }
+
+        let mut kept = 0;
+        for j in 0..self.partials.len() {
+            let mut y = self.partials[j];
+            if x.abs() < y.abs() {
+                std::mem::swap(&mut x, &mut y);
+            }
+            let hi = x + y;
+            let lo = y - (hi - x);
+            if lo != 0.0 {
+                self.partials[kept] = lo;
+                kept += 1;
+            }
+            x = hi;
+        }
+        self.partials.truncate(kept);
+        self.partials.push(x);
+    }
+
+    fn merge(&mut self, other: ExactSum) {
+        self.ints += other.ints;
+        self.nonfinite += other.nonfinite;
+        for p in other.partials {
+            self.add_float(p);
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.ints.to_le_bytes());
+        put_f64(buf, self.nonfinite);
+        put_u64(buf, self.partials.len() as u64);
+        for p in &self.partials {
+            put_f64(buf, *p);
+        }
+    }
+
+    fn decode(d: &mut Decoder) -> Result<Self> {
+        let ints = i128::from_le_bytes(d.bytes(16)?.try_into()?);
+        let nonfinite = d.f64()?;
+        let partials = (0..d.u64()?).map(|_| d.f64()).collect::<Result<_>>()?;
+        Ok(Self {
+            ints,
+            partials,
+            nonfinite,
+        })
+    }
+
+    /// The exact sum, rounded once to the nearest float.
+    fn value(mut self) -> f64 {
+        if self.nonfinite != 0.0 || self.nonfinite.is_nan() {
+            return self.nonfinite;
+        }
+
+        // Split the integer total into two floats that add up to it exactly.
+        let hi = self.ints as f64;
+        self.add_float(hi);
+        self.add_float((self.ints - hi as i128) as f64);
+
+        let p = &self.partials;
+        let Some(&last) = p.last() else {
+            return 0.0;
+        };
+        let (mut hi, mut lo) = (last, 0.0);
+        let mut n = p.len() - 1;
+        while n > 0 {
+            n -= 1;
+            let (x, y) = (hi, p[n]);
+            hi = x + y;
+            lo = y - (hi - x);
+            if lo != 0.0 {
+                break;
+            }
+        }
+        // Round half to even across the remaining partials.
+        if n > 0 && ((lo < 0.0 && p[n - 1] < 0.0) || (lo > 0.0 && p[n - 1] > 0.0)) {
+            let y = lo * 2.0;
+            let x = hi + y;
+            if y == x - hi {
+                hi = x;
+            }
+        }
+        hi
+    }
+}
+
+/// Running state of one aggregate for one group. Null inputs are ignored by
+/// every function except `count(*)`.
+enum Accumulator {
+    CountStar(i64),
+    Count(i64),
+    CountDistinct(HashSet<Scalar>),
+    // `None` until the first non-null input, so empty groups sum to null
+    Sum(Option<ExactSum>),
+    Avg { sum: ExactSum, n: u64 },
+    Min(Option<Scalar>),
+    Max(Option<Scalar>),
+}
+
+impl Accumulator {
+    fn new(spec: &AggSpec) -> Self {
+        match (spec.func, &spec.arg, spec.distinct) {
+            (AggFunc::Count, None, _) => Accumulator::CountStar(0),
+            (AggFunc::Count, Some(_), true) => Accumulator::CountDistinct(HashSet::new()),
+            (AggFunc::Count, Some(_), false) => Accumulator::Count(0),
+            (AggFunc::Sum, _, _) => Accumulator::Sum(None),
+            (AggFunc::Avg, _, _) => Accumulator::Avg {
+                sum: ExactSum::default(),
+                n: 0,
+            },
+            (AggFunc::Min, _, _) => Accumulator::Min(None),
+            (AggFunc::Max, _, _) => Accumulator::Max(None),
+        }
+    }
+
+    /// Fold in row `i` of the evaluated argument (`None` for `count(*)`).
+    /// Returns roughly how many bytes the state grew by.
+    fn update(&mut self, spec: &AggSpec, arg: Option<&Column>, i: usize) -> Result<usize> {
+        if let Accumulator::CountStar(n) = self {
+            *n += 1;
+            return Ok(0);
+        }
+
+        let Some(col) = arg.filter(|c| c.is_valid(i)) else {
+            return Ok(0);
+        };
+
+        match self {
+            Accumulator::CountStar(_) => {}
+            Accumulator::Count(n) => *n += 1,
+            Accumulator::CountDistinct(seen) => {
+                let v = col.value(i);
+                let size = scalar_bytes(&v);
+                if seen.insert(v) {
+                    return Ok(size);
+                }
+            }
+            Accumulator::Sum(sum) => add_numeric(spec, sum.get_or_insert_default(), col, i)?,
+            Accumulator::Avg { sum, n } => {
+                add_numeric(spec, sum, col, i)?;
+                *n += 1;
+            }
+            Accumulator::Min(cur) => keep_if(cur, col.value(i), Ordering::Less),
+            Accumulator::Max(cur) => keep_if(cur, col.value(i), Ordering::Greater),
+        }
+        Ok(0)
+    }
+
+    /// Combine with the state of the same aggregate from a later partition.
+    fn merge(&mut self, other: Accumulator) {
+        match (self, other) {
+            (Accumulator::CountStar(a), Accumulator::CountStar(b))
+            | (Accumulator::Count(a), Accumulator::Count(b)) => *a += b,
+            (Accumulator::CountDistinct(a), Accumulator::CountDistinct(b)) => a.extend(b),
+            (Accumulator::Sum(a), Accumulator::Sum(b)) => match (a.as_mut(), b) {
+                (Some(a), Some(b)) => a.merge(b),
+                (None, b) => *a = b,
+                (_, None) => {}
+            },
+            (Accumulator::Avg { sum, n }, Accumulator::Avg { sum: s2, n: n2 }) => {
+                sum.merge(s2);
+                *n += n2;
+            }
+            (Accumulator::Min(cur), Accumulator::Min(Some(v))) => keep_if(cur, v, Ordering::Less),
+            (Accumulator::Max(cur), Accumulator::Max(Some(v))) => {
+                keep_if(cur, v, Ordering::Greater)
+            }
+            _ => {}
+        }
+    }
+
+    /// Estimated bytes held outside the accumulator itself.
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Accumulator::CountDistinct(seen) => seen.iter().map(scalar_bytes).sum(),
+            _ => 0,
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Accumulator::CountStar(n) | Accumulator::Count(n) => put_u64(buf, *n as u64),
+            Accumulator::CountDistinct(seen) => {
+                put_u64(buf, seen.len() as u64);
+                for v in seen {
+                    put_scalar(buf, v);
+                }
+            }
+            Accumulator::Sum(None) => buf.push(0),
+            Accumulator::Sum(Some(sum)) => {
+                buf.push(1);
+                sum.encode(buf);
+            }
+            Accumulator::Avg { sum, n } => {
+                sum.encode(buf);
+                put_u64(buf, *n);
+            }
+            Accumulator::Min(v) | Accumulator::Max(v) => {
+                put_scalar(buf, v.as_ref().unwrap_or(&Scalar::Null))
+            }
+        }
+    }
+
+    fn decode(spec: &AggSpec, d: &mut Decoder) -> Result<Self> {
+        let mut acc = Accumulator::new(spec);
+        match &mut acc {
+            Accumulator::CountStar(n) | Accumulator::Count(n) => *n = d.u64()? as i64,
+            Accumulator::CountDistinct(seen) => {
+                for _ in 0..d.u64()? {
+                    seen.insert(d.scalar()?);
+                }
+            }
+            Accumulator::Sum(sum) => {
+                if d.u8()? == 1 {
+                    *sum = Some(ExactSum::decode(d)?);
+                }
+            }
+            Accumulator::Avg { sum, n } => {
+                *sum = ExactSum::decode(d)?;
+                *n = d.u64()?;
+            }
+            Accumulator::Min(v) | Accumulator::Max(v) => {
+                *v = Some(d.scalar()?).filter(|s| *s != Scalar::Null)
+            }
+        }
+        Ok(acc)
+    }
+
+    fn finish(self) -> Scalar {
+        match self {
+            Accumulator::CountStar(n) | Accumulator::Count(n) => Scalar::Int64(n),
+            Accumulator::CountDistinct(seen) => Scalar::Int64(seen.len() as i64),
+            Accumulator::Sum(sum) => sum.map_or(Scalar::Null, |s| Scalar::Float64(s.value())),
+            Accumulator::Avg { n: 0, .. } => Scalar::Null,
+            Accumulator::Avg { sum, n } => Scalar::Float64(sum.value() / n as f64),
+            Accumulator::Min(v) | Accumulator::Max(v) => v.unwrap_or(Scalar::Null),
+        }
+    }
+}
+
+// Replace `cur` with `v` if `v` compares as `wanted` against it; ties keep
+// the earlier value.
+fn keep_if(cur: &mut Option<Scalar>, v: Scalar, wanted: Ordering) {
+    if cur.as_ref().is_none_or(|c| v.cmp_value(c) == wanted) {
+        *cur = Some(v);
+    }
+}
+
+fn add_numeric(spec: &AggSpec, sum: &mut ExactSum, col: &Column, i: usize) -> Result<()> {
+    match &col.data {
+        ColumnData::Int64(v) => sum.add_int(v[i]),
+        ColumnData::Float64(v) => sum.add_float(v[i]),
+        _ => bail!(
+            "{} expects numeric input, got {}",
+            spec.alias,
+            col.value(i).to_json()
+        ),
+    }
+    Ok(())
+}
+
+// Rough per-group bookkeeping cost beyond keys and accumulators.
+const GROUP_OVERHEAD: usize = 64;
+
+fn scalar_bytes(v: &Scalar) -> usize {
+    std::mem::size_of::<Scalar>()
+        + match v {
+            Scalar::Utf8(s) => s.len(),
+            _ => 0,
+        }
+}
+
+/// A group's encoded key, key values, first input row and states.
+type Group = (Vec<u8>, Vec<Scalar>, u64, Vec<Accumulator>);
+
+/// Groups in first-seen order with one accumulator per aggregate.
+#[derive(Default)]
+struct GroupTable {
+    // Encoded key values -> group number
+    index: HashMap<Vec<u8>, usize>,
+    keys: Vec<Vec<Scalar>>,
+    // Input row each group was first seen at, to restore first-seen order
+    // once groups have been spilled and merged back by hash partition.
+    first: Vec<u64>,
+    states: Vec<Vec<Accumulator>>,
+    // Estimated heap footprint, checked against the memory limit.
+    mem_bytes: usize,
+}
+
+// Key value `i` of a column, encoded as `put_scalar` would.
+fn encode_key_part(col: &Column, i: usize, buf: &mut Vec<u8>) {
+    match &col.data {
+        ColumnData::Utf8(v) if col.is_valid(i) => put_str(buf, &v[i]),
+        _ => put_scalar(buf, &col.value(i)),
+    }
+}
+
+impl GroupTable {
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Group number for an encoded key seen at input row `row`, creating
+    /// the group if it is new.
+    fn group(
+        &mut self,
+        encoded: &[u8],
+        key: impl FnOnce() -> Vec<Scalar>,
+        row: u64,
+        aggs: &[AggSpec],
+    ) -> usize {
+        if let Some(&g) = self.index.get(encoded) {
+            self.first[g] = self.first[g].min(row);
+            return g;
+        }
+        let key = key();
+        self.mem_bytes += GROUP_OVERHEAD
+            + encoded.len()
+            + key.iter().map(scalar_bytes).sum::<usize>()
+            + aggs.len() * std::mem::size_of::<Accumulator>()
+            + std::mem::size_of::<u64>();
+
+        self.index.insert(encoded.to_vec(), self.keys.len());
+        self.keys.push(key);
+        self.first.push(row);
+        self.states
+            .push(aggs.iter().map(Accumulator::new).collect());
+        self.keys.len() - 1
+    }
+
+    /// Fold in every row of `batch`, whose first row is input row
+    /// `first_row`, calling `after_row` after each so the caller can react
+    /// to the table's size as it grows.
+    fn update(
+        &mut self,
+        batch: &RecordBatch,
+        first_row: u64,
+        group_keys: &[String],
+        aggs: &[AggSpec],
+        mut after_row: impl FnMut(&mut GroupTable) -> Result<()>,
+    ) -> Result<()> {
+        let key_cols = group_keys
+            .iter()
+            .map(|k| {
+                batch
+                    .column_by_name(k)
+                    .ok_or_else(|| anyhow!("unknown group key `{k}`"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let arg_cols = aggs
+            .iter()
+            .map(|a| a.arg.as_ref().map(|e| e.eval(batch)).transpose())
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut buf = Vec::new();
+        for i in 0..batch.num_rows() {
+            buf.clear();
+            for c in &key_cols {
+                encode_key_part(c, i, &mut buf);
+            }
+            let key = || key_cols.iter().map(|c| c.value(i)).collect();
+            let g = self.group(&buf, key, first_row + i as u64, aggs);
+            for ((acc, agg), arg) in self.states[g].iter_mut().zip(aggs).zip(&arg_cols) {
+                self.mem_bytes += acc.update(agg, arg.as_ref(), i)?;
+            }
+            after_row(self)?;
+        }
+        Ok(())
+    }
+
+    fn merge_group(
+        &mut self,
+        encoded: &[u8],
+        key: impl FnOnce() -> Vec<Scalar>,
+        row: u64,
+        states: Vec<Accumulator>,
+        aggs: &[AggSpec],
+    ) {
+        let g = self.group(encoded, key, row, aggs);
+        for (acc, s) in self.states[g].iter_mut().zip(states) {
+            // Distinct sets may overlap; over-counting is the safe side.
+            self.mem_bytes += s.heap_bytes();
+            acc.merge(s);
+        }
+    }
+
+    /// Groups as (encoded key, key values, first row, states), in the
+    /// order they were created.
+    fn into_groups(self) -> impl Iterator<Item = Group> {
+        let mut encoded: Vec<(Vec<u8>, usize)> = self.index.into_iter().collect();
+        encoded.sort_unstable_by_key(|(_, g)| *g);
+        encoded
+            .into_iter()
+            .zip(self.keys)
+            .zip(self.first)
+            .zip(self.states)
+            .map(|((((e, _), k), f), s)| (e, k, f, s))
+    }
+
+    /// Fold in a table built over later input, whose first row is input row
+    /// `first_row`; groups new to `self` are appended in `other`'s order, so
+    /// merging partitions in input order gives the same groups, in the same
+    /// order, as one serial pass.
+    fn merge(&mut self, other: GroupTable, first_row: u64, aggs: &[AggSpec]) {
+        for (encoded, key, row, states) in other.into_groups() {
+            self.merge_group(&encoded, || key, first_row + row, states, aggs);
+        }
+    }
+
+    fn into_batch(self, schema: &SchemaRef) -> Result<RecordBatch> {
+        let num_rows = self.keys.len();
+        let mut columns: Vec<Column> = schema
+            .fields
+            .iter()
+            .map(|f| Column::with_capacity(f.dtype, num_rows))
+            .collect();
+
+        // Group-by columns first, then aggregate outputs
+        for (key, accs) in self.keys.into_iter().zip(self.states) {
+            let values = key
+                .into_iter()
+                .chain(accs.into_iter().map(Accumulator::finish));
+            for (col, v) in columns.iter_mut().zip(values) {
+                col.push(v)?;
+            }
+        }
+        Ok(RecordBatch::new(schema.clone(), columns, num_rows))
+    }
+}
+
+// Spilled groups are hash-partitioned so each partition can be merged back
+// on its own, needing roughly 1/SPILL_PARTITIONS of the memory.
+const SPILL_PARTITIONS: usize = 16;
+
+// How many times a partition too big to merge within the limit is split
+// again; past this it is merged in memory whatever its size.
+const MAX_SPILL_DEPTH: u32 = 4;
+
+/// Group states written to disk. Each run is one spilled table, split into
+/// one file per hash partition of the group key.
+#[derive(Default)]
+struct Spill {
+    /// How many times these groups have already been split; each level
+    /// hashes keys with a different seed so a partition that is split again
+    /// spreads over new partitions.
+    depth: u32,
+    dirs: Vec<SpillDir>,
+    runs: Vec<Run>,
+    groups: u64,
+    bytes: u64,
+}
+
+/// One spilled table: a file per non-empty hash partition. Group first rows
+/// are stored relative to `first_row`, where the table's input started.
+struct Run {
+    first_row: u64,
+    files: Vec<Option<PathBuf>>,
+}
+
+impl Spill {
+    fn partition_of(&self, encoded: &[u8]) -> usize {
+        let mut h = DefaultHasher::new();
+        self.depth.hash(&mut h);
+        encoded.hash(&mut h);
+        h.finish() as usize % SPILL_PARTITIONS
+    }
+
+    /// Stream `table` to disk, each group framed by its length so it can be
+    /// read back one at a time.
+    fn write_run(&mut self, table: GroupTable) -> Result<()> {
+        if self.dirs.is_empty() {
+            self.dirs.push(SpillDir::new("aggregate")?);
+        }
+
+        let mut files: Vec<Option<(PathBuf, BufWriter<File>)>> =
+            (0..SPILL_PARTITIONS).map(|_| None).collect();
+        let mut buf = Vec::new();
+        for (encoded, _, row, states) in table.into_groups() {
+            buf.clear();
+            put_u64(&mut buf, row);
+            put_u64(&mut buf, encoded.len() as u64);
+            buf.extend_from_slice(&encoded);
+            for acc in &states {
+                acc.encode(&mut buf);
+            }
+
+            let file = match &mut files[self.partition_of(&encoded)] {
+                Some((_, out)) => out,
+                slot => {
+                    let path = self.dirs.last_mut().expect("spill directory").next_file();
+                    let out = File::create(&path).with_context(|| {
+                        format!("Failed to create spill file {}", path.display())
+                    })?;
+                    &mut slot.insert((path, BufWriter::new(out))).1
+                }
+            };
+            file.write_all(&(buf.len() as u64).to_le_bytes())
+                .and_then(|_| file.write_all(&buf))
+                .context("Failed to write spill file")?;
+            self.bytes += 8 + buf.len() as u64;
+            self.groups += 1;
+        }
+
+        let mut run = Run {
+            first_row: 0,
+            files: Vec::with_capacity(SPILL_PARTITIONS),
+        };
+        for file in files {
+            run.files.push(match file {
+                Some((path, mut out)) => {
+                    out.flush().context("Failed to write spill file")?;
+                    Some(path)
+                }
+                None => None,
+            });
+        }
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Merge partition `p` of every run, in run order, reading one group at
+    /// a time. Past `limit` the merged groups spill again, split with the
+    /// next level's seed.
+    fn read_partition(
+        &self,
+        p: usize,
+        n_keys: usize,
+        aggs: &[AggSpec],
+        limit: Option<usize>,
+    ) -> Result<SpillingTable> {
+        let limit = limit.filter(|_| self.depth < MAX_SPILL_DEPTH);
+        let mut table = SpillingTable::new(limit);
+        table.spill.depth = self.depth + 1;
+
+        let mut buf = Vec::new();
+        for run in &self.runs {
+            let Some(path) = &run.files[p] else { continue };
+            let file = File::open(path)
+                .with_context(|| format!("Failed to read spill file {}", path.display()))?;
+            let mut input = BufReader::new(file);
+            let mut frame = [0u8; 8];
+            loop {
+                match input.read_exact(&mut frame) {
+                    Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                    r => r.context("truncated spill file")?,
+                }
+                buf.resize(u64::from_le_bytes(frame) as usize, 0);
+                input.read_exact(&mut buf).context("truncated spill file")?;
+
+                let mut d = Decoder::new(&buf);
+                let row = run.first_row + d.u64()?;
+                let len = d.u64()? as usize;
+                let encoded = d.bytes(len)?;
+                let states = aggs
+                    .iter()
+                    .map(|a| Accumulator::decode(a, &mut d))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let mut kd = Decoder::new(encoded);
+                let key = (0..n_keys)
+                    .map(|_| kd.scalar())
+                    .collect::<Result<Vec<_>>>()?;
+                table.table.merge_group(encoded, || key, row, states, aggs);
+                table.spill_if_full()?;
+            }
+        }
+        Ok(table)
+    }
+}
+
+/// Group table that moves its groups to disk whenever its estimated size
+/// passes `limit`.
+struct SpillingTable {
+    table: GroupTable,
+    limit: Option<usize>,
+    spill: Spill,
+    rows: u64,
+    peak_mem: usize,
+}
+
+impl SpillingTable {
+    fn new(limit: Option<usize>) -> Self {
+        Self {
+            table: GroupTable::default(),
+            limit,
+            spill: Spill::default(),
+            rows: 0,
+            peak_mem: 0,
+        }
+    }
+
+    fn update(
+        &mut self,
+        batch: &RecordBatch,
+        group_keys: &[String],
+        aggs: &[AggSpec],
+    ) -> Result<()> {
+        let (limit, spill, peak_mem) = (self.limit, &mut self.spill, &mut self.peak_mem);
+        self.table
+            .update(batch, self.rows, group_keys, aggs, |table| {
+                *peak_mem = (*peak_mem).max(table.mem_bytes);
+                if limit.is_some_and(|l| table.mem_bytes > l) {
+                    spill.write_run(std::mem::take(table))?;
+                }
+                Ok(())
+            })?;
+        self.rows += batch.num_rows() as u64;
+        Ok(())
+    }
+
+    fn spill_if_full(&mut self) -> Result<()> {
+        self.peak_mem = self.peak_mem.max(self.table.mem_bytes);
+        if self.limit.is_some_and(|l| self.table.mem_bytes > l) {
+            self.spill.write_run(std::mem::take(&mut self.table))?;
+        }
+        Ok(())
+    }
+
+    /// Fold in the table of a later partition, keeping everything in input
+    /// order: if `other` spilled, what is in memory here goes to disk first.
+    fn absorb(&mut self, mut other: SpillingTable, aggs: &[AggSpec]) -> Result<()> {
+        let first_row = self.rows;
+        self.rows += other.rows;
+        if other.spill.runs.is_empty() {
+            self.table.merge(other.table, first_row, aggs);
+            return self.spill_if_full();
+        }
+
+        if !self.table.is_empty() {
+            self.spill.write_run(std::mem::take(&mut self.table))?;
+        }
+        if !other.table.is_empty() {
+            other.spill.write_run(other.table)?;
+        }
+        for run in &mut other.spill.runs {
+            run.first_row += first_row;
+        }
+        self.spill.dirs.append(&mut other.spill.dirs);
+        self.spill.runs.append(&mut other.spill.runs);
+        self.spill.groups += other.spill.groups;
+        self.spill.bytes += other.spill.bytes;
+        Ok(())
+    }
+}
+
+/// Where the aggregate reads its input from.
+enum AggInput {
+    Serial(Box<dyn ExecNode>),
+    /// Aggregated per partition on separate threads, then merged.
+    /// `template` is an unexecuted copy of the partition pipeline, kept to
+    /// describe it.
+    Partitioned {
+        template: Box<dyn ExecNode>,
+        parts: Vec<PartitionFn>,
+        threads: usize,
+    },
+}
+
+/// Where finished groups come from once the input is consumed.
+enum AggOutput {
+    Pending,
+    InMemory(Option<GroupTable>),
+    Spilled(FinishedRuns),
+}
+
+/// Finished rows of spilled groups, one run per merged hash partition,
+/// each sorted by the row its group was first seen at. Merging the runs on
+/// that row gives the groups in the same order as an in-memory pass.
+struct FinishedRuns {
+    _dir: SpillDir,
+    readers: Vec<RowReader>,
+    // Next row of each run, led by its first-seen row number.
+    heads: Vec<Option<Vec<Scalar>>>,
+}
+
+impl FinishedRuns {
+    fn next_row(&mut self) -> Result<Option<Vec<Scalar>>> {
+        let first_row = |row: &Vec<Scalar>| match row[0] {
+            Scalar::Int64(r) => r,
+            _ => unreachable!("finished rows start with their first-seen row"),
+        };
+        let best = self
+            .heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, h)| Some((first_row(h.as_ref()?), i)))
+            .min();
+        let Some((_, i)) = best else { return Ok(None) };
+        let next = self.readers[i].next_row()?;
+        let mut row = std::mem::replace(&mut self.heads[i], next).expect("run head");
+        row.remove(0);
+        Ok(Some(row))
+    }
 }
 
 pub struct HashAggregateExec {
-    input: Box<dyn ExecNode>,
+    input: AggInput,
     group_keys: Vec<String>,
     aggs: Vec<AggSpec>,
+    memory_limit: Option<usize>,
+    schema: SchemaRef,
 
-    built: bool,
-    out_rows: Vec<Row>,
-    idx: usize,
+    output: AggOutput,
+    input_rows: u64,
+    peak_mem: usize,
+    groups: u64,
+    spill_runs: u64,
+    spilled_groups: u64,
+    spill_bytes: u64,
 }
 
 impl HashAggregateExec {
-    pub fn new(input: Box<dyn ExecNode>, group_keys: Vec<String>, aggs: Vec<AggSpec>) -> Self {
+    /// Past `memory_limit` bytes of estimated group state, groups are
+    /// spilled to temporary files and merged back at the end.
+    pub fn new(
+        input: Box<dyn ExecNode>,
+        group_keys: Vec<String>,
+        aggs: Vec<AggSpec>,
+        memory_limit: Option<usize>,
+    ) -> Result<Self> {
+        let schema = output_schema(&input.schema(), &group_keys, &aggs)?;
+        Ok(Self::with_input(
+            AggInput::Serial(input),
+            schema,
+            group_keys,
+            aggs,
+            memory_limit,
+        ))
+    }
+
+    /// Aggregate each partition on its own thread, then merge the partial
+    /// results in partition order. `template` is the pipeline every
+    /// partition runs, built but never executed; each thread gets an equal
+    /// share of the memory limit.
+    pub fn partitioned(
+        partitions: Vec<PartitionFn>,
+        template: Box<dyn ExecNode>,
+        group_keys: Vec<String>,
+        aggs: Vec<AggSpec>,
+        memory_limit: Option<usize>,
+    ) -> Result<Self> {
+        let schema = output_schema(&template.schema(), &group_keys, &aggs)?;
+        Ok(Self::with_input(
+            AggInput::Partitioned {
+                template,
+                threads: partitions.len(),
+                parts: partitions,
+            },
+            schema,
+            group_keys,
+            aggs,
+            memory_limit,
+        ))
+    }
+
+    fn with_input(
+        input: AggInput,
+        schema: SchemaRef,
+        group_keys: Vec<String>,
+        aggs: Vec<AggSpec>,
+        memory_limit: Option<usize>,
+    ) -> Self {
         Self {
             input,
             group_keys,
             aggs,
-            built: false,
-            out_rows: Vec::new(),
-            idx: 0,
+            memory_limit,
+            schema,
+            output: AggOutput::Pending,
+            input_rows: 0,
+            peak_mem: 0,
+            groups: 0,
+            spill_runs: 0,
+            spilled_groups: 0,
+            spill_bytes: 0,
         }
     }
 
     fn build(&mut self) -> Result<()> {
-        // key: serialized group values
-        // state: sums/counts per group
-        #[derive(Default)]
-        struct State {
-            sums: Vec<f64>,
-            counts: Vec<u64>,
-            key_vals: Vec<JsonValue>,
-        }
-
-        let mut map: HashMap<String, State> = HashMap::new();
-
-        while let Some(row) = self.input.next_row()? {
-            let mut key_vals: Vec<JsonValue> = Vec::with_capacity(self.group_keys.len());
-            for k in &self.group_keys {
-                key_vals.push(row.get(k).cloned().unwrap_or(JsonValue::Null));
+        let (group_keys, aggs) = (&self.group_keys, &self.aggs);
+        let aggregate = |input: &mut dyn ExecNode, limit: Option<usize>| {
+            let mut table = SpillingTable::new(limit);
+            while let Some(batch) = input.next_batch()? {
+                table.update(&batch, group_keys, aggs)?;
             }
+            Ok::<_, anyhow::Error>(table)
+        };
 
-            let key_str = serde_json::to_string(&key_vals)
-                .map_err(|e| anyhow!("Failed to serialize group key: {e}"))?;
-
-            let entry = map.entry(key_str).or_insert_with(|| State {
-                sums: vec![0.0; self.aggs.len()],
-                counts: vec![0; self.aggs.len()],
-                key_vals: key_vals.clone(),
-            });
+        let mut table = match &mut self.input {
+            AggInput::Serial(input) => aggregate(input.as_mut(), self.memory_limit)?,
+            AggInput::Partitioned { parts, .. } => {
+                let share = self.memory_limit.map(|l| l / parts.len().max(1));
+                let partials = std::thread::scope(|s| {
+                    let handles = parts
+                        .drain(..)
+                        .map(|make| s.spawn(move || aggregate(make()?.as_mut(), share)))
+                        .collect::<Vec<_>>();
+                    handles
+                        .into_iter()
+                        .map(|h| h.join().expect("aggregation thread panicked"))
+                        .collect::<Result<Vec<_>>>()
+                })?;
 
-            // Update aggregates
-            for (i, agg) in self.aggs.iter().enumerate() {
-                match agg.func {
-                    AggFunc::Count => {
-                        entry.counts[i] += 1;
-                    }
-                    AggFunc::Sum => {
-                        let v = row.get(&agg.col).cloned().unwrap_or(JsonValue::Null);
-                        let n = v.as_f64().unwrap_or(0.0);
-                        entry.sums[i] += n;
-                    }
+                // Every thread's table is alive until they are all merged.
+                let thread_peaks = partials.iter().map(|t| t.peak_mem).sum::<usize>();
+                let mut merged = SpillingTable::new(self.memory_limit);
+                for t in partials {
+                    merged.absorb(t, aggs)?;
                 }
+                merged.peak_mem = merged.peak_mem.max(thread_peaks);
+                merged
             }
-        }
+        };
 
-        // Convert states to rows
-        let mut out = Vec::with_capacity(map.len());
-        for (_k, st) in map {
-            let mut r: Row = Row::new();
+        // A global aggregate over no rows still yields one row (count = 0, sum = null)
+        if self.group_keys.is_empty() && table.table.is_empty() && table.spill.runs.is_empty() {
+            table.table.group(&[], Vec::new, 0, aggs);
+        }
 
-            // group-by columns first
-            for (i, col) in self.group_keys.iter().enumerate() {
-                r.insert(
-                    col.clone(),
-                    st.key_vals.get(i).cloned().unwrap_or(JsonValue::Null),
-                );
+        self.input_rows = table.rows;
+        self.peak_mem = table.peak_mem;
+        self.output = if table.spill.runs.is_empty() {
+            AggOutput::InMemory(Some(table.table))
+        } else {
+            if !table.table.is_empty() {
+                table.spill.write_run(table.table)?;
             }
+            AggOutput::Spilled(self.merge_spill(table.spill)?)
+        };
+        Ok(())
+    }
 
-            // then aggregate outputs
-            for (i, agg) in self.aggs.iter().enumerate() {
-                let val = match agg.func {
-                    AggFunc::Count => JsonValue::from(st.counts[i] as i64),
-                    AggFunc::Sum => JsonValue::from(st.sums[i]),
-                };
-                r.insert(agg.alias.clone(), val);
+    /// Merge every hash partition of `spill` back, one at a time, writing
+    /// each partition's finished groups out as a run in first-seen order.
+    /// A partition that spills again while merging is finished first.
+    fn merge_spill(&mut self, spill: Spill) -> Result<FinishedRuns> {
+        let mut dir = SpillDir::new("aggregate")?;
+        let mut runs = Vec::new();
+        let mut stack = Vec::new();
+        self.count_spill(&spill);
+        stack.push((spill, 0));
+
+        while let Some((spill, next)) = stack.last_mut() {
+            if *next == SPILL_PARTITIONS {
+                stack.pop();
+                continue;
+            }
+            *next += 1;
+            let mut merged = spill.read_partition(
+                *next - 1,
+                self.group_keys.len(),
+                &self.aggs,
+                self.memory_limit,
+            )?;
+            self.peak_mem = self.peak_mem.max(merged.peak_mem);
+            if !merged.spill.runs.is_empty() {
+                if !merged.table.is_empty() {
+                    merged.spill.write_run(merged.table)?;
+                }
+                self.count_spill(&merged.spill);
+                stack.push((merged.spill, 0));
+                continue;
+            }
+            if merged.table.is_empty() {
+                continue;
             }
 
-            out.push(r);
+            self.groups += merged.table.keys.len() as u64;
+            self.peak_mem = self.peak_mem.max(merged.table.mem_bytes);
+            let mut groups: Vec<Group> = merged.table.into_groups().collect();
+            groups.sort_unstable_by_key(|(_, _, row, _)| *row);
+            let path = dir.next_file();
+            let mut out = RowWriter::create(&path)?;
+            for (_, key, row, states) in groups {
+                let finished = std::iter::once(Scalar::Int64(row as i64))
+                    .chain(key)
+                    .chain(states.into_iter().map(Accumulator::finish))
+                    .collect::<Vec<_>>();
+                out.write_row(&finished)?;
+            }
+            self.spill_bytes += out.finish()?;
+            runs.push(path);
         }
 
-        self.out_rows = out;
-        self.built = true;
-        Ok(())
+        let width = 1 + self.schema.fields.len();
+        let mut readers = runs
+            .iter()
+            .map(|p| RowReader::open(p, width))
+            .collect::<Result<Vec<_>>>()?;
+        let heads = readers
+            .iter_mut()
+            .map(|r| r.next_row())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(FinishedRuns {
+            _dir: dir,
+            readers,
+            heads,
+        })
+    }
+
+    fn count_spill(&mut self, spill: &Spill) {
+        self.spill_runs += spill.runs.len() as u64;
+        self.spilled_groups += spill.groups;
+        self.spill_bytes += spill.bytes;
+    }
+}
+
+fn output_schema(input: &Schema, group_keys: &[String], aggs: &[AggSpec]) -> Result<SchemaRef> {
+    let mut fields = Vec::with_capacity(group_keys.len() + aggs.len());
+    for k in group_keys {
+        let f = input
+            .field(k)
+            .ok_or_else(|| anyhow!("unknown group key `{k}`"))?;
+        fields.push(f.clone());
     }
+    for a in aggs {
+        fields.push(Field {
+            name: a.alias.clone(),
+            dtype: a.data_type(input)?,
+        });
+    }
+    Ok(Arc::new(Schema { fields }))
 }
 
 impl ExecNode for HashAggregateExec {
-    fn next_row(&mut self) -> Result<Option<Row>> {
-        if !self.built {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        if matches!(self.output, AggOutput::Pending) {
             self.build()?;
         }
 
-        if self.idx >= self.out_rows.len() {
-            return Ok(None);
+        match &mut self.output {
+            AggOutput::Pending => unreachable!("aggregate already built"),
+            AggOutput::InMemory(table) => match table.take() {
+                Some(t) if !t.is_empty() => {
+                    self.groups += t.keys.len() as u64;
+                    self.peak_mem = self.peak_mem.max(t.mem_bytes);
+                    t.into_batch(&self.schema).map(Some)
+                }
+                _ => Ok(None),
+            },
+            AggOutput::Spilled(runs) => {
+                let mut columns: Vec<Column> = self
+                    .schema
+                    .fields
+                    .iter()
+                    .map(|f| Column::with_capacity(f.dtype, BATCH_SIZE))
+                    .collect();
+                let mut num_rows = 0;
+                while num_rows < BATCH_SIZE
+                    && let Some(row) = runs.next_row()?
+                {
+                    for (col, v) in columns.iter_mut().zip(row) {
+                        col.push(v)?;
+                    }
+                    num_rows += 1;
+                }
+                Ok(
+                    (num_rows > 0)
+                        .then(|| RecordBatch::new(self.schema.clone(), columns, num_rows)),
+                )
+            }
         }
+    }
 
-        let r = self.out_rows[self.idx].clone();
-        self.idx += 1;
-        Ok(Some(r))
+    fn name(&self) -> &'static str {
+        "HashAggregate"
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        match &self.input {
+            AggInput::Serial(input) => vec![input.as_ref()],
+            AggInput::Partitioned { template, .. } => vec![template.as_ref()],
+        }
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        let aggs = self
+            .aggs
+            .iter()
+            .map(|a| Detail::new(&a.alias, explain::agg_json(a)));
+        let mut d = vec![
+            (
+                "group_keys",
+                Detail::list(self.group_keys.iter().map(Detail::text)),
+            ),
+            ("aggs", Detail::list(aggs)),
+        ];
+        if let AggInput::Partitioned { threads, .. } = &self.input {
+            d.push(("threads", (*threads).into()));
+        }
+        d.push(("memory_limit", Detail::memory_limit(self.memory_limit)));
+        if self.memory_limit.is_some() {
+            d.push(("spill_partitions", SPILL_PARTITIONS.into()));
+        }
+        d
+    }
+
+    fn input_rows(&self) -> Option<u64> {
+        match self.input {
+            AggInput::Serial(_) => None,
+            AggInput::Partitioned { .. } => Some(self.input_rows),
+        }
+    }
+
+    fn peak_memory(&self) -> usize {
+        self.peak_mem
+    }
+
+    fn metrics(&self) -> Vec<(&'static str, u64)> {
+        let mut m = vec![("groups", self.groups)];
+        if self.spill_runs > 0 {
+            m.extend([
+                ("spill_runs", self.spill_runs),
+                ("spilled_groups", self.spilled_groups),
+                ("spill_bytes", self.spill_bytes),
+            ]);
+        }
+        m
     }
 }
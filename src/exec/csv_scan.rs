@@ -1,47 +1,583 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use csv::StringRecord;
-use std::fs::File;
+use serde_json::{Value as JsonValue, json};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::Arc;
 
-use crate::exec::ExecNode;
-use crate::value::{Row, parse_cell};
+use crate::ast::PredExpr;
+use crate::batch::{BATCH_SIZE, Column, RecordBatch, Scalar};
+use crate::exec::{Detail, ExecNode, cmp_f64, cmp_ord};
+use crate::explain;
+use crate::schema::{DataType, Field, Schema, SchemaRef};
+use crate::value::cmp_json;
 
 pub struct CsvScan {
-    headers: Vec<String>,
-    rdr: csv::Reader<File>,
+    path: String,
+    alias: Option<String>,
+    range: Option<Range<u64>>,
+    file_schema: Schema,
+    // Positions in `file_schema` of the columns read, in file order.
+    projection: Vec<usize>,
+    // The columns read, with names qualified by the alias, if any.
+    schema: SchemaRef,
+    // Pushed-down predicate, and the same compiled against file columns.
+    filter: Option<(PredExpr, RawPred)>,
+    rejected: u64,
+    limit: Option<usize>,
+    // Records still to output under `limit`.
+    remaining: usize,
+    strict: bool,
+    // Byte offset the reader started at, for error line numbers.
+    start: u64,
+    rdr: csv::Reader<QuoteParity<io::Take<File>>>,
+    record: StringRecord,
 }
 
 impl CsvScan {
-    pub fn new(path: String) -> Result<Self> {
-        let file = File::open(&path).with_context(|| format!("Failed to open CSV: {path}"))?;
-        let mut rdr = csv::Reader::from_reader(file);
+    /// With an `alias`, output columns are named `alias.col`. Only the file
+    /// columns in `columns` (all for `None`) are parsed and output. Cells are
+    /// parsed into the schema's column types; cells that do not fit are null,
+    /// or an error when `strict`. Records `filter` rejects are skipped
+    /// before any of their columns are parsed. With a `range` (from
+    /// `split_ranges`), only the records in those bytes are read.
+    pub fn new(
+        path: String,
+        alias: Option<String>,
+        schema: Schema,
+        columns: Option<Vec<String>>,
+        filter: Option<PredExpr>,
+        strict: bool,
+        range: Option<Range<u64>>,
+    ) -> Result<Self> {
+        let mut file = File::open(&path).with_context(|| format!("Failed to open CSV: {path}"))?;
 
-        let headers = rdr
-            .headers()
-            .context("CSV missing headers row")?
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
+        let (rdr, start) = match range.clone() {
+            Some(r) => {
+                file.seek(SeekFrom::Start(r.start))?;
+                let rdr = csv::ReaderBuilder::new()
+                    .has_headers(false)
+                    .from_reader(QuoteParity {
+                        inner: file.take(r.end - r.start),
+                        odd: false,
+                    });
+                (rdr, r.start)
+            }
+            None => {
+                let mut rdr = csv::Reader::from_reader(QuoteParity {
+                    inner: file.take(u64::MAX),
+                    odd: false,
+                });
+                let file_headers = rdr.headers().context("CSV missing headers row")?;
+                let matches_schema = file_headers.len() == schema.fields.len()
+                    && file_headers
+                        .iter()
+                        .zip(&schema.fields)
+                        .all(|(h, f)| h == f.name);
+                if !matches_schema {
+                    bail!("CSV header of {path} no longer matches its planned schema");
+                }
+                (rdr, 0)
+            }
+        };
+
+        let projection = (0..schema.fields.len())
+            .filter(|&i| {
+                columns
+                    .as_ref()
+                    .is_none_or(|c| c.contains(&schema.fields[i].name))
+            })
+            .collect::<Vec<_>>();
+        let qualified = Schema {
+            fields: projection
+                .iter()
+                .map(|&i| &schema.fields[i])
+                .map(|f| Field {
+                    name: match &alias {
+                        Some(a) => format!("{a}.{}", f.name),
+                        None => f.name.clone(),
+                    },
+                    dtype: f.dtype,
+                })
+                .collect(),
+        };
+
+        let filter = match filter {
+            Some(pred) => {
+                let raw = RawPred::compile(&pred, &schema, alias.as_deref())
+                    .with_context(|| format!("Cannot filter {path}"))?;
+                Some((pred, raw))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            path,
+            alias,
+            range,
+            file_schema: schema,
+            projection,
+            schema: Arc::new(qualified),
+            filter,
+            rejected: 0,
+            limit: None,
+            remaining: usize::MAX,
+            strict,
+            start,
+            rdr,
+            record: StringRecord::new(),
+        })
+    }
 
-        Ok(Self { headers, rdr })
+    /// Stop reading once `limit` records have been output, if set.
+    pub fn with_limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self.remaining = limit.unwrap_or(usize::MAX);
+        self
     }
 
-    fn record_to_row(&self, rec: &StringRecord) -> Row {
-        let mut row = Row::new();
-        for (i, key) in self.headers.iter().enumerate() {
-            let cell = rec.get(i).unwrap_or("");
-            row.insert(key.clone(), parse_cell(cell));
+    // A cell of file column `i` that does not fit its type: an error when
+    // strict, otherwise read as null.
+    fn bad_cell(&self, i: usize, cell: &str) -> Result<()> {
+        if !self.strict {
+            return Ok(());
         }
-        row
+        let field = &self.file_schema.fields[i];
+        let line = self.record.position().map(|p| p.line()).unwrap_or(0)
+            + lines_before(&self.path, self.start)?;
+        bail!(
+            "{}:{line}: column `{}` expects {}, got {cell:?}",
+            self.path,
+            field.name,
+            field.dtype
+        );
     }
 }
 
 impl ExecNode for CsvScan {
-    fn next_row(&mut self) -> Result<Option<Row>> {
-        let mut rec = StringRecord::new();
-        let ok = self.rdr.read_record(&mut rec)?;
-        if !ok {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let fields = &self.file_schema.fields;
+        let mut columns: Vec<Column> = self
+            .projection
+            .iter()
+            .map(|&i| Column::with_capacity(fields[i].dtype, BATCH_SIZE))
+            .collect();
+
+        // Records past the limit are never read.
+        let max = self.remaining.min(BATCH_SIZE);
+        let mut rows = 0;
+        while rows < max && self.rdr.read_record(&mut self.record)? {
+            if let Some((_, raw)) = &self.filter
+                && !raw.eval(&self.record, &|i, cell| self.bad_cell(i, cell))?
+            {
+                self.rejected += 1;
+                continue;
+            }
+
+            for (col, &i) in columns.iter_mut().zip(&self.projection) {
+                let cell = self.record.get(i).unwrap_or("");
+                if !col.push_cell(cell) {
+                    self.bad_cell(i, cell)?;
+                    col.push_null();
+                }
+            }
+            rows += 1;
+        }
+
+        // A range that ends inside quotes means the next range was split
+        // off mid-field and read garbage.
+        if rows < max
+            && let Some(r) = &self.range
+            && self.rdr.get_ref().odd
+            && fs::metadata(&self.path).is_ok_and(|m| r.end < m.len())
+        {
+            bail!(
+                "{}: cannot split at byte {} for parallel scans, it is inside a quoted field; \
+                 rerun with --threads 1",
+                self.path,
+                r.end
+            );
+        }
+        if rows == 0 {
             return Ok(None);
         }
-        Ok(Some(self.record_to_row(&rec)))
+        self.remaining -= rows;
+        Ok(Some(RecordBatch::new(self.schema.clone(), columns, rows)))
+    }
+
+    fn name(&self) -> &'static str {
+        "CsvScan"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        let mut d = vec![(
+            "path",
+            Detail::new(format!("\"{}\"", self.path), self.path.clone()),
+        )];
+        if let Some(a) = &self.alias {
+            d.push(("as", Detail::text(a)));
+        }
+        let columns = self
+            .projection
+            .iter()
+            .map(|&i| Detail::field(&self.file_schema.fields[i]));
+        d.push(("columns", Detail::list(columns)));
+        if let Some((pred, _)) = &self.filter {
+            d.push(("filter", Detail::new(pred, explain::pred_json(pred))));
+        }
+        if let Some(n) = self.limit {
+            d.push(("limit", n.into()));
+        }
+        d.push(("strict", self.strict.into()));
+        if let Some(r) = &self.range {
+            d.push((
+                "bytes",
+                Detail::new(
+                    format!("{}..{}", r.start, r.end),
+                    json!({"start": r.start, "end": r.end}),
+                ),
+            ));
+        }
+        d
+    }
+
+    fn metrics(&self) -> Vec<(&'static str, u64)> {
+        match self.filter {
+            Some(_) => vec![("rejected", self.rejected)],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// A predicate over file column positions, evaluated on a record's raw
+/// cells. Each cell is read as its column's type, with the same comparison
+/// rules as `predicate_mask` applies to parsed batches.
+enum RawPred {
+    And(Vec<RawPred>),
+    Or(Vec<RawPred>),
+    Not(Box<RawPred>),
+    Cmp(RawCmp),
+}
+
+struct RawCmp {
+    index: usize,
+    dtype: DataType,
+    op: String,
+    val: JsonValue,
+    // The literal as text, for utf8 columns.
+    text: String,
+    // Result for a null cell; also rejects unknown operators up front.
+    if_null: bool,
+}
+
+impl RawPred {
+    fn compile(pred: &PredExpr, schema: &Schema, alias: Option<&str>) -> Result<RawPred> {
+        let all = |items: &[PredExpr]| {
+            items
+                .iter()
+                .map(|p| RawPred::compile(p, schema, alias))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(match pred {
+            PredExpr::And { and } => RawPred::And(all(and)?),
+            PredExpr::Or { or } => RawPred::Or(all(or)?),
+            PredExpr::Not { not } => RawPred::Not(Box::new(RawPred::compile(not, schema, alias)?)),
+            PredExpr::Compare(c) => bail!("cannot check `{c}` before parsing records"),
+            PredExpr::Cmp(p) => {
+                let name = match alias {
+                    Some(a) => p
+                        .col
+                        .strip_prefix(a)
+                        .and_then(|c| c.strip_prefix('.'))
+                        .unwrap_or(&p.col),
+                    None => &p.col,
+                };
+                let Some(index) = schema.index_of(name) else {
+                    bail!("unknown column `{}`", p.col);
+                };
+                RawPred::Cmp(RawCmp {
+                    index,
+                    dtype: schema.fields[index].dtype,
+                    op: p.op.clone(),
+                    val: p.val.clone(),
+                    text: p
+                        .val
+                        .as_str()
+                        .map_or_else(|| p.val.to_string(), str::to_string),
+                    if_null: cmp_json(&JsonValue::Null, &p.op, &p.val)?,
+                })
+            }
+        })
+    }
+
+    /// Whether `record` passes. `bad_cell` is told about cells that do not
+    /// fit their column type; if it returns `Ok`, they read as null.
+    fn eval(
+        &self,
+        record: &StringRecord,
+        bad_cell: &dyn Fn(usize, &str) -> Result<()>,
+    ) -> Result<bool> {
+        Ok(match self {
+            RawPred::And(items) => {
+                for p in items {
+                    if !p.eval(record, bad_cell)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            RawPred::Or(items) => {
+                for p in items {
+                    if p.eval(record, bad_cell)? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+            RawPred::Not(p) => !p.eval(record, bad_cell)?,
+            RawPred::Cmp(c) => c.eval(record, bad_cell)?,
+        })
+    }
+}
+
+impl RawCmp {
+    fn eval(
+        &self,
+        record: &StringRecord,
+        bad_cell: &dyn Fn(usize, &str) -> Result<()>,
+    ) -> Result<bool> {
+        let cell = record.get(self.index).unwrap_or("");
+        let t = cell.trim();
+        if t.is_empty() {
+            return Ok(self.if_null);
+        }
+
+        // Text compares without copying the cell.
+        let value = match self.dtype {
+            DataType::Utf8 => return cmp_ord(t, &self.op, self.text.as_str()),
+            DataType::Int64 => t.parse().ok().map(Scalar::Int64),
+            DataType::Float64 => t.parse().ok().map(Scalar::Float64),
+            DataType::Bool => {
+                if t.eq_ignore_ascii_case("true") {
+                    Some(Scalar::Bool(true))
+                } else if t.eq_ignore_ascii_case("false") {
+                    Some(Scalar::Bool(false))
+                } else {
+                    None
+                }
+            }
+        };
+        let Some(value) = value else {
+            bad_cell(self.index, cell)?;
+            return Ok(self.if_null);
+        };
+
+        match (value.as_f64(), self.val.as_f64()) {
+            (Some(v), Some(lit)) => cmp_f64(v, &self.op, lit),
+            _ => cmp_json(&value.to_json(), &self.op, &self.val),
+        }
+    }
+}
+
+// How far past each split point `split_ranges` reads looking for proof of
+// whether the point is inside a quoted field.
+const RESYNC_WINDOW: u64 = 64 * 1024;
+
+/// Split the records of a CSV into at most `n` byte ranges, each starting
+/// at the beginning of a record. Each split is found by reading forward
+/// from an even share of the file, at most about `RESYNC_WINDOW` bytes, so
+/// the work done before the ranges are scanned stays small. Line breaks
+/// inside quoted fields are not record boundaries; where that cannot be
+/// told within the window a split assumes it is outside quotes, and the
+/// scan of the range before it checks that assumption.
+pub fn split_ranges(path: &str, n: usize) -> Result<Vec<Range<u64>>> {
+    let mut rdr =
+        csv::Reader::from_path(path).with_context(|| format!("Failed to open CSV: {path}"))?;
+    rdr.headers().context("CSV missing headers row")?;
+    let data_start = rdr.position().byte();
+    let len = fs::metadata(path)?.len();
+
+    let mut file = BufReader::new(File::open(path)?);
+    let bounds = record_bounds(&mut file, data_start, len, n)?;
+    Ok(bounds.windows(2).map(|w| w[0]..w[1]).collect())
+}
+
+// Range boundaries for `split_ranges`: `data_start`, the first record start
+// at or after each of the `n - 1` even split points, and `len`.
+fn record_bounds<R: BufRead + Seek>(
+    input: &mut R,
+    data_start: u64,
+    len: u64,
+    n: usize,
+) -> io::Result<Vec<u64>> {
+    let mut bounds = vec![data_start];
+    for k in 1..n as u64 {
+        let target = data_start + (len - data_start) * k / n as u64;
+        let last = *bounds.last().expect("data start");
+        if target <= last {
+            continue;
+        }
+        // Start one byte early: a line break just before the target makes
+        // the target itself a record start.
+        input.seek(SeekFrom::Start(target - 1))?;
+        if let Some(b) = next_record_start(input, target - 1)?
+            && b > last
+            && b < len
+        {
+            bounds.push(b);
+        }
+    }
+    bounds.push(len);
+    Ok(bounds)
+}
+
+// Where the first record after byte `from` starts: just past the first line
+// break outside quotes. Whether `from` is inside quotes is not known, so
+// line breaks are noted for both cases until a quote settles it. In valid
+// CSV a quote followed by anything but a delimiter, line break or quote
+// leaves a quoted field open, and one preceded by any of those others
+// closes it (or starts an escaped pair, which toggles back). Past the
+// window, or at the end of the file, the start is taken to be outside
+// quotes, which the end of the file proves and the scan of the previous
+// range checks otherwise.
+fn next_record_start<R: BufRead>(input: &mut R, from: u64) -> io::Result<Option<u64>> {
+    let structural = |b: u8| matches!(b, b',' | b'\n' | b'\r' | b'"');
+    let mut pos = from;
+    // Odd number of quotes since `from`.
+    let mut odd = false;
+    // First record start after a line break, by `odd` at the break.
+    let mut starts: [Option<u64>; 2] = [None, None];
+    let mut prev: Option<u8> = None;
+    // Whether `from` is inside quotes, once known.
+    let mut inside: Option<bool> = None;
+
+    loop {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            // A file ends outside quotes.
+            let inside = inside.unwrap_or(odd);
+            return Ok(starts[inside as usize]);
+        }
+        let read = buf.len();
+        for &b in buf {
+            pos += 1;
+            if b == b'"' {
+                odd = !odd;
+                if inside.is_none() && prev.is_some_and(|p| !structural(p)) {
+                    // Closed: outside after this quote.
+                    inside = Some(odd);
+                }
+            } else if prev == Some(b'"') && !structural(b) && inside.is_none() {
+                // Opened: inside after the previous quote.
+                inside = Some(!odd);
+            }
+            if b == b'\n' {
+                starts[odd as usize].get_or_insert(pos);
+            }
+            prev = Some(b);
+
+            if inside.is_none() && pos - from > RESYNC_WINDOW {
+                inside = Some(false);
+            }
+            if let Some(inside) = inside
+                && let Some(start) = starts[inside as usize]
+            {
+                return Ok(Some(start));
+            }
+        }
+        input.consume(read);
+    }
+}
+
+/// Counts the quotes read through it, so a range scan can check that it
+/// ended outside a quoted field.
+struct QuoteParity<R> {
+    inner: R,
+    odd: bool,
+}
+
+impl<R: Read> Read for QuoteParity<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let quotes = buf[..n].iter().filter(|&&b| b == b'"').count();
+        self.odd ^= quotes % 2 == 1;
+        Ok(n)
+    }
+}
+
+// Line breaks before byte `offset`, to turn range-relative line numbers
+// into file line numbers.
+fn lines_before(path: &str, offset: u64) -> Result<u64> {
+    if offset == 0 {
+        return Ok(0);
+    }
+    let mut buf = Vec::new();
+    File::open(path)?.take(offset).read_to_end(&mut buf)?;
+    Ok(buf.iter().filter(|&&b| b == b'\n').count() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Counts the bytes read from the wrapped input.
+    struct Counted<R> {
+        inner: R,
+        read: u64,
+    }
+
+    impl<R: Read> Read for Counted<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n as u64;
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for Counted<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn bounds(data: &str, n: usize) -> (Vec<u64>, u64) {
+        let mut input = BufReader::new(Counted {
+            inner: Cursor::new(data.as_bytes()),
+            read: 0,
+        });
+        let b = record_bounds(&mut input, 0, data.len() as u64, n).unwrap();
+        (b, input.get_ref().read)
+    }
+
+    #[test]
+    fn splits_read_a_bounded_window_per_split() {
+        let data: String = (0..400_000).map(|i| format!("{i},x{}\n", i % 7)).collect();
+        let (b, read) = bounds(&data, 8);
+
+        assert_eq!(b.len(), 9);
+        for &start in &b[1..8] {
+            assert_eq!(data.as_bytes()[start as usize - 1], b'\n');
+        }
+        let budget = 7 * (RESYNC_WINDOW + 16 * 1024);
+        assert!(read <= budget, "read {read} of {} bytes", data.len());
+    }
+
+    #[test]
+    fn splits_skip_line_breaks_inside_quotes() {
+        let row = "1,\"a\nb \"\"c\"\"\nd\",2\n";
+        let data = row.repeat(1000);
+        let (b, _) = bounds(&data, 5);
+
+        assert_eq!(b.len(), 6);
+        for &start in &b[1..5] {
+            assert_eq!(start as usize % row.len(), 0, "{start}");
+        }
     }
 }
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use std::sync::Arc;
+
+use crate::batch::RecordBatch;
+use crate::exec::{Detail, ExecNode};
+use crate::schema::{Schema, SchemaRef};
+
+/// Produces no rows, in place of a subtree the optimizer proved empty.
+pub struct EmptyExec {
+    schema: SchemaRef,
+}
+
+impl EmptyExec {
+    pub fn new(schema: Schema) -> Self {
+        Self {
+            schema: Arc::new(schema),
+        }
+    }
+}
+
+impl ExecNode for EmptyExec {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        Ok(None)
+    }
+
+    fn name(&self) -> &'static str {
+        "Empty"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        let columns = self.schema.fields.iter().map(Detail::field);
+        vec![("columns", Detail::list(columns))]
+    }
+}
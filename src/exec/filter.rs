@@ -1,31 +1,58 @@
 use anyhow::Result;
 
-use crate::ast::Predicate;
-use crate::exec::{ExecNode, predicate_list_match};
-use crate::value::Row;
+use crate::ast::PredExpr;
+use crate::batch::RecordBatch;
+use crate::exec::{Detail, ExecNode, predicate_mask};
+use crate::explain;
+use crate::schema::SchemaRef;
 
 pub struct FilterExec {
     input: Box<dyn ExecNode>,
-    preds: Vec<Predicate>,
+    pred: PredExpr,
 }
 
 impl FilterExec {
-    pub fn new(input: Box<dyn ExecNode>, preds: Vec<Predicate>) -> Self {
-        Self { input, preds }
+    pub fn new(input: Box<dyn ExecNode>, pred: PredExpr) -> Self {
+        Self { input, pred }
     }
 }
 
 impl ExecNode for FilterExec {
-    fn next_row(&mut self) -> Result<Option<Row>> {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
         loop {
-            let row = match self.input.next_row()? {
-                Some(r) => r,
+            let batch = match self.input.next_batch()? {
+                Some(b) => b,
                 None => return Ok(None),
             };
 
-            if predicate_list_match(&row, &self.preds)? {
-                return Ok(Some(row));
+            let mask = predicate_mask(&batch, &self.pred)?;
+            let keep: Vec<usize> = (0..batch.num_rows()).filter(|&i| mask[i]).collect();
+
+            if keep.len() == batch.num_rows() {
+                return Ok(Some(batch));
+            }
+            if !keep.is_empty() {
+                return Ok(Some(batch.take(&keep)));
             }
         }
     }
+
+    fn name(&self) -> &'static str {
+        "Filter"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        vec![(
+            "predicate",
+            Detail::new(&self.pred, explain::pred_json(&self.pred)),
+        )]
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        vec![self.input.as_ref()]
+    }
 }
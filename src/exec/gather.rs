@@ -0,0 +1,113 @@
+use anyhow::{Result, anyhow};
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, sync_channel};
+use std::thread::{self, JoinHandle};
+
+use crate::batch::RecordBatch;
+use crate::exec::{Detail, ExecNode, PartitionFn};
+use crate::schema::SchemaRef;
+
+// Batches a partition may run ahead of the consumer.
+const CHANNEL_DEPTH: usize = 8;
+
+/// Runs each partition pipeline on its own thread and yields their batches
+/// partition by partition, so output order matches a serial scan.
+pub struct GatherExec {
+    // Unexecuted copy of the partition pipeline, kept to describe it.
+    template: Box<dyn ExecNode>,
+    partitions: Vec<PartitionFn>,
+    threads: usize,
+    rows: u64,
+
+    started: bool,
+    // Unfinished partitions, in order.
+    workers: VecDeque<(Receiver<Result<RecordBatch>>, JoinHandle<()>)>,
+}
+
+impl GatherExec {
+    /// `template` is the pipeline every partition runs, built but never
+    /// executed.
+    pub fn new(template: Box<dyn ExecNode>, partitions: Vec<PartitionFn>) -> Self {
+        Self {
+            template,
+            threads: partitions.len(),
+            partitions,
+            rows: 0,
+            started: false,
+            workers: VecDeque::new(),
+        }
+    }
+
+    fn start(&mut self) {
+        for make in self.partitions.drain(..) {
+            let (tx, rx) = sync_channel(CHANNEL_DEPTH);
+            let handle = thread::spawn(move || {
+                let mut input = match make() {
+                    Ok(n) => n,
+                    Err(e) => {
+                        let _ = tx.send(Err(e));
+                        return;
+                    }
+                };
+                loop {
+                    let msg = match input.next_batch() {
+                        Ok(Some(b)) => Ok(b),
+                        Ok(None) => return,
+                        Err(e) => Err(e),
+                    };
+                    let failed = msg.is_err();
+                    // A closed channel means the consumer stopped early.
+                    if tx.send(msg).is_err() || failed {
+                        return;
+                    }
+                }
+            });
+            self.workers.push_back((rx, handle));
+        }
+        self.started = true;
+    }
+}
+
+impl ExecNode for GatherExec {
+    fn schema(&self) -> SchemaRef {
+        self.template.schema()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        if !self.started {
+            self.start();
+        }
+
+        while let Some((rx, _)) = self.workers.front() {
+            match rx.recv() {
+                Ok(msg) => {
+                    let batch = msg?;
+                    self.rows += batch.num_rows() as u64;
+                    return Ok(Some(batch));
+                }
+                // Sender dropped: the partition finished, or its thread died.
+                Err(_) => {
+                    let (_, handle) = self.workers.pop_front().unwrap();
+                    handle.join().map_err(|_| anyhow!("scan thread panicked"))?;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn name(&self) -> &'static str {
+        "Gather"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        vec![("threads", self.threads.into())]
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        vec![self.template.as_ref()]
+    }
+
+    fn input_rows(&self) -> Option<u64> {
+        Some(self.rows)
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::batch::RecordBatch;
+use crate::exec::{Detail, ExecNode};
+use crate::schema::SchemaRef;
+
+/// What an `InstrumentedExec` measured while the query ran.
+#[derive(Debug, Clone, Copy)]
+pub struct OperatorStats {
+    /// `None` for leaves, which read no rows from other operators.
+    pub rows_in: Option<u64>,
+    pub rows_out: u64,
+    pub batches: u64,
+    /// Wall time spent in this operator and everything below it.
+    pub elapsed: Duration,
+    /// The operator's own estimate, as `ExecNode::peak_memory`.
+    pub peak_memory: usize,
+}
+
+/// Statistics of the copies of one pipeline node that ran on partition
+/// threads, combined as each partition finishes.
+#[derive(Debug, Default)]
+pub struct PartitionStats {
+    partitions: u64,
+    rows_out: u64,
+    batches: u64,
+    // Partitions run at the same time, so the slowest one is the wall time.
+    elapsed: Duration,
+    // Every partition's state is alive at once.
+    peak_memory: usize,
+    metrics: Vec<(&'static str, u64)>,
+}
+
+/// Wraps a node to count its output and time its `next_batch` calls,
+/// otherwise passing everything through.
+pub struct InstrumentedExec {
+    inner: Box<dyn ExecNode>,
+    rows_out: u64,
+    batches: u64,
+    elapsed: Duration,
+    /// For a node of a partition pipeline, where to add its statistics when
+    /// it is dropped; for the unexecuted template node describing such a
+    /// pipeline, where they are read from.
+    partitions: Arc<Mutex<PartitionStats>>,
+    reports: bool,
+}
+
+impl InstrumentedExec {
+    pub fn new(inner: Box<dyn ExecNode>) -> Self {
+        Self {
+            inner,
+            rows_out: 0,
+            batches: 0,
+            elapsed: Duration::ZERO,
+            partitions: Arc::default(),
+            reports: false,
+        }
+    }
+
+    /// Wraps a node of one partition pipeline, which adds its statistics to
+    /// `partitions` once the partition is done with it.
+    pub fn reporting_to(inner: Box<dyn ExecNode>, partitions: Arc<Mutex<PartitionStats>>) -> Self {
+        Self {
+            inner,
+            rows_out: 0,
+            batches: 0,
+            elapsed: Duration::ZERO,
+            partitions,
+            reports: true,
+        }
+    }
+
+    /// Statistics of the partition pipelines this template node stands for.
+    pub fn partition_stats(&self) -> Arc<Mutex<PartitionStats>> {
+        self.partitions.clone()
+    }
+
+    fn executed_on_partitions(&self) -> bool {
+        !self.reports && self.partitions.lock().unwrap().partitions > 0
+    }
+}
+
+impl Drop for InstrumentedExec {
+    fn drop(&mut self) {
+        if !self.reports {
+            return;
+        }
+        let Ok(mut p) = self.partitions.lock() else {
+            return;
+        };
+        p.partitions += 1;
+        p.rows_out += self.rows_out;
+        p.batches += self.batches;
+        p.elapsed = p.elapsed.max(self.elapsed);
+        p.peak_memory += self.inner.peak_memory();
+        for (name, v) in self.inner.metrics() {
+            match p.metrics.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, total)) => *total += v,
+                None => p.metrics.push((name, v)),
+            }
+        }
+    }
+}
+
+impl ExecNode for InstrumentedExec {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let start = Instant::now();
+        let out = self.inner.next_batch();
+        self.elapsed += start.elapsed();
+
+        if let Ok(Some(batch)) = &out {
+            self.rows_out += batch.num_rows() as u64;
+            self.batches += 1;
+        }
+        out
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        self.inner.details()
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        self.inner.children()
+    }
+
+    fn metrics(&self) -> Vec<(&'static str, u64)> {
+        if self.executed_on_partitions() {
+            return self.partitions.lock().unwrap().metrics.clone();
+        }
+        self.inner.metrics()
+    }
+
+    fn input_rows(&self) -> Option<u64> {
+        self.inner.input_rows()
+    }
+
+    fn peak_memory(&self) -> usize {
+        self.inner.peak_memory()
+    }
+
+    fn stats(&self) -> Option<OperatorStats> {
+        let children = self.inner.children();
+        let rows_in = self.inner.input_rows().or_else(|| {
+            (!children.is_empty()).then(|| {
+                children
+                    .iter()
+                    .filter_map(|c| c.stats())
+                    .map(|s| s.rows_out)
+                    .sum()
+            })
+        });
+        if self.executed_on_partitions() {
+            let p = self.partitions.lock().unwrap();
+            return Some(OperatorStats {
+                rows_in,
+                rows_out: p.rows_out,
+                batches: p.batches,
+                elapsed: p.elapsed,
+                peak_memory: p.peak_memory,
+            });
+        }
+        Some(OperatorStats {
+            rows_in,
+            rows_out: self.rows_out,
+            batches: self.batches,
+            elapsed: self.elapsed,
+            peak_memory: self.inner.peak_memory(),
+        })
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::Result;
+use serde_json::{Value as JsonValue, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ast::JoinKind;
+use crate::batch::{BATCH_SIZE, RecordBatch};
+use crate::exec::{Detail, ExecNode, collect_rows, next_rows_batch};
+use crate::schema::{DataType, Schema, SchemaRef};
+use crate::value::{Row, row_bytes};
+
+/// Equi-join that loads the build side into a hash table and streams the
+/// probe side past it. Either input may be the build side; outer-join
+/// semantics follow the logical left/right inputs regardless.
+pub struct HashJoinExec {
+    left: Box<dyn ExecNode>,
+    right: Box<dyn ExecNode>,
+    kind: JoinKind,
+    left_keys: Vec<String>,
+    right_keys: Vec<String>,
+    // Per key pair: whether either side is float, so keys compare as floats.
+    float_keys: Vec<bool>,
+    build_left: bool,
+    schema: SchemaRef,
+
+    built: bool,
+    table: HashMap<String, Vec<usize>>,
+    build_rows: Vec<Row>,
+    build_matched: Vec<bool>,
+    build_bytes: usize,
+    pending: Vec<Row>,
+    probe_done: bool,
+}
+
+impl HashJoinExec {
+    pub fn new(
+        left: Box<dyn ExecNode>,
+        right: Box<dyn ExecNode>,
+        kind: JoinKind,
+        on: Vec<(String, String)>,
+        build_left: bool,
+    ) -> Self {
+        let (left_keys, right_keys): (Vec<String>, Vec<String>) = on.into_iter().unzip();
+        let is_float = |input: &dyn ExecNode, col: &str| {
+            input
+                .schema()
+                .field(col)
+                .is_some_and(|f| f.dtype == DataType::Float64)
+        };
+        let float_keys = left_keys
+            .iter()
+            .zip(&right_keys)
+            .map(|(l, r)| is_float(left.as_ref(), l) || is_float(right.as_ref(), r))
+            .collect();
+        let mut fields = left.schema().fields.clone();
+        fields.extend(right.schema().fields.iter().cloned());
+        Self {
+            left,
+            right,
+            kind,
+            left_keys,
+            right_keys,
+            float_keys,
+            build_left,
+            schema: Arc::new(Schema { fields }),
+            built: false,
+            table: HashMap::new(),
+            build_rows: Vec::new(),
+            build_matched: Vec::new(),
+            build_bytes: 0,
+            pending: Vec::new(),
+            probe_done: false,
+        }
+    }
+
+    fn keep_unmatched_left(&self) -> bool {
+        matches!(self.kind, JoinKind::Left | JoinKind::Full)
+    }
+
+    fn keep_unmatched_right(&self) -> bool {
+        matches!(self.kind, JoinKind::Right | JoinKind::Full)
+    }
+
+    fn build(&mut self) -> Result<()> {
+        let (input, keys) = if self.build_left {
+            (&mut self.left, &self.left_keys)
+        } else {
+            (&mut self.right, &self.right_keys)
+        };
+
+        self.build_rows = collect_rows(input.as_mut())?;
+        for (idx, row) in self.build_rows.iter().enumerate() {
+            self.build_bytes += row_bytes(row);
+            if let Some(k) = join_key(row, keys, &self.float_keys) {
+                self.build_bytes += k.len() + std::mem::size_of::<usize>();
+                self.table.entry(k).or_default().push(idx);
+            }
+        }
+
+        self.build_matched = vec![false; self.build_rows.len()];
+        self.built = true;
+        Ok(())
+    }
+
+    // Pull one probe batch and queue its output rows. Returns false once
+    // the probe side is exhausted.
+    fn probe_next(&mut self) -> Result<bool> {
+        let (probe, keys, keep_unmatched) = if self.build_left {
+            let keep = self.keep_unmatched_right();
+            (&mut self.right, &self.right_keys, keep)
+        } else {
+            let keep = self.keep_unmatched_left();
+            (&mut self.left, &self.left_keys, keep)
+        };
+
+        let Some(batch) = probe.next_batch()? else {
+            return Ok(false);
+        };
+
+        for row in batch.to_rows() {
+            let matches = join_key(&row, keys, &self.float_keys).and_then(|k| self.table.get(&k));
+            match matches {
+                Some(idxs) => {
+                    for &i in idxs {
+                        self.build_matched[i] = true;
+                        let mut out = row.clone();
+                        out.extend(
+                            self.build_rows[i]
+                                .iter()
+                                .map(|(k, v)| (k.clone(), v.clone())),
+                        );
+                        self.pending.push(out);
+                    }
+                }
+                // Columns of the missing side are absent, which reads as null.
+                None if keep_unmatched => self.pending.push(row),
+                None => {}
+            }
+        }
+        Ok(true)
+    }
+
+    // Unmatched build rows for outer joins, in build order.
+    fn queue_unmatched_build_rows(&mut self) {
+        let keep = if self.build_left {
+            self.keep_unmatched_left()
+        } else {
+            self.keep_unmatched_right()
+        };
+        if keep {
+            let rows = std::mem::take(&mut self.build_rows);
+            self.pending.extend(
+                rows.into_iter()
+                    .zip(&self.build_matched)
+                    .filter(|(_, matched)| !**matched)
+                    .map(|(r, _)| r),
+            );
+        }
+    }
+}
+
+/// Hash key for a row's join columns; `None` if any is null, since null
+/// never equals anything. Numbers in a key pair with a float side are
+/// normalized so `1` matches `1.0`; integer pairs keep their exact value.
+fn join_key(row: &Row, cols: &[String], float_keys: &[bool]) -> Option<String> {
+    let mut parts = Vec::with_capacity(cols.len());
+    for (c, &float) in cols.iter().zip(float_keys) {
+        let v = row.get(c).unwrap_or(&JsonValue::Null);
+        match v {
+            JsonValue::Null => return None,
+            JsonValue::Number(n) if float => parts.push(JsonValue::from(n.as_f64().unwrap_or(0.0))),
+            other => parts.push(other.clone()),
+        }
+    }
+    serde_json::to_string(&parts).ok()
+}
+
+impl ExecNode for HashJoinExec {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        if !self.built {
+            self.build()?;
+        }
+
+        while !self.probe_done && self.pending.len() < BATCH_SIZE {
+            if !self.probe_next()? {
+                self.probe_done = true;
+                self.queue_unmatched_build_rows();
+            }
+        }
+
+        let n = self.pending.len().min(BATCH_SIZE);
+        next_rows_batch(&self.schema, &mut self.pending.drain(..n))
+    }
+
+    fn name(&self) -> &'static str {
+        "HashJoin"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        let on = self
+            .left_keys
+            .iter()
+            .zip(&self.right_keys)
+            .map(|(l, r)| Detail::new(format!("{l} = {r}"), json!({"left": l, "right": r})));
+        let build = if self.build_left { "left" } else { "right" };
+        vec![
+            ("type", Detail::text(self.kind)),
+            ("on", Detail::list(on)),
+            ("build", Detail::text(build)),
+        ]
+    }
+
+    fn peak_memory(&self) -> usize {
+        self.build_bytes
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        vec![self.left.as_ref(), self.right.as_ref()]
+    }
+}
@@ -1,10 +1,12 @@
 use anyhow::Result;
 
-use crate::exec::ExecNode;
-use crate::value::Row;
+use crate::batch::RecordBatch;
+use crate::exec::{Detail, ExecNode};
+use crate::schema::SchemaRef;
 
 pub struct LimitExec {
     input: Box<dyn ExecNode>,
+    n: usize,
     remaining: usize,
 }
 
@@ -12,20 +14,38 @@ impl LimitExec {
     pub fn new(input: Box<dyn ExecNode>, n: usize) -> Self {
         Self {
             input,
+            n,
             remaining: n,
         }
     }
 }
 
 impl ExecNode for LimitExec {
-    fn next_row(&mut self) -> Result<Option<Row>> {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
         if self.remaining == 0 {
             return Ok(None);
         }
-        let row = self.input.next_row()?;
-        if row.is_some() {
-            self.remaining -= 1;
-        }
-        Ok(row)
+        let Some(mut batch) = self.input.next_batch()? else {
+            return Ok(None);
+        };
+        batch.truncate(self.remaining);
+        self.remaining -= batch.num_rows();
+        Ok(Some(batch))
+    }
+
+    fn name(&self) -> &'static str {
+        "Limit"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        vec![("n", self.n.into())]
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        vec![self.input.as_ref()]
     }
 }
@@ -1,30 +1,254 @@
 mod aggregate;
 mod csv_scan;
+mod empty;
 mod filter;
+mod gather;
+mod instrument;
+mod join;
 mod limit;
 mod project;
+mod sort;
+mod spill;
+mod topk;
 
-use anyhow::Result;
+use anyhow::{Result, bail};
+use serde_json::{Value as JsonValue, json};
+use std::fmt::Display;
 
-use crate::ast::Predicate;
-use crate::value::Row;
+use crate::ast::{PredExpr, Predicate};
+use crate::batch::{BATCH_SIZE, ColumnData, RecordBatch};
+use crate::schema::{Field, SchemaRef};
+use crate::value::{Row, cmp_json};
 
 pub use aggregate::{AggFunc, AggSpec, HashAggregateExec};
-pub use csv_scan::CsvScan;
+pub use csv_scan::{CsvScan, split_ranges};
+pub use empty::EmptyExec;
 pub use filter::FilterExec;
+pub use gather::GatherExec;
+pub use instrument::{InstrumentedExec, OperatorStats};
+pub use join::HashJoinExec;
 pub use limit::LimitExec;
 pub use project::ProjectExec;
+pub use sort::SortExec;
+pub use topk::TopKExec;
 
 pub trait ExecNode {
-    fn next_row(&mut self) -> Result<Option<Row>>;
+    /// Columns of every batch the node produces.
+    fn schema(&self) -> SchemaRef;
+
+    /// Next batch of output rows, or `None` once exhausted. Batches are
+    /// never empty.
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>>;
+
+    /// Operator name shown in plans and execution statistics.
+    fn name(&self) -> &'static str;
+
+    /// Settings the operator was planned with, as (key, value) pairs for
+    /// `--explain-physical`.
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        Vec::new()
+    }
+
+    /// Inputs executed on this thread, in display order.
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        Vec::new()
+    }
+
+    /// Counters collected while running, e.g. groups built or bytes spilled.
+    fn metrics(&self) -> Vec<(&'static str, u64)> {
+        Vec::new()
+    }
+
+    /// Rows consumed, for operators whose inputs are not children (e.g.
+    /// partitions aggregated on other threads).
+    fn input_rows(&self) -> Option<u64> {
+        None
+    }
+
+    /// Estimated peak bytes of state the operator buffered: hash tables,
+    /// sort buffers and the like, not the batches passing through.
+    fn peak_memory(&self) -> usize {
+        0
+    }
+
+    /// Runtime statistics, present when the node was wrapped for analysis.
+    fn stats(&self) -> Option<OperatorStats> {
+        None
+    }
+}
+
+/// One setting of an operator: display text for plan listings and a typed
+/// value for JSON output.
+#[derive(Debug, Clone)]
+pub struct Detail {
+    pub text: String,
+    pub value: JsonValue,
+}
+
+impl Detail {
+    pub fn new(text: impl Display, value: impl Into<JsonValue>) -> Self {
+        Self {
+            text: text.to_string(),
+            value: value.into(),
+        }
+    }
+
+    /// A string setting, shown as is.
+    pub fn text(text: impl Display) -> Self {
+        let text = text.to_string();
+        Self::new(&text, text.clone())
+    }
+
+    /// `[a, b, c]`, exported as an array of the items' values.
+    pub fn list(items: impl IntoIterator<Item = Detail>) -> Self {
+        let (texts, values): (Vec<_>, Vec<_>) =
+            items.into_iter().map(|d| (d.text, d.value)).unzip();
+        Self::new(format!("[{}]", texts.join(", ")), values)
+    }
+
+    /// `name: type`, exported as `{"name", "type"}`.
+    pub fn field(field: &Field) -> Self {
+        Self::new(
+            format!("{}: {}", field.name, field.dtype),
+            json!({"name": field.name, "type": field.dtype.to_string()}),
+        )
+    }
+
+    /// Bytes allowed before spilling; `none` (null) without a limit.
+    pub fn memory_limit(limit: Option<usize>) -> Self {
+        match limit {
+            Some(n) => Self::new(crate::explain::format_bytes(n), n),
+            None => Self::new("none", JsonValue::Null),
+        }
+    }
+}
+
+impl From<usize> for Detail {
+    fn from(n: usize) -> Self {
+        Self::new(n, n)
+    }
+}
+
+impl From<bool> for Detail {
+    fn from(b: bool) -> Self {
+        Self::new(b, b)
+    }
 }
 
-pub fn predicate_list_match(row: &Row, preds: &[Predicate]) -> Result<bool> {
-    for p in preds {
-        let v = row.get(&p.col).unwrap_or(&serde_json::Value::Null);
-        if !crate::value::cmp_json(v, &p.op, &p.val)? {
-            return Ok(false);
+/// Builds the pipeline for one partition of the input; runs on the thread
+/// that executes that partition.
+pub type PartitionFn = Box<dyn FnOnce() -> Result<Box<dyn ExecNode>> + Send>;
+
+/// Drain an input into rows, for operators that reorder or buffer them.
+pub fn collect_rows(input: &mut dyn ExecNode) -> Result<Vec<Row>> {
+    let mut rows = Vec::new();
+    while let Some(b) = input.next_batch()? {
+        rows.extend(b.to_rows());
+    }
+    Ok(rows)
+}
+
+/// Next batch of up to `BATCH_SIZE` buffered rows.
+pub fn next_rows_batch(
+    schema: &SchemaRef,
+    rows: &mut impl Iterator<Item = Row>,
+) -> Result<Option<RecordBatch>> {
+    let chunk: Vec<Row> = rows.take(BATCH_SIZE).collect();
+    if chunk.is_empty() {
+        return Ok(None);
+    }
+    RecordBatch::from_rows(schema.clone(), &chunk).map(Some)
+}
+
+/// Evaluate a predicate for every row of a batch.
+pub fn predicate_mask(batch: &RecordBatch, pred: &PredExpr) -> Result<Vec<bool>> {
+    Ok(match pred {
+        PredExpr::And { and } => {
+            let mut mask = vec![true; batch.num_rows()];
+            for p in and {
+                let m = predicate_mask(batch, p)?;
+                mask.iter_mut().zip(m).for_each(|(a, b)| *a &= b);
+            }
+            mask
+        }
+        PredExpr::Or { or } => {
+            let mut mask = vec![false; batch.num_rows()];
+            for p in or {
+                let m = predicate_mask(batch, p)?;
+                mask.iter_mut().zip(m).for_each(|(a, b)| *a |= b);
+            }
+            mask
+        }
+        PredExpr::Not { not } => predicate_mask(batch, not)?
+            .into_iter()
+            .map(|b| !b)
+            .collect(),
+        PredExpr::Cmp(p) => compare_mask(batch, p)?,
+        PredExpr::Compare(c) => {
+            let (left, right) = (c.left.eval(batch)?, c.right.eval(batch)?);
+            (0..batch.num_rows())
+                .map(|i| cmp_json(&left.value(i).to_json(), &c.op, &right.value(i).to_json()))
+                .collect::<Result<_>>()?
+        }
+    })
+}
+
+// `col op literal` over a column, with typed fast paths for numeric and
+// string columns; other combinations go through `cmp_json` per row.
+fn compare_mask(batch: &RecordBatch, p: &Predicate) -> Result<Vec<bool>> {
+    let n = batch.num_rows();
+    // Rejects unknown operators even when there are no non-null rows.
+    let if_null = cmp_json(&JsonValue::Null, &p.op, &p.val)?;
+    let Some(col) = batch.column_by_name(&p.col) else {
+        return Ok(vec![if_null; n]);
+    };
+
+    let mut mask = Vec::with_capacity(n);
+    match (&col.data, p.val.as_f64(), p.val.as_str()) {
+        (ColumnData::Int64(_) | ColumnData::Float64(_), Some(lit), _) => {
+            for i in 0..n {
+                mask.push(match col.f64_at(i) {
+                    Some(v) => cmp_f64(v, &p.op, lit)?,
+                    None => if_null,
+                });
+            }
+        }
+        (ColumnData::Utf8(values), _, lit) => {
+            let lit = lit.map_or_else(|| p.val.to_string(), str::to_string);
+            for (i, v) in values.iter().enumerate() {
+                mask.push(if col.is_valid(i) {
+                    cmp_ord(v.as_str(), &p.op, lit.as_str())?
+                } else {
+                    if_null
+                });
+            }
+        }
+        _ => {
+            for i in 0..n {
+                mask.push(cmp_json(&col.value(i).to_json(), &p.op, &p.val)?);
+            }
         }
     }
-    Ok(true)
+    Ok(mask)
+}
+
+// Same numeric semantics as `cmp_json`, equality within epsilon.
+fn cmp_f64(a: f64, op: &str, b: f64) -> Result<bool> {
+    Ok(match op {
+        "==" => (a - b).abs() < f64::EPSILON,
+        "!=" => (a - b).abs() >= f64::EPSILON,
+        _ => cmp_ord(&a, op, &b)?,
+    })
+}
+
+fn cmp_ord<T: PartialOrd + ?Sized>(a: &T, op: &str, b: &T) -> Result<bool> {
+    Ok(match op {
+        "==" => a == b,
+        "!=" => a != b,
+        ">" => a > b,
+        ">=" => a >= b,
+        "<" => a < b,
+        "<=" => a <= b,
+        _ => bail!("Unsupported operator: {op}"),
+    })
 }
@@ -1,34 +1,78 @@
 use anyhow::Result;
+use serde_json::json;
+use std::sync::Arc;
 
-use crate::exec::ExecNode;
-use crate::value::Row;
+use crate::batch::RecordBatch;
+use crate::exec::{Detail, ExecNode};
+use crate::explain;
+use crate::expr::NamedExpr;
+use crate::schema::{Field, Schema, SchemaRef};
 
 pub struct ProjectExec {
     input: Box<dyn ExecNode>,
-    cols: Vec<String>,
+    exprs: Vec<NamedExpr>,
+    schema: SchemaRef,
 }
 
 impl ProjectExec {
-    pub fn new(input: Box<dyn ExecNode>, cols: Vec<String>) -> Self {
-        Self { input, cols }
+    pub fn new(input: Box<dyn ExecNode>, exprs: Vec<NamedExpr>) -> Result<Self> {
+        let in_schema = input.schema();
+        let fields = exprs
+            .iter()
+            .map(|e| {
+                Ok(Field {
+                    name: e.name.clone(),
+                    dtype: e.expr.data_type(&in_schema)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            input,
+            exprs,
+            schema: Arc::new(Schema { fields }),
+        })
     }
 }
 
 impl ExecNode for ProjectExec {
-    fn next_row(&mut self) -> Result<Option<Row>> {
-        let row = match self.input.next_row()? {
-            Some(r) => r,
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let batch = match self.input.next_batch()? {
+            Some(b) => b,
             None => return Ok(None),
         };
 
-        let mut out = Row::new();
-        for c in &self.cols {
-            if let Some(v) = row.get(c) {
-                out.insert(c.clone(), v.clone());
-            } else {
-                out.insert(c.clone(), serde_json::Value::Null);
-            }
-        }
-        Ok(Some(out))
+        let columns = self
+            .exprs
+            .iter()
+            .map(|e| e.expr.eval(&batch))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(RecordBatch::new(
+            self.schema.clone(),
+            columns,
+            batch.num_rows(),
+        )))
+    }
+
+    fn name(&self) -> &'static str {
+        "Project"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        let exprs = self.exprs.iter().map(|e| {
+            Detail::new(
+                e,
+                json!({"name": e.name, "expr": explain::expr_json(&e.expr)}),
+            )
+        });
+        vec![("exprs", Detail::list(exprs))]
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        vec![self.input.as_ref()]
     }
 }
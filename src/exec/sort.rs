@@ -0,0 +1,351 @@
+use anyhow::Result;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::ast::{OrderKey, SortDir};
+use crate::batch::{BATCH_SIZE, Column, RecordBatch, Scalar};
+use crate::exec::spill::{RowReader, RowWriter, SpillDir};
+use crate::exec::{Detail, ExecNode};
+use crate::explain;
+use crate::schema::SchemaRef;
+use crate::value::{Row, cmp_values};
+
+/// Compare two rows on a list of sort keys, honoring direction and null placement.
+/// Missing columns sort as nulls.
+pub fn cmp_rows(a: &Row, b: &Row, keys: &[OrderKey]) -> Ordering {
+    for key in keys {
+        let va = a.get(&key.col).filter(|v| !v.is_null());
+        let vb = b.get(&key.col).filter(|v| !v.is_null());
+
+        let ord = match (va, vb) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) if key.nulls_first() => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) if key.nulls_first() => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(x), Some(y)) => match key.dir {
+                SortDir::Asc => cmp_values(x, y),
+                SortDir::Desc => cmp_values(y, x),
+            },
+        };
+
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+// Per-row bookkeeping counted against the memory limit on top of the values.
+const ROW_OVERHEAD: usize = std::mem::size_of::<Vec<Scalar>>();
+
+// Read buffer each run being merged needs; with the memory limit it bounds
+// how many runs one merge pass can take.
+const MERGE_BUFFER: usize = 64 * 1024;
+const MAX_FAN_IN: usize = 64;
+
+/// Sort key resolved to a column position; `None` if the column is missing,
+/// in which case every row sorts as null.
+struct SortKey {
+    index: Option<usize>,
+    dir: SortDir,
+    nulls_first: bool,
+}
+
+fn cmp_keyed(a: &[Scalar], b: &[Scalar], keys: &[SortKey]) -> Ordering {
+    for key in keys {
+        let Some(i) = key.index else { continue };
+        let ord = match (&a[i], &b[i]) {
+            (Scalar::Null, Scalar::Null) => Ordering::Equal,
+            (Scalar::Null, _) if key.nulls_first => Ordering::Less,
+            (Scalar::Null, _) => Ordering::Greater,
+            (_, Scalar::Null) if key.nulls_first => Ordering::Greater,
+            (_, Scalar::Null) => Ordering::Less,
+            (x, y) => match key.dir {
+                SortDir::Asc => x.cmp_value(y),
+                SortDir::Desc => y.cmp_value(x),
+            },
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+fn row_bytes(row: &[Scalar]) -> usize {
+    ROW_OVERHEAD
+        + row
+            .iter()
+            .map(|v| {
+                std::mem::size_of::<Scalar>()
+                    + match v {
+                        Scalar::Utf8(s) => s.len(),
+                        _ => 0,
+                    }
+            })
+            .sum::<usize>()
+}
+
+/// K-way merge of sorted runs, streaming rows in order.
+struct RunMerger {
+    readers: Vec<RowReader>,
+    heads: Vec<Option<Vec<Scalar>>>,
+}
+
+impl RunMerger {
+    fn open(runs: &[PathBuf], width: usize) -> Result<Self> {
+        let mut readers = runs
+            .iter()
+            .map(|p| RowReader::open(p, width))
+            .collect::<Result<Vec<_>>>()?;
+        let heads = readers
+            .iter_mut()
+            .map(|r| r.next_row())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { readers, heads })
+    }
+
+    /// Next row in key order, or `None` once every run is drained.
+    fn next_row(&mut self, keys: &[SortKey]) -> Result<Option<Vec<Scalar>>> {
+        // Fan-in is small, so a linear scan for the minimum beats a heap
+        // that would need to own the head rows.
+        let mut best: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some(row) = head else { continue };
+            let better = match best.and_then(|b| self.heads[b].as_ref()) {
+                // Strictly less: ties stay with the earlier run.
+                Some(cur) => cmp_keyed(row, cur, keys) == Ordering::Less,
+                None => true,
+            };
+            if better {
+                best = Some(i);
+            }
+        }
+        let Some(i) = best else { return Ok(None) };
+        let next = self.readers[i].next_row()?;
+        Ok(std::mem::replace(&mut self.heads[i], next))
+    }
+}
+
+enum SortOutput {
+    Pending,
+    InMemory(std::vec::IntoIter<Vec<Scalar>>),
+    Merging(RunMerger),
+}
+
+/// Stable sort of its whole input. Past `memory_limit` bytes of buffered
+/// rows, the buffer is sorted and written out as a run; runs are then
+/// merged back, in several passes if there are more than one pass can open.
+pub struct SortExec {
+    input: Box<dyn ExecNode>,
+    order: Vec<OrderKey>,
+    keys: Vec<SortKey>,
+    memory_limit: Option<usize>,
+
+    output: SortOutput,
+    dir: Option<SpillDir>,
+    peak_mem: usize,
+    runs: u64,
+    max_run_rows: u64,
+    merge_passes: u64,
+    spill_bytes: u64,
+}
+
+impl SortExec {
+    pub fn new(
+        input: Box<dyn ExecNode>,
+        order: Vec<OrderKey>,
+        memory_limit: Option<usize>,
+    ) -> Self {
+        let schema = input.schema();
+        let keys = order
+            .iter()
+            .map(|k| SortKey {
+                index: schema.index_of(&k.col),
+                dir: k.dir,
+                nulls_first: k.nulls_first(),
+            })
+            .collect();
+        Self {
+            input,
+            order,
+            keys,
+            memory_limit,
+            output: SortOutput::Pending,
+            dir: None,
+            peak_mem: 0,
+            runs: 0,
+            max_run_rows: 0,
+            merge_passes: 0,
+            spill_bytes: 0,
+        }
+    }
+
+    fn build(&mut self) -> Result<()> {
+        let mut buffered: Vec<Vec<Scalar>> = Vec::new();
+        let mut mem = 0usize;
+        let mut runs: Vec<PathBuf> = Vec::new();
+
+        while let Some(batch) = self.input.next_batch()? {
+            for i in 0..batch.num_rows() {
+                let row: Vec<Scalar> = batch.columns().iter().map(|c| c.value(i)).collect();
+                mem += row_bytes(&row);
+                buffered.push(row);
+            }
+            self.peak_mem = self.peak_mem.max(mem);
+            if self.memory_limit.is_some_and(|l| mem > l) {
+                runs.push(self.write_run(std::mem::take(&mut buffered))?);
+                mem = 0;
+            }
+        }
+
+        // Stable, so rows with equal keys keep their input order.
+        buffered.sort_by(|a, b| cmp_keyed(a, b, &self.keys));
+        if runs.is_empty() {
+            self.output = SortOutput::InMemory(buffered.into_iter());
+            return Ok(());
+        }
+        if !buffered.is_empty() {
+            runs.push(self.write_run(buffered)?);
+        }
+
+        let fan_in = (self.memory_limit.unwrap_or(0) / MERGE_BUFFER).clamp(2, MAX_FAN_IN);
+        while runs.len() > fan_in {
+            runs = runs
+                .chunks(fan_in)
+                .map(|group| self.merge_runs(group))
+                .collect::<Result<_>>()?;
+            self.merge_passes += 1;
+        }
+
+        // The final pass streams straight to the output.
+        self.merge_passes += 1;
+        let width = self.input.schema().fields.len();
+        self.output = SortOutput::Merging(RunMerger::open(&runs, width)?);
+        Ok(())
+    }
+
+    fn run_file(&mut self) -> Result<PathBuf> {
+        let dir = match &mut self.dir {
+            Some(d) => d,
+            None => self.dir.insert(SpillDir::new("sort")?),
+        };
+        Ok(dir.next_file())
+    }
+
+    fn write_run(&mut self, mut rows: Vec<Vec<Scalar>>) -> Result<PathBuf> {
+        rows.sort_by(|a, b| cmp_keyed(a, b, &self.keys));
+
+        let path = self.run_file()?;
+        let mut out = RowWriter::create(&path)?;
+        for row in &rows {
+            out.write_row(row)?;
+        }
+        self.spill_bytes += out.finish()?;
+        self.runs += 1;
+        self.max_run_rows = self.max_run_rows.max(rows.len() as u64);
+        Ok(path)
+    }
+
+    /// Merge consecutive runs into one, deleting the inputs.
+    fn merge_runs(&mut self, group: &[PathBuf]) -> Result<PathBuf> {
+        if let [single] = group {
+            return Ok(single.clone());
+        }
+
+        let width = self.input.schema().fields.len();
+        let mut merger = RunMerger::open(group, width)?;
+        let path = self.run_file()?;
+        let mut out = RowWriter::create(&path)?;
+        while let Some(row) = merger.next_row(&self.keys)? {
+            out.write_row(&row)?;
+        }
+        self.spill_bytes += out.finish()?;
+
+        for p in group {
+            let _ = fs::remove_file(p);
+        }
+        Ok(path)
+    }
+
+    fn next_rows(&mut self) -> Result<Vec<Vec<Scalar>>> {
+        let mut rows = Vec::new();
+        match &mut self.output {
+            SortOutput::Pending => unreachable!("sort already built"),
+            SortOutput::InMemory(it) => rows.extend(it.take(BATCH_SIZE)),
+            SortOutput::Merging(merger) => {
+                while rows.len() < BATCH_SIZE {
+                    match merger.next_row(&self.keys)? {
+                        Some(row) => rows.push(row),
+                        None => break,
+                    }
+                }
+            }
+        }
+        Ok(rows)
+    }
+}
+
+impl ExecNode for SortExec {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        if matches!(self.output, SortOutput::Pending) {
+            self.build()?;
+        }
+
+        let rows = self.next_rows()?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let schema = self.input.schema();
+        let mut columns: Vec<Column> = schema
+            .fields
+            .iter()
+            .map(|f| Column::with_capacity(f.dtype, rows.len()))
+            .collect();
+        let num_rows = rows.len();
+        for row in rows {
+            for (col, v) in columns.iter_mut().zip(row) {
+                col.push(v)?;
+            }
+        }
+        Ok(Some(RecordBatch::new(schema, columns, num_rows)))
+    }
+
+    fn name(&self) -> &'static str {
+        "Sort"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        vec![
+            ("keys", explain::keys_detail(&self.order)),
+            ("memory_limit", Detail::memory_limit(self.memory_limit)),
+        ]
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        vec![self.input.as_ref()]
+    }
+
+    fn peak_memory(&self) -> usize {
+        self.peak_mem
+    }
+
+    fn metrics(&self) -> Vec<(&'static str, u64)> {
+        if self.runs == 0 {
+            return Vec::new();
+        }
+        vec![
+            ("runs", self.runs),
+            ("max_run_rows", self.max_run_rows),
+            ("merge_passes", self.merge_passes),
+            ("spill_bytes", self.spill_bytes),
+        ]
+    }
+}
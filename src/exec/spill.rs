@@ -0,0 +1,222 @@
+use anyhow::{Context, Result, bail};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::batch::Scalar;
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// Private temporary directory for one operator's spill files, removed with
+/// everything in it when dropped.
+pub struct SpillDir {
+    path: PathBuf,
+    files: usize,
+}
+
+impl SpillDir {
+    pub fn new(kind: &str) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "plancraft-{kind}-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create spill directory {}", path.display()))?;
+        Ok(Self { path, files: 0 })
+    }
+
+    /// Path for a new spill file.
+    pub fn next_file(&mut self) -> PathBuf {
+        self.files += 1;
+        self.path.join(format!("{}.spill", self.files))
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// ---------- binary encoding ----------
+
+pub fn put_u64(buf: &mut Vec<u8>, v: u64) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+pub fn put_f64(buf: &mut Vec<u8>, v: f64) {
+    put_u64(buf, v.to_bits());
+}
+
+/// A scalar as a type tag followed by its value. Equal values encode to
+/// equal bytes, so the encoding doubles as a hash key.
+pub fn put_scalar(buf: &mut Vec<u8>, v: &Scalar) {
+    match v {
+        Scalar::Null => buf.push(0),
+        Scalar::Int64(x) => {
+            buf.push(1);
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+        Scalar::Float64(x) => {
+            buf.push(2);
+            put_f64(buf, *x);
+        }
+        Scalar::Bool(x) => buf.extend_from_slice(&[3, *x as u8]),
+        Scalar::Utf8(x) => put_str(buf, x),
+    }
+}
+
+/// Same bytes as `put_scalar(Scalar::Utf8(..))`, without building the scalar.
+pub fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.push(4);
+    put_u64(buf, s.len() as u64);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Cursor over bytes written with the `put_*` functions.
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < n {
+            bail!("truncated spill file");
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into()?))
+    }
+
+    pub fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    pub fn scalar(&mut self) -> Result<Scalar> {
+        Ok(match self.u8()? {
+            0 => Scalar::Null,
+            1 => Scalar::Int64(i64::from_le_bytes(self.bytes(8)?.try_into()?)),
+            2 => Scalar::Float64(self.f64()?),
+            3 => Scalar::Bool(self.u8()? != 0),
+            4 => {
+                let len = self.u64()? as usize;
+                Scalar::Utf8(String::from_utf8(self.bytes(len)?.to_vec())?)
+            }
+            t => bail!("corrupt spill file: unknown value tag {t}"),
+        })
+    }
+}
+
+// ---------- row streams ----------
+
+/// Writes rows of scalars to a spill file through a buffer.
+pub struct RowWriter {
+    out: BufWriter<File>,
+    buf: Vec<u8>,
+    bytes: u64,
+}
+
+impl RowWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create spill file {}", path.display()))?;
+        Ok(Self {
+            out: BufWriter::new(file),
+            buf: Vec::new(),
+            bytes: 0,
+        })
+    }
+
+    pub fn write_row(&mut self, row: &[Scalar]) -> Result<()> {
+        self.buf.clear();
+        for v in row {
+            put_scalar(&mut self.buf, v);
+        }
+        self.bytes += self.buf.len() as u64;
+        self.out
+            .write_all(&self.buf)
+            .context("Failed to write spill file")
+    }
+
+    /// Flush and return the number of bytes written.
+    pub fn finish(mut self) -> Result<u64> {
+        self.out.flush().context("Failed to write spill file")?;
+        Ok(self.bytes)
+    }
+}
+
+/// Reads back rows written by a `RowWriter`, one at a time.
+pub struct RowReader {
+    input: BufReader<File>,
+    width: usize,
+}
+
+impl RowReader {
+    pub fn open(path: &Path, width: usize) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to read spill file {}", path.display()))?;
+        Ok(Self {
+            input: BufReader::new(file),
+            width,
+        })
+    }
+
+    pub fn next_row(&mut self) -> Result<Option<Vec<Scalar>>> {
+        let mut row = Vec::with_capacity(self.width);
+        for i in 0..self.width {
+            let mut tag = [0u8];
+            match self.input.read_exact(&mut tag) {
+                Err(e) if i == 0 && e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                r => r.context("truncated spill file")?,
+            }
+            row.push(self.scalar(tag[0])?);
+        }
+        Ok(Some(row))
+    }
+
+    fn scalar(&mut self, tag: u8) -> Result<Scalar> {
+        let mut word = [0u8; 8];
+        let mut read = |n: usize, buf: &mut [u8]| {
+            self.input
+                .read_exact(&mut buf[..n])
+                .context("truncated spill file")
+        };
+        Ok(match tag {
+            0 => Scalar::Null,
+            1 => {
+                read(8, &mut word)?;
+                Scalar::Int64(i64::from_le_bytes(word))
+            }
+            2 => {
+                read(8, &mut word)?;
+                Scalar::Float64(f64::from_bits(u64::from_le_bytes(word)))
+            }
+            3 => {
+                read(1, &mut word)?;
+                Scalar::Bool(word[0] != 0)
+            }
+            4 => {
+                read(8, &mut word)?;
+                let mut s = vec![0u8; u64::from_le_bytes(word) as usize];
+                let n = s.len();
+                read(n, &mut s)?;
+                Scalar::Utf8(String::from_utf8(s)?)
+            }
+            t => bail!("corrupt spill file: unknown value tag {t}"),
+        })
+    }
+}
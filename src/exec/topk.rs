@@ -0,0 +1,135 @@
+use anyhow::Result;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::rc::Rc;
+
+use crate::ast::OrderKey;
+use crate::batch::{BATCH_SIZE, RecordBatch};
+use crate::exec::sort::cmp_rows;
+use crate::exec::{Detail, ExecNode, next_rows_batch};
+use crate::explain;
+use crate::schema::SchemaRef;
+use crate::value::{Row, row_bytes};
+
+/// Sort + Limit in one pass: keeps at most `n` rows in a bounded max-heap whose
+/// top is the worst row retained so far.
+pub struct TopKExec {
+    input: Box<dyn ExecNode>,
+    keys: Rc<[OrderKey]>,
+    n: usize,
+
+    built: bool,
+    out_rows: std::vec::IntoIter<Row>,
+    kept_bytes: usize,
+}
+
+struct HeapEntry {
+    row: Row,
+    // Arrival order, so ties resolve the same way as a stable sort.
+    seq: u64,
+    keys: Rc<[OrderKey]>,
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_rows(&self.row, &other.row, &self.keys).then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl TopKExec {
+    pub fn new(input: Box<dyn ExecNode>, keys: Vec<OrderKey>, n: usize) -> Self {
+        Self {
+            input,
+            keys: keys.into(),
+            n,
+            built: false,
+            out_rows: Vec::new().into_iter(),
+            kept_bytes: 0,
+        }
+    }
+
+    fn build(&mut self) -> Result<()> {
+        self.built = true;
+        if self.n == 0 {
+            return Ok(());
+        }
+
+        // The heap grows as it fills; a huge LIMIT must not reserve it all up front.
+        let mut heap: BinaryHeap<HeapEntry> =
+            BinaryHeap::with_capacity(self.n.saturating_add(1).min(BATCH_SIZE));
+        let mut seq = 0u64;
+
+        while let Some(batch) = self.input.next_batch()? {
+            for i in 0..batch.num_rows() {
+                let entry = HeapEntry {
+                    row: batch.row(i),
+                    seq,
+                    keys: Rc::clone(&self.keys),
+                };
+                seq += 1;
+
+                if heap.len() < self.n {
+                    heap.push(entry);
+                } else if heap.peek().is_some_and(|worst| entry < *worst) {
+                    heap.pop();
+                    heap.push(entry);
+                }
+            }
+        }
+
+        let rows = heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|e| e.row)
+            .collect::<Vec<_>>();
+        self.kept_bytes = rows.iter().map(row_bytes).sum();
+        self.out_rows = rows.into_iter();
+        Ok(())
+    }
+}
+
+impl ExecNode for TopKExec {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        if !self.built {
+            self.build()?;
+        }
+        next_rows_batch(&self.input.schema(), &mut self.out_rows)
+    }
+
+    fn name(&self) -> &'static str {
+        "TopK"
+    }
+
+    fn details(&self) -> Vec<(&'static str, Detail)> {
+        vec![
+            ("n", self.n.into()),
+            ("keys", explain::keys_detail(&self.keys)),
+        ]
+    }
+
+    fn peak_memory(&self) -> usize {
+        self.kept_bytes
+    }
+
+    fn children(&self) -> Vec<&dyn ExecNode> {
+        vec![self.input.as_ref()]
+    }
+}
@@ -1,21 +1,86 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value as JsonValue, json};
+
+use crate::ast::{OrderKey, PredExpr, SortDir};
+use crate::cost::CostModel;
+use crate::exec::{AggSpec, Detail, ExecNode};
+use crate::expr::Expr;
 use crate::logical::LogicalPlan;
 
-pub fn format_plan(plan: &LogicalPlan) -> String {
+/// How `--explain*` output is rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExplainFormat {
+    /// Indented tree
+    #[default]
+    Text,
+    /// Nested objects with every node's settings; children under `inputs`
+    Json,
+    /// Graphviz digraph
+    Dot,
+}
+
+/// Indented logical plan; nodes `cost` can estimate end in `[est_rows=N]`.
+pub fn format_plan(plan: &LogicalPlan, cost: &CostModel) -> String {
     let mut out = String::new();
-    fmt(plan, 0, &mut out);
+    fmt(plan, cost, 0, &mut out);
     out
 }
 
-fn fmt(plan: &LogicalPlan, indent: usize, out: &mut String) {
+fn fmt(plan: &LogicalPlan, cost: &CostModel, indent: usize, out: &mut String) {
     let pad = "  ".repeat(indent);
+    let est = cost
+        .rows(plan)
+        .map(|n| format!(" [est_rows={}]", n.ceil() as u64))
+        .unwrap_or_default();
 
     match plan {
-        LogicalPlan::Scan { path } => {
-            out.push_str(&format!("{pad}Scan(path=\"{path}\")\n"));
+        LogicalPlan::Scan {
+            path,
+            alias,
+            columns,
+            filter,
+            limit,
+            ..
+        } => {
+            let mut args = vec![format!("path=\"{path}\"")];
+            if let Some(a) = alias {
+                args.push(format!("as={a}"));
+            }
+            if let Some(c) = columns {
+                args.push(format!("columns=[{}]", c.join(", ")));
+            }
+            if let Some(f) = filter {
+                args.push(format!("filter={f}"));
+            }
+            if let Some(n) = limit {
+                args.push(format!("limit={n}"));
+            }
+            out.push_str(&format!("{pad}Scan({}){est}\n", args.join(", ")));
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            kind,
+            on,
+            build_left,
+        } => {
+            let on = on
+                .iter()
+                .map(|(l, r)| format!("{l} = {r}"))
+                .collect::<Vec<_>>();
+            let build = build_left
+                .map(|l| format!(", build={}", if l { "left" } else { "right" }))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "{pad}Join(type={kind}, on=[{}]{build}){est}\n",
+                on.join(", ")
+            ));
+            fmt(left, cost, indent + 1, out);
+            fmt(right, cost, indent + 1, out);
         }
-        LogicalPlan::Filter { input, preds } => {
-            out.push_str(&format!("{pad}Filter(preds={})\n", preds.len()));
-            fmt(input, indent + 1, out);
+        LogicalPlan::Filter { input, pred } => {
+            out.push_str(&format!("{pad}Filter({pred}){est}\n"));
+            fmt(input, cost, indent + 1, out);
         }
         LogicalPlan::Aggregate {
             input,
@@ -23,19 +88,506 @@ fn fmt(plan: &LogicalPlan, indent: usize, out: &mut String) {
             aggs,
         } => {
             out.push_str(&format!(
-                "{pad}Aggregate(group_keys={:?}, aggs={})\n",
+                "{pad}Aggregate(group_keys={:?}, aggs={}){est}\n",
                 group_keys,
                 aggs.len()
             ));
-            fmt(input, indent + 1, out);
+            fmt(input, cost, indent + 1, out);
+        }
+        LogicalPlan::Project { input, exprs } => {
+            let cols = exprs.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+            out.push_str(&format!("{pad}Project(cols={:?}){est}\n", cols));
+            fmt(input, cost, indent + 1, out);
         }
-        LogicalPlan::Project { input, cols } => {
-            out.push_str(&format!("{pad}Project(cols={:?})\n", cols));
-            fmt(input, indent + 1, out);
+        LogicalPlan::Sort { input, keys } => {
+            let keys = keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+            out.push_str(&format!("{pad}Sort(keys=[{}]){est}\n", keys.join(", ")));
+            fmt(input, cost, indent + 1, out);
         }
         LogicalPlan::Limit { input, n } => {
-            out.push_str(&format!("{pad}Limit(n={n})\n"));
-            fmt(input, indent + 1, out);
+            out.push_str(&format!("{pad}Limit(n={n}){est}\n"));
+            fmt(input, cost, indent + 1, out);
+        }
+        LogicalPlan::TopK { input, keys, n } => {
+            let keys = keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+            out.push_str(&format!(
+                "{pad}TopK(n={n}, keys=[{}]){est}\n",
+                keys.join(", ")
+            ));
+            fmt(input, cost, indent + 1, out);
+        }
+        LogicalPlan::Empty { schema } => {
+            let cols = schema
+                .fields
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>();
+            out.push_str(&format!("{pad}Empty(columns=[{}]){est}\n", cols.join(", ")));
+        }
+    }
+}
+
+/// Physical operator tree, each node with the settings it was planned with.
+pub fn format_physical_plan(root: &dyn ExecNode) -> String {
+    let mut out = String::new();
+    fmt_physical(root, 0, &mut out);
+    out
+}
+
+fn fmt_physical(node: &dyn ExecNode, indent: usize, out: &mut String) {
+    let details = node
+        .details()
+        .into_iter()
+        .map(|(k, v)| format!("{k}={}", v.text))
+        .collect::<Vec<_>>();
+    out.push_str(&format!(
+        "{}{}({})\n",
+        "  ".repeat(indent),
+        node.name(),
+        details.join(", ")
+    ));
+    for child in node.children() {
+        fmt_physical(child, indent + 1, out);
+    }
+}
+
+/// Operator tree after execution, with each node's counters and, when it
+/// ran instrumented, its rows, time and memory.
+pub fn format_exec_stats(root: &dyn ExecNode) -> String {
+    let mut out = String::new();
+    fmt_exec(root, 0, &mut out);
+    out
+}
+
+fn fmt_exec(node: &dyn ExecNode, indent: usize, out: &mut String) {
+    let metrics = node
+        .metrics()
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>();
+    out.push_str(&format!(
+        "{}{}({})",
+        "  ".repeat(indent),
+        node.name(),
+        metrics.join(", ")
+    ));
+
+    if let Some(s) = node.stats() {
+        let mut parts = Vec::new();
+        if let Some(n) = s.rows_in {
+            parts.push(format!("rows_in={n}"));
         }
+        parts.push(format!("rows_out={}", s.rows_out));
+        parts.push(format!("batches={}", s.batches));
+        parts.push(format!("time={:.3}ms", s.elapsed.as_secs_f64() * 1000.0));
+        parts.push(format!("peak_mem={}", format_bytes(s.peak_memory)));
+        out.push_str(&format!(" [{}]", parts.join(", ")));
+    }
+    out.push('\n');
+
+    for child in node.children() {
+        fmt_exec(child, indent + 1, out);
+    }
+}
+
+pub fn format_bytes(n: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut v = n as f64;
+    let mut unit = 0;
+    while v >= 1024.0 && unit + 1 < UNITS.len() {
+        v /= 1024.0;
+        unit += 1;
     }
+    if unit == 0 {
+        format!("{n}B")
+    } else {
+        format!("{v:.1}{}", UNITS[unit])
+    }
+}
+
+// ---------- JSON ----------
+
+/// The whole logical plan as nested objects: `node` names the operator,
+/// `inputs` holds its children and every other key one of its settings.
+pub fn plan_json(plan: &LogicalPlan, cost: &CostModel) -> JsonValue {
+    let (mut obj, inputs) = match plan {
+        LogicalPlan::Scan {
+            path,
+            alias,
+            schema,
+            columns,
+            filter,
+            limit,
+            strict,
+        } => {
+            let columns = schema
+                .select(columns.as_deref())
+                .into_iter()
+                .map(|f| json!({"name": f.name, "type": f.dtype.to_string()}))
+                .collect::<Vec<_>>();
+            (
+                json!({
+                    "node": "Scan",
+                    "path": path,
+                    "alias": alias,
+                    "columns": columns,
+                    "filter": filter.as_ref().map(pred_json),
+                    "limit": limit,
+                    "strict": strict,
+                }),
+                vec![],
+            )
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            kind,
+            on,
+            build_left,
+        } => {
+            let on = on
+                .iter()
+                .map(|(l, r)| json!({"left": l, "right": r}))
+                .collect::<Vec<_>>();
+            let mut obj = json!({"node": "Join", "type": kind.to_string(), "on": on});
+            if let Some(l) = build_left {
+                obj["build"] = json!(if *l { "left" } else { "right" });
+            }
+            (obj, vec![left, right])
+        }
+        LogicalPlan::Filter { input, pred } => (
+            json!({"node": "Filter", "predicate": pred_json(pred), "text": pred.to_string()}),
+            vec![input],
+        ),
+        LogicalPlan::Aggregate {
+            input,
+            group_keys,
+            aggs,
+        } => {
+            let aggs = aggs.iter().map(agg_json).collect::<Vec<_>>();
+            (
+                json!({"node": "Aggregate", "group_keys": group_keys, "aggs": aggs}),
+                vec![input],
+            )
+        }
+        LogicalPlan::Project { input, exprs } => {
+            let exprs = exprs
+                .iter()
+                .map(|e| json!({"name": e.name, "expr": expr_json(&e.expr)}))
+                .collect::<Vec<_>>();
+            (json!({"node": "Project", "exprs": exprs}), vec![input])
+        }
+        LogicalPlan::Sort { input, keys } => (
+            json!({"node": "Sort", "keys": keys_json(keys)}),
+            vec![input],
+        ),
+        LogicalPlan::Limit { input, n } => (json!({"node": "Limit", "n": n}), vec![input]),
+        LogicalPlan::TopK { input, keys, n } => (
+            json!({"node": "TopK", "n": n, "keys": keys_json(keys)}),
+            vec![input],
+        ),
+        LogicalPlan::Empty { schema } => {
+            let columns = schema
+                .fields
+                .iter()
+                .map(|f| json!({"name": f.name, "type": f.dtype.to_string()}))
+                .collect::<Vec<_>>();
+            (json!({"node": "Empty", "columns": columns}), vec![])
+        }
+    };
+
+    if let Some(n) = cost.rows(plan) {
+        obj["est_rows"] = json!(n.ceil() as u64);
+    }
+    let inputs = inputs
+        .into_iter()
+        .map(|p| plan_json(p, cost))
+        .collect::<Vec<_>>();
+    obj["inputs"] = JsonValue::Array(inputs);
+    obj
+}
+
+/// Same shape as predicates in the JSON query DSL, except that the sides of
+/// a `{"left", "op", "right"}` comparison are structured as in `expr_json`.
+pub fn pred_json(pred: &PredExpr) -> JsonValue {
+    match pred {
+        PredExpr::And { and } => json!({"and": and.iter().map(pred_json).collect::<Vec<_>>()}),
+        PredExpr::Or { or } => json!({"or": or.iter().map(pred_json).collect::<Vec<_>>()}),
+        PredExpr::Not { not } => json!({"not": pred_json(not)}),
+        PredExpr::Cmp(p) => json!({"col": p.col, "op": p.op, "val": p.val}),
+        PredExpr::Compare(c) => json!({
+            "left": expr_json(&c.left),
+            "op": c.op,
+            "right": expr_json(&c.right),
+        }),
+    }
+}
+
+/// An expression as nested objects: `{"col": ..}`, `{"lit": ..}`,
+/// `{"neg": ..}`, `{"op", "left", "right"}` or an aggregate call.
+pub fn expr_json(expr: &Expr) -> JsonValue {
+    match expr {
+        Expr::Column(c) => json!({"col": c}),
+        Expr::Literal(v) => json!({"lit": v}),
+        Expr::Neg(e) => json!({"neg": expr_json(e)}),
+        Expr::Binary { left, op, right } => json!({
+            "op": op.symbol(),
+            "left": expr_json(left),
+            "right": expr_json(right),
+        }),
+        Expr::Agg {
+            func,
+            arg,
+            distinct,
+        } => json!({
+            "func": func.to_string(),
+            "arg": arg.as_deref().map(expr_json),
+            "distinct": distinct,
+        }),
+    }
+}
+
+pub fn agg_json(agg: &AggSpec) -> JsonValue {
+    json!({
+        "func": agg.func.to_string(),
+        "arg": agg.arg.as_ref().map(expr_json),
+        "distinct": agg.distinct,
+        "alias": agg.alias,
+    })
+}
+
+fn key_json(key: &OrderKey) -> JsonValue {
+    let dir = match key.dir {
+        SortDir::Asc => "asc",
+        SortDir::Desc => "desc",
+    };
+    let nulls = if key.nulls_first() { "first" } else { "last" };
+    json!({"col": key.col, "dir": dir, "nulls": nulls})
+}
+
+fn keys_json(keys: &[OrderKey]) -> JsonValue {
+    keys.iter().map(key_json).collect()
+}
+
+/// Sort keys as an operator setting.
+pub fn keys_detail(keys: &[OrderKey]) -> Detail {
+    Detail::list(keys.iter().map(|k| Detail::new(k, key_json(k))))
+}
+
+/// The physical operator tree as nested objects, shaped like `plan_json`.
+/// Instrumented runs add `stats` and the operator's counters under
+/// `metrics`.
+pub fn physical_json(node: &dyn ExecNode) -> JsonValue {
+    let mut obj = Map::new();
+    obj.insert("node".into(), node.name().into());
+    for (k, v) in node.details() {
+        obj.insert(k.into(), v.value);
+    }
+
+    if let Some(s) = node.stats() {
+        let metrics = node
+            .metrics()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), JsonValue::from(v)))
+            .collect::<Map<_, _>>();
+        obj.insert("metrics".into(), metrics.into());
+        obj.insert(
+            "stats".into(),
+            json!({
+                "rows_in": s.rows_in,
+                "rows_out": s.rows_out,
+                "batches": s.batches,
+                "time_ms": s.elapsed.as_secs_f64() * 1000.0,
+                "peak_memory_bytes": s.peak_memory,
+            }),
+        );
+    }
+
+    let inputs = node.children().into_iter().map(physical_json).collect();
+    obj.insert("inputs".into(), JsonValue::Array(inputs));
+    obj.into()
+}
+
+// ---------- Graphviz ----------
+
+/// A plan node reduced to display text, for rendering as a graph.
+pub struct DotNode {
+    name: String,
+    details: Vec<(String, String)>,
+    inputs: Vec<DotNode>,
+}
+
+impl DotNode {
+    pub fn logical(plan: &LogicalPlan, cost: &CostModel) -> Self {
+        fn list<T: ToString>(items: impl IntoIterator<Item = T>) -> String {
+            let items = items.into_iter().map(|i| i.to_string()).collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        }
+
+        let (name, mut details, inputs): (_, Vec<(&str, String)>, Vec<&LogicalPlan>) = match plan {
+            LogicalPlan::Scan {
+                path,
+                alias,
+                schema,
+                columns,
+                filter,
+                limit,
+                strict,
+            } => {
+                let mut d = vec![("path", path.clone())];
+                if let Some(a) = alias {
+                    d.push(("as", a.clone()));
+                }
+                let columns = schema
+                    .select(columns.as_deref())
+                    .into_iter()
+                    .map(|f| format!("{}: {}", f.name, f.dtype));
+                d.push(("columns", list(columns)));
+                if let Some(f) = filter {
+                    d.push(("filter", f.to_string()));
+                }
+                if let Some(n) = limit {
+                    d.push(("limit", n.to_string()));
+                }
+                d.push(("strict", strict.to_string()));
+                ("Scan", d, vec![])
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                build_left,
+            } => {
+                let on = on.iter().map(|(l, r)| format!("{l} = {r}"));
+                let mut d = vec![("type", kind.to_string()), ("on", list(on))];
+                if let Some(l) = build_left {
+                    d.push(("build", if *l { "left" } else { "right" }.to_string()));
+                }
+                ("Join", d, vec![left, right])
+            }
+            LogicalPlan::Filter { input, pred } => {
+                ("Filter", vec![("predicate", pred.to_string())], vec![input])
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_keys,
+                aggs,
+            } => (
+                "Aggregate",
+                vec![
+                    ("group_keys", list(group_keys)),
+                    ("aggs", list(aggs.iter().map(|a| &a.alias))),
+                ],
+                vec![input],
+            ),
+            LogicalPlan::Project { input, exprs } => {
+                ("Project", vec![("exprs", list(exprs))], vec![input])
+            }
+            LogicalPlan::Sort { input, keys } => ("Sort", vec![("keys", list(keys))], vec![input]),
+            LogicalPlan::Limit { input, n } => ("Limit", vec![("n", n.to_string())], vec![input]),
+            LogicalPlan::TopK { input, keys, n } => (
+                "TopK",
+                vec![("n", n.to_string()), ("keys", list(keys))],
+                vec![input],
+            ),
+            LogicalPlan::Empty { schema } => {
+                let columns = schema
+                    .fields
+                    .iter()
+                    .map(|f| format!("{}: {}", f.name, f.dtype));
+                ("Empty", vec![("columns", list(columns))], vec![])
+            }
+        };
+
+        if let Some(n) = cost.rows(plan) {
+            details.push(("est_rows", (n.ceil() as u64).to_string()));
+        }
+
+        Self {
+            name: name.to_string(),
+            details: details
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            inputs: inputs
+                .into_iter()
+                .map(|p| DotNode::logical(p, cost))
+                .collect(),
+        }
+    }
+
+    pub fn physical(node: &dyn ExecNode) -> Self {
+        let mut details = node
+            .details()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.text))
+            .collect::<Vec<_>>();
+        if let Some(s) = node.stats() {
+            details.extend(
+                node.metrics()
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string())),
+            );
+            if let Some(n) = s.rows_in {
+                details.push(("rows_in".into(), n.to_string()));
+            }
+            details.push(("rows_out".into(), s.rows_out.to_string()));
+            details.push((
+                "time".into(),
+                format!("{:.3}ms", s.elapsed.as_secs_f64() * 1000.0),
+            ));
+            details.push(("peak_mem".into(), format_bytes(s.peak_memory)));
+        }
+
+        Self {
+            name: node.name().to_string(),
+            details,
+            inputs: node.children().into_iter().map(DotNode::physical).collect(),
+        }
+    }
+}
+
+/// A Graphviz digraph with an edge from each node to its inputs. Several
+/// trees (e.g. original and optimized plans) are drawn as labelled clusters.
+pub fn format_dot(trees: &[(&str, DotNode)]) -> String {
+    let mut out = String::from("digraph plan {\n  node [shape=box, fontname=\"monospace\"];\n");
+    let mut next_id = 0;
+    for (i, (label, tree)) in trees.iter().enumerate() {
+        if trees.len() > 1 {
+            out.push_str(&format!(
+                "  subgraph cluster_{i} {{\n    label=\"{}\";\n",
+                dot_escape(label)
+            ));
+        }
+        dot_node(tree, &mut next_id, &mut out);
+        if trees.len() > 1 {
+            out.push_str("  }\n");
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+// Emits the node and its inputs; returns the node's id.
+fn dot_node(node: &DotNode, next_id: &mut usize, out: &mut String) -> String {
+    let id = format!("n{next_id}");
+    *next_id += 1;
+
+    // One left-aligned line per setting under the operator name.
+    let mut label = format!("{}\\l", dot_escape(&node.name));
+    for (k, v) in &node.details {
+        label.push_str(&format!("{}={}\\l", dot_escape(k), dot_escape(v)));
+    }
+    out.push_str(&format!("  {id} [label=\"{label}\"];\n"));
+
+    for input in &node.inputs {
+        let child = dot_node(input, next_id, out);
+        out.push_str(&format!("  {id} -> {child};\n"));
+    }
+    id
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
 }
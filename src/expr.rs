@@ -0,0 +1,559 @@
+use anyhow::{Result, anyhow, bail};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::fmt;
+
+use crate::batch::{Bitmap, Column, ColumnData, RecordBatch, Scalar};
+use crate::exec::AggFunc;
+use crate::lexer::{Token, TokenKind, TokenStream};
+use crate::schema::{DataType, Schema};
+
+/// Scalar expression used by select items and aggregate arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(String),
+    Literal(JsonValue),
+    Neg(Box<Expr>),
+    Binary {
+        left: Box<Expr>,
+        op: BinOp,
+        right: Box<Expr>,
+    },
+    /// Aggregate call; `arg` is `None` for `count(*)`. Planning replaces these
+    /// with references to the aggregate's output column.
+    Agg {
+        func: AggFunc,
+        arg: Option<Box<Expr>>,
+        distinct: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl BinOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Add | BinOp::Sub => 1,
+            BinOp::Mul | BinOp::Div | BinOp::Mod => 2,
+        }
+    }
+}
+
+/// One entry of a `select` list: an expression with an optional `AS` alias.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SelectItem {
+    pub expr: Expr,
+    pub alias: Option<String>,
+}
+
+impl SelectItem {
+    /// Output column name: the alias if given, otherwise the expression text.
+    pub fn name(&self) -> String {
+        self.alias.clone().unwrap_or_else(|| self.expr.to_string())
+    }
+}
+
+impl TryFrom<String> for SelectItem {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        let mut ts = TokenStream::new(&s)?;
+        let item = parse_select_item(&mut ts)?;
+        ts.expect_eof()?;
+        Ok(item)
+    }
+}
+
+/// An expression bound to the name of the column it produces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedExpr {
+    pub expr: Expr,
+    pub name: String,
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Expr> {
+        let mut ts = TokenStream::new(s)?;
+        let e = parse_expr(&mut ts)?;
+        ts.expect_eof()?;
+        Ok(e)
+    }
+
+    pub fn contains_agg(&self) -> bool {
+        match self {
+            Expr::Agg { .. } => true,
+            Expr::Neg(e) => e.contains_agg(),
+            Expr::Binary { left, right, .. } => left.contains_agg() || right.contains_agg(),
+            Expr::Column(_) | Expr::Literal(_) => false,
+        }
+    }
+
+    /// Every column name the expression reads, including aggregate arguments.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut out = Vec::new();
+        self.collect_columns(&mut out);
+        out
+    }
+
+    fn collect_columns<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Expr::Column(c) => out.push(c),
+            Expr::Literal(_) => {}
+            Expr::Neg(e) => e.collect_columns(out),
+            Expr::Binary { left, right, .. } => {
+                left.collect_columns(out);
+                right.collect_columns(out);
+            }
+            Expr::Agg { arg, .. } => {
+                if let Some(a) = arg {
+                    a.collect_columns(out);
+                }
+            }
+        }
+    }
+
+    /// Replace each column reference with the expression `lookup` gives for
+    /// it; `None` if `lookup` has none for some column.
+    pub fn substitute(&self, lookup: &dyn Fn(&str) -> Option<Expr>) -> Option<Expr> {
+        Some(match self {
+            Expr::Column(c) => lookup(c)?,
+            Expr::Literal(_) => self.clone(),
+            Expr::Neg(e) => Expr::Neg(Box::new(e.substitute(lookup)?)),
+            Expr::Binary { left, op, right } => Expr::Binary {
+                left: Box::new(left.substitute(lookup)?),
+                op: *op,
+                right: Box::new(right.substitute(lookup)?),
+            },
+            Expr::Agg {
+                func,
+                arg,
+                distinct,
+            } => Expr::Agg {
+                func: *func,
+                arg: match arg {
+                    Some(a) => Some(Box::new(a.substitute(lookup)?)),
+                    None => None,
+                },
+                distinct: *distinct,
+            },
+        })
+    }
+
+    /// Replace subexpressions over literals alone by their value. Any that
+    /// would overflow or be null are left for evaluation to handle.
+    pub fn fold(&self) -> Expr {
+        match self {
+            Expr::Neg(e) => {
+                let e = e.fold();
+                let folded = match &e {
+                    Expr::Literal(v) => match Scalar::from_json(v) {
+                        Scalar::Int64(i) => i.checked_neg().map(JsonValue::from),
+                        Scalar::Float64(f) => Some(JsonValue::from(-f)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                folded.map_or_else(|| Expr::Neg(Box::new(e)), Expr::Literal)
+            }
+            Expr::Binary { left, op, right } => {
+                let (left, right) = (left.fold(), right.fold());
+                if let (Expr::Literal(a), Expr::Literal(b)) = (&left, &right)
+                    && let Some(v) = fold_arith(*op, a, b)
+                {
+                    return Expr::Literal(v);
+                }
+                Expr::Binary {
+                    left: Box::new(left),
+                    op: *op,
+                    right: Box::new(right),
+                }
+            }
+            Expr::Agg {
+                func,
+                arg,
+                distinct,
+            } => Expr::Agg {
+                func: *func,
+                arg: arg.as_ref().map(|a| Box::new(a.fold())),
+                distinct: *distinct,
+            },
+            Expr::Column(_) | Expr::Literal(_) => self.clone(),
+        }
+    }
+
+    /// Type of the values the expression produces over input `schema`.
+    pub fn data_type(&self, schema: &Schema) -> Result<DataType> {
+        Ok(match self {
+            Expr::Column(c) => match schema.field(c) {
+                Some(f) => f.dtype,
+                None => bail!("unknown column `{c}`"),
+            },
+            // A bare null has no type of its own; int64 mixes with any number.
+            Expr::Literal(v) => Scalar::from_json(v).data_type().unwrap_or(DataType::Int64),
+            Expr::Neg(e) => match e.data_type(schema)? {
+                t @ (DataType::Int64 | DataType::Float64) => t,
+                t => bail!("cannot negate {t} expression `{e}`"),
+            },
+            Expr::Binary { left, op, right } => {
+                let (l, r) = (left.data_type(schema)?, right.data_type(schema)?);
+                match (l, r) {
+                    (DataType::Int64, DataType::Int64) if *op != BinOp::Div => DataType::Int64,
+                    (DataType::Int64 | DataType::Float64, DataType::Int64 | DataType::Float64) => {
+                        DataType::Float64
+                    }
+                    _ => bail!("cannot apply `{}` to {l} and {r} in `{self}`", op.symbol()),
+                }
+            }
+            Expr::Agg { .. } => bail!("aggregate `{self}` used outside of an aggregation"),
+        })
+    }
+
+    /// Evaluate over every row of a batch, producing a column of
+    /// `data_type`. Nulls propagate through arithmetic.
+    pub fn eval(&self, batch: &RecordBatch) -> Result<Column> {
+        let n = batch.num_rows();
+        match self {
+            Expr::Column(c) => batch
+                .column_by_name(c)
+                .cloned()
+                .ok_or_else(|| anyhow!("unknown column `{c}`")),
+            Expr::Literal(v) => {
+                Column::repeat(&Scalar::from_json(v), self.data_type(batch.schema())?, n)
+            }
+            Expr::Neg(e) => {
+                let col = e.eval(batch)?;
+                let mut out = Column::with_capacity(col.data_type(), n);
+                for i in 0..n {
+                    out.push(match col.value(i) {
+                        Scalar::Int64(x) => x.checked_neg().map_or(Scalar::Null, Scalar::Int64),
+                        Scalar::Float64(x) => Scalar::Float64(-x),
+                        other => other,
+                    })?;
+                }
+                Ok(out)
+            }
+            Expr::Binary { left, op, right } => {
+                let dtype = self.data_type(batch.schema())?;
+                Ok(arith(*op, &left.eval(batch)?, &right.eval(batch)?, dtype))
+            }
+            Expr::Agg { .. } => bail!("aggregate `{self}` used outside of an aggregation"),
+        }
+    }
+}
+
+/// Elementwise arithmetic. Integer results stay integers, and one that
+/// overflows is null; `/` always yields a float; division by zero yields
+/// null.
+fn arith(op: BinOp, l: &Column, r: &Column, dtype: DataType) -> Column {
+    let n = l.len();
+    let mut validity = Bitmap::with_capacity(n);
+
+    if let (ColumnData::Int64(a), ColumnData::Int64(b), DataType::Int64) = (&l.data, &r.data, dtype)
+    {
+        let mut out = Vec::with_capacity(n);
+        for i in 0..n {
+            let v = if !l.is_valid(i) || !r.is_valid(i) || (op == BinOp::Mod && b[i] == 0) {
+                None
+            } else {
+                match op {
+                    BinOp::Add => a[i].checked_add(b[i]),
+                    BinOp::Sub => a[i].checked_sub(b[i]),
+                    BinOp::Mul => a[i].checked_mul(b[i]),
+                    BinOp::Mod => a[i].checked_rem(b[i]),
+                    BinOp::Div => unreachable!("division always yields float64"),
+                }
+            };
+            validity.push(v.is_some());
+            out.push(v.unwrap_or(0));
+        }
+        return Column {
+            data: ColumnData::Int64(out),
+            validity,
+        };
+    }
+
+    let mut out = Vec::with_capacity(n);
+    for i in 0..n {
+        let v = match (l.f64_at(i), r.f64_at(i)) {
+            (Some(_), Some(b)) if b == 0.0 && matches!(op, BinOp::Div | BinOp::Mod) => None,
+            (Some(a), Some(b)) => Some(match op {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul => a * b,
+                BinOp::Div => a / b,
+                BinOp::Mod => a % b,
+            }),
+            _ => None,
+        };
+        validity.push(v.is_some());
+        out.push(v.unwrap_or(0.0));
+    }
+    Column {
+        data: ColumnData::Float64(out),
+        validity,
+    }
+}
+
+// `arith` on two literals; `None` where it would yield null, overflow or
+// involve a non-number.
+fn fold_arith(op: BinOp, a: &JsonValue, b: &JsonValue) -> Option<JsonValue> {
+    match (Scalar::from_json(a), Scalar::from_json(b)) {
+        (Scalar::Int64(x), Scalar::Int64(y)) if op != BinOp::Div => {
+            let v = match op {
+                BinOp::Add => x.checked_add(y),
+                BinOp::Sub => x.checked_sub(y),
+                BinOp::Mul => x.checked_mul(y),
+                BinOp::Mod => x.checked_rem(y),
+                BinOp::Div => unreachable!("division always yields float64"),
+            };
+            v.map(JsonValue::from)
+        }
+        (x, y) => {
+            let (x, y) = (x.as_f64()?, y.as_f64()?);
+            if y == 0.0 && matches!(op, BinOp::Div | BinOp::Mod) {
+                return None;
+            }
+            let v = match op {
+                BinOp::Add => x + y,
+                BinOp::Sub => x - y,
+                BinOp::Mul => x * y,
+                BinOp::Div => x / y,
+                BinOp::Mod => x % y,
+            };
+            v.is_finite().then(|| JsonValue::from(v))
+        }
+    }
+}
+
+// ---------- parsing ----------
+
+pub fn parse_select_item(ts: &mut TokenStream) -> Result<SelectItem> {
+    let expr = parse_expr(ts)?;
+    let alias = if ts.eat_keyword("as") {
+        Some(ts.expect_ident()?)
+    } else {
+        None
+    };
+    Ok(SelectItem { expr, alias })
+}
+
+pub fn parse_expr(ts: &mut TokenStream) -> Result<Expr> {
+    parse_binary(ts, 1)
+}
+
+fn parse_binary(ts: &mut TokenStream, min_prec: u8) -> Result<Expr> {
+    let mut left = parse_unary(ts)?;
+
+    loop {
+        let op = match ts.peek_kind() {
+            TokenKind::Symbol("+") => BinOp::Add,
+            TokenKind::Symbol("-") => BinOp::Sub,
+            TokenKind::Symbol("*") => BinOp::Mul,
+            TokenKind::Symbol("/") => BinOp::Div,
+            TokenKind::Symbol("%") => BinOp::Mod,
+            _ => break,
+        };
+        if op.precedence() < min_prec {
+            break;
+        }
+        ts.advance();
+
+        let right = parse_binary(ts, op.precedence() + 1)?;
+        left = Expr::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_unary(ts: &mut TokenStream) -> Result<Expr> {
+    if ts.eat_symbol("-") {
+        return Ok(match parse_unary(ts)? {
+            Expr::Literal(v) if v.is_number() => Expr::Literal(match v.as_i64() {
+                Some(i) => JsonValue::from(-i),
+                None => JsonValue::from(-v.as_f64().unwrap_or(0.0)),
+            }),
+            e => Expr::Neg(Box::new(e)),
+        });
+    }
+    parse_primary(ts)
+}
+
+fn parse_primary(ts: &mut TokenStream) -> Result<Expr> {
+    let tok = ts.advance();
+    Ok(match tok.kind.clone() {
+        TokenKind::Int(i) => Expr::Literal(JsonValue::from(i)),
+        TokenKind::Float(f) => Expr::Literal(JsonValue::from(f)),
+        TokenKind::Str(s) => Expr::Literal(JsonValue::from(s)),
+        TokenKind::Symbol("(") => {
+            let e = parse_expr(ts)?;
+            ts.expect_symbol(")")?;
+            e
+        }
+        TokenKind::Ident(name) => {
+            if ts.peek_kind() == &TokenKind::Symbol("(") {
+                ts.advance();
+                return parse_call(ts, &name, &tok);
+            }
+            match name.to_ascii_lowercase().as_str() {
+                "true" => Expr::Literal(JsonValue::from(true)),
+                "false" => Expr::Literal(JsonValue::from(false)),
+                "null" => Expr::Literal(JsonValue::Null),
+                kw if is_reserved(kw) => {
+                    return Err(ts.error_at(&tok, &format!("expected expression, found `{name}`")));
+                }
+                _ => Expr::Column(qualified_name(ts, name)?),
+            }
+        }
+        TokenKind::QuotedIdent(name) => Expr::Column(qualified_name(ts, name)?),
+        other => return Err(ts.error_at(&tok, &format!("expected expression, found {other}"))),
+    })
+}
+
+/// Column name with optional table qualifier, e.g. `t.user_id`.
+pub fn parse_column_name(ts: &mut TokenStream) -> Result<String> {
+    let first = ts.expect_ident()?;
+    qualified_name(ts, first)
+}
+
+fn qualified_name(ts: &mut TokenStream, mut name: String) -> Result<String> {
+    while ts.eat_symbol(".") {
+        name.push('.');
+        name.push_str(&ts.expect_ident()?);
+    }
+    Ok(name)
+}
+
+/// Keywords that cannot be used as bare column names; quote them instead.
+const RESERVED: &[&str] = &[
+    "select", "from", "where", "group", "by", "having", "order", "limit", "and", "or", "not", "as",
+    "asc", "desc", "nulls", "distinct", "join", "inner", "left", "right", "full", "outer", "on",
+];
+
+pub fn is_reserved(lower: &str) -> bool {
+    RESERVED.contains(&lower)
+}
+
+// Called with the opening parenthesis already consumed.
+fn parse_call(ts: &mut TokenStream, name: &str, at: &Token) -> Result<Expr> {
+    let func = match name.to_ascii_lowercase().as_str() {
+        "sum" => AggFunc::Sum,
+        "count" => AggFunc::Count,
+        "avg" => AggFunc::Avg,
+        "min" => AggFunc::Min,
+        "max" => AggFunc::Max,
+        _ => return Err(ts.error_at(at, &format!("unknown function `{name}`"))),
+    };
+
+    let distinct = ts.eat_keyword("distinct");
+    if distinct && func != AggFunc::Count {
+        return Err(ts.error_at(at, &format!("DISTINCT is not supported for `{func}`")));
+    }
+
+    let arg = if func == AggFunc::Count && !distinct && ts.eat_symbol("*") {
+        None
+    } else {
+        Some(Box::new(parse_expr(ts)?))
+    };
+    ts.expect_symbol(")")?;
+
+    Ok(Expr::Agg {
+        func,
+        arg,
+        distinct,
+    })
+}
+
+// ---------- display ----------
+
+// Plain identifiers, optionally dot-qualified, display without quotes.
+fn is_plain_ident(s: &str) -> bool {
+    s.split('.').all(|part| {
+        let mut chars = part.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Column(c) if is_plain_ident(c) => write!(f, "{c}"),
+            Expr::Column(c) => write!(f, "\"{}\"", c.replace('"', "\"\"")),
+            Expr::Literal(JsonValue::String(s)) => write!(f, "'{}'", s.replace('\'', "''")),
+            Expr::Literal(v) => write!(f, "{v}"),
+            Expr::Neg(e) => match **e {
+                Expr::Binary { .. } => write!(f, "-({e})"),
+                _ => write!(f, "-{e}"),
+            },
+            Expr::Binary { left, op, right } => {
+                let prec = op.precedence();
+                match &**left {
+                    Expr::Binary { op: lop, .. } if lop.precedence() < prec => {
+                        write!(f, "({left})")?
+                    }
+                    _ => write!(f, "{left}")?,
+                }
+                write!(f, " {} ", op.symbol())?;
+                // Parsing is left-associative, so an equal-precedence right
+                // operand needs parentheses to round-trip.
+                match &**right {
+                    Expr::Binary { op: rop, .. } if rop.precedence() <= prec => {
+                        write!(f, "({right})")
+                    }
+                    _ => write!(f, "{right}"),
+                }
+            }
+            Expr::Agg {
+                func,
+                arg,
+                distinct,
+            } => match (arg, distinct) {
+                (Some(a), true) => write!(f, "{func}(DISTINCT {a})"),
+                (Some(a), false) => write!(f, "{func}({a})"),
+                (None, _) => write!(f, "{func}(*)"),
+            },
+        }
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.alias {
+            Some(a) => write!(f, "{} AS {a}", self.expr),
+            None => write!(f, "{}", self.expr),
+        }
+    }
+}
+
+impl fmt::Display for NamedExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expr {
+            Expr::Column(c) if *c == self.name => write!(f, "{c}"),
+            e => write!(f, "{e} AS {}", self.name),
+        }
+    }
+}
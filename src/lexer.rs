@@ -0,0 +1,262 @@
+use anyhow::{Result, bail};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    /// `"quoted"` or `` `quoted` `` identifier; never treated as a keyword.
+    QuotedIdent(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Symbol(&'static str),
+    Eof,
+}
+
+/// A token plus the 1-based line and column where it starts.
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Ident(s) => write!(f, "`{s}`"),
+            TokenKind::QuotedIdent(s) => write!(f, "\"{s}\""),
+            TokenKind::Int(i) => write!(f, "`{i}`"),
+            TokenKind::Float(x) => write!(f, "`{x}`"),
+            TokenKind::Str(s) => write!(f, "'{s}'"),
+            TokenKind::Symbol(s) => write!(f, "`{s}`"),
+            TokenKind::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+// Longest symbols first so `<=` wins over `<`.
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "==", "!=", "<>", "(", ")", ",", ".", "+", "-", "*", "/", "%", "<", ">", "=", ";",
+];
+
+pub fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let (mut i, mut line, mut col) = (0usize, 1usize, 1usize);
+
+    while i < chars.len() {
+        let c = chars[i];
+        let (start_line, start_col) = (line, col);
+
+        // whitespace
+        if c.is_whitespace() {
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+            i += 1;
+            continue;
+        }
+
+        // `--` line comment
+        if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        let start = i;
+        let kind = if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Ident(chars[start..i].iter().collect())
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let mut is_float = false;
+            if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()) {
+                is_float = true;
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            match (is_float, text.parse::<i64>()) {
+                (false, Ok(n)) => TokenKind::Int(n),
+                _ => TokenKind::Float(text.parse::<f64>()?),
+            }
+        } else if c == '\'' || c == '"' || c == '`' {
+            // 'string literal', "quoted identifier" or `quoted identifier`;
+            // a doubled quote character escapes itself.
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => bail!("unterminated quote at line {start_line}, column {start_col}"),
+                    Some(&q) if q == c => {
+                        if chars.get(i + 1) == Some(&c) {
+                            text.push(c);
+                            i += 2;
+                        } else {
+                            i += 1;
+                            break;
+                        }
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            if c == '\'' {
+                TokenKind::Str(text)
+            } else {
+                TokenKind::QuotedIdent(text)
+            }
+        } else if let Some(sym) = SYMBOLS.iter().find(|s| {
+            s.chars()
+                .enumerate()
+                .all(|(k, sc)| chars.get(i + k) == Some(&sc))
+        }) {
+            i += sym.len();
+            TokenKind::Symbol(sym)
+        } else {
+            bail!("unexpected character '{c}' at line {line}, column {col}");
+        };
+
+        // Tokens never span lines except quoted text; count columns per char.
+        for ch in &chars[start..i] {
+            if *ch == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            col: start_col,
+        });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        line,
+        col,
+    });
+    Ok(tokens)
+}
+
+/// Cursor over a token list shared by the expression and SQL parsers.
+pub struct TokenStream {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl TokenStream {
+    pub fn new(src: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(src)?,
+            pos: 0,
+        })
+    }
+
+    pub fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    pub fn peek_kind(&self) -> &TokenKind {
+        &self.peek().kind
+    }
+
+    pub fn advance(&mut self) -> Token {
+        let tok = self.tokens[self.pos].clone();
+        // The trailing Eof token is never consumed.
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    pub fn eat_symbol(&mut self, sym: &str) -> bool {
+        if matches!(self.peek_kind(), TokenKind::Symbol(s) if *s == sym) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    pub fn is_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek_kind(), TokenKind::Ident(s) if s.eq_ignore_ascii_case(kw))
+    }
+
+    pub fn eat_keyword(&mut self, kw: &str) -> bool {
+        if self.is_keyword(kw) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    pub fn expect_symbol(&mut self, sym: &str) -> Result<()> {
+        if self.eat_symbol(sym) {
+            return Ok(());
+        }
+        Err(self.error(&format!("expected `{sym}`, found {}", self.peek_kind())))
+    }
+
+    pub fn expect_ident(&mut self) -> Result<String> {
+        match self.peek_kind().clone() {
+            TokenKind::Ident(s) | TokenKind::QuotedIdent(s) => {
+                self.advance();
+                Ok(s)
+            }
+            other => Err(self.error(&format!("expected identifier, found {other}"))),
+        }
+    }
+
+    pub fn expect_keyword(&mut self, kw: &str) -> Result<()> {
+        if self.eat_keyword(kw) {
+            return Ok(());
+        }
+        Err(self.error(&format!(
+            "expected `{}`, found {}",
+            kw.to_ascii_uppercase(),
+            self.peek_kind()
+        )))
+    }
+
+    /// Current position, for backtracking with `reset`.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn reset(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    pub fn expect_eof(&self) -> Result<()> {
+        match self.peek_kind() {
+            TokenKind::Eof => Ok(()),
+            other => Err(self.error(&format!("unexpected {other}"))),
+        }
+    }
+
+    /// Error pointing at the current token.
+    pub fn error(&self, msg: &str) -> anyhow::Error {
+        self.error_at(self.peek(), msg)
+    }
+
+    pub fn error_at(&self, tok: &Token, msg: &str) -> anyhow::Error {
+        anyhow::anyhow!("{msg} at line {}, column {}", tok.line, tok.col)
+    }
+}
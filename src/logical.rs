@@ -1,14 +1,42 @@
-use crate::ast::{Predicate, Query};
-use crate::exec::{AggFunc, AggSpec};
+use anyhow::{Result, anyhow, bail};
+use std::path::Path;
 
-#[derive(Debug, Clone)]
+use crate::ast::{Comparison, JoinKind, JoinOn, OrderKey, PredExpr, Predicate, Query};
+use crate::exec::AggSpec;
+use crate::expr::{Expr, NamedExpr, SelectItem};
+use crate::schema::{ScanOptions, Schema};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum LogicalPlan {
     Scan {
         path: String,
+        /// When set, columns come out qualified as `alias.col`.
+        alias: Option<String>,
+        schema: Schema,
+        /// File columns to read, set by projection pushdown; `None` reads
+        /// every column.
+        columns: Option<Vec<String>>,
+        /// Predicate checked on each raw record before it is parsed, set by
+        /// predicate pushdown.
+        filter: Option<PredExpr>,
+        /// Records to output at most, set by limit pushdown.
+        limit: Option<usize>,
+        strict: bool,
+    },
+    /// Equi-join; each `on` pair is (left column, right column).
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        kind: JoinKind,
+        on: Vec<(String, String)>,
+        /// Whether the hash table is built from the left input, chosen by
+        /// the optimizer from statistics; `None` leaves it to the physical
+        /// planner.
+        build_left: Option<bool>,
     },
     Filter {
         input: Box<LogicalPlan>,
-        preds: Vec<Predicate>,
+        pred: PredExpr,
     },
     Aggregate {
         input: Box<LogicalPlan>,
@@ -17,64 +45,322 @@ pub enum LogicalPlan {
     },
     Project {
         input: Box<LogicalPlan>,
-        cols: Vec<String>,
+        exprs: Vec<NamedExpr>,
+    },
+    Sort {
+        input: Box<LogicalPlan>,
+        keys: Vec<OrderKey>,
     },
     Limit {
         input: Box<LogicalPlan>,
         n: usize,
     },
+    /// Sort followed by Limit, fused by the optimizer.
+    TopK {
+        input: Box<LogicalPlan>,
+        keys: Vec<OrderKey>,
+        n: usize,
+    },
+    /// No rows, with the columns of the subtree it replaced; the optimizer
+    /// substitutes it for subtrees whose filter can never pass.
+    Empty {
+        schema: Schema,
+    },
 }
 
-fn parse_select(select: &[String]) -> (Vec<String>, Vec<AggSpec>) {
-    let mut cols_out: Vec<String> = Vec::new();
+impl LogicalPlan {
+    /// Rebuild this node with `f` applied to each of its direct inputs.
+    pub fn map_children(self, mut f: impl FnMut(LogicalPlan) -> LogicalPlan) -> LogicalPlan {
+        let mut map = |input: Box<LogicalPlan>| Box::new(f(*input));
+        match self {
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                build_left,
+            } => LogicalPlan::Join {
+                left: map(left),
+                right: map(right),
+                kind,
+                on,
+                build_left,
+            },
+            LogicalPlan::Filter { input, pred } => LogicalPlan::Filter {
+                input: map(input),
+                pred,
+            },
+            LogicalPlan::Aggregate {
+                input,
+                group_keys,
+                aggs,
+            } => LogicalPlan::Aggregate {
+                input: map(input),
+                group_keys,
+                aggs,
+            },
+            LogicalPlan::Project { input, exprs } => LogicalPlan::Project {
+                input: map(input),
+                exprs,
+            },
+            LogicalPlan::Sort { input, keys } => LogicalPlan::Sort {
+                input: map(input),
+                keys,
+            },
+            LogicalPlan::Limit { input, n } => LogicalPlan::Limit {
+                input: map(input),
+                n,
+            },
+            LogicalPlan::TopK { input, keys, n } => LogicalPlan::TopK {
+                input: map(input),
+                keys,
+                n,
+            },
+            leaf @ (LogicalPlan::Scan { .. } | LogicalPlan::Empty { .. }) => leaf,
+        }
+    }
+
+    /// Rewrite every node with `f`, inputs before the nodes that read them,
+    /// so `f` always sees already rewritten inputs.
+    pub fn transform_up(self, f: &mut impl FnMut(LogicalPlan) -> LogicalPlan) -> LogicalPlan {
+        let node = self.map_children(|c| c.transform_up(f));
+        f(node)
+    }
+
+    /// Rewrite every node with `f`, then descend into the inputs of what it
+    /// returned, so nodes `f` moves down are visited again below.
+    pub fn transform_down(self, f: &mut impl FnMut(LogicalPlan) -> LogicalPlan) -> LogicalPlan {
+        f(self).map_children(|c| c.transform_down(f))
+    }
+}
+
+fn parse_select(select: &[SelectItem]) -> Result<(Vec<NamedExpr>, Vec<AggSpec>)> {
+    let mut exprs: Vec<NamedExpr> = Vec::new();
     let mut aggs: Vec<AggSpec> = Vec::new();
 
-    for s in select {
-        let t = s.trim();
-
-        // sum(col)
-        if let Some(inner) = t.strip_prefix("sum(").and_then(|x| x.strip_suffix(')')) {
-            let col = inner.trim().to_string();
-            let alias = format!("sum({})", col);
-            cols_out.push(alias.clone());
-            aggs.push(AggSpec {
-                func: AggFunc::Sum,
-                col,
-                alias,
-            });
-            continue;
+    for item in select {
+        exprs.push(NamedExpr {
+            expr: extract_aggs(&item.expr, &mut aggs)?,
+            name: item.name(),
+        });
+    }
+
+    Ok((exprs, aggs))
+}
+
+/// Replace every aggregate call with a reference to its output column,
+/// registering the aggregate (once per distinct call) in `aggs`.
+fn extract_aggs(expr: &Expr, aggs: &mut Vec<AggSpec>) -> Result<Expr> {
+    Ok(match expr {
+        Expr::Agg {
+            func,
+            arg,
+            distinct,
+        } => {
+            if arg.as_ref().is_some_and(|a| a.contains_agg()) {
+                bail!("nested aggregate functions are not allowed: {expr}");
+            }
+            let alias = expr.to_string();
+            if !aggs.iter().any(|a| a.alias == alias) {
+                aggs.push(AggSpec {
+                    func: *func,
+                    arg: arg.as_deref().cloned(),
+                    distinct: *distinct,
+                    alias: alias.clone(),
+                });
+            }
+            Expr::Column(alias)
+        }
+        Expr::Neg(e) => Expr::Neg(Box::new(extract_aggs(e, aggs)?)),
+        Expr::Binary { left, op, right } => Expr::Binary {
+            left: Box::new(extract_aggs(left, aggs)?),
+            op: *op,
+            right: Box::new(extract_aggs(right, aggs)?),
+        },
+        Expr::Column(_) | Expr::Literal(_) => expr.clone(),
+    })
+}
+
+/// Resolve each HAVING leaf to columns of the aggregate output: group keys,
+/// aggregate calls (registered in `aggs` if not selected) or aliases of
+/// selected aggregates.
+fn plan_having(
+    having: &PredExpr,
+    group_keys: &[String],
+    select: &[SelectItem],
+    aggs: &mut Vec<AggSpec>,
+) -> Result<PredExpr> {
+    having.try_map_leaves(&mut |leaf: &PredExpr| {
+        let p = match leaf {
+            PredExpr::Cmp(p) => p,
+            PredExpr::Compare(c) => {
+                return Ok(PredExpr::Compare(Comparison {
+                    left: having_expr(&c.left, group_keys, select, aggs)?,
+                    op: c.op.clone(),
+                    right: having_expr(&c.right, group_keys, select, aggs)?,
+                }));
+            }
+            _ => unreachable!("only leaves are mapped"),
+        };
+        let resolved = |col: String| Predicate {
+            col,
+            op: p.op.clone(),
+            val: p.val.clone(),
+        };
+
+        if group_keys.contains(&p.col) {
+            return Ok(PredExpr::Cmp(resolved(p.col.clone())));
+        }
+
+        let alias_target = select
+            .iter()
+            .find(|s| s.alias.as_deref() == Some(p.col.as_str()))
+            .map(|s| s.expr.clone());
+
+        let expr = match alias_target {
+            Some(e) => e,
+            None => Expr::parse(&p.col)
+                .map_err(|e| anyhow!("Invalid HAVING column `{}`: {e}", p.col))?,
+        };
+
+        match extract_aggs(&expr, aggs)? {
+            Expr::Column(c) if expr.contains_agg() || group_keys.contains(&c) => {
+                Ok(PredExpr::Cmp(resolved(c)))
+            }
+            _ if expr.contains_agg() => bail!(
+                "HAVING `{}` must compare a single aggregate or group key",
+                p.col
+            ),
+            _ => bail!(
+                "HAVING references column `{}`, which is neither grouped nor aggregated",
+                p.col
+            ),
         }
+    })
+}
 
-        // count(*)
-        if t.eq_ignore_ascii_case("count(*)") {
-            let alias = "count(*)".to_string();
-            cols_out.push(alias.clone());
-            aggs.push(AggSpec {
-                func: AggFunc::Count,
-                col: "*".to_string(),
-                alias,
-            });
-            continue;
+// A side of a HAVING comparison over the aggregate output: aliases of
+// selected items are expanded and aggregate calls become their columns.
+fn having_expr(
+    expr: &Expr,
+    group_keys: &[String],
+    select: &[SelectItem],
+    aggs: &mut Vec<AggSpec>,
+) -> Result<Expr> {
+    let alias = |c: &str| {
+        let target = select
+            .iter()
+            .find(|s| !group_keys.iter().any(|k| k == c) && s.alias.as_deref() == Some(c));
+        Some(target.map_or_else(|| Expr::Column(c.to_string()), |s| s.expr.clone()))
+    };
+    let expr = extract_aggs(expr, aggs)?
+        .substitute(&alias)
+        .expect("every column has a replacement");
+    let expr = extract_aggs(&expr, aggs)?;
+    for c in expr.columns() {
+        if !group_keys.iter().any(|k| k == c) && !aggs.iter().any(|a| a.alias == c) {
+            bail!("HAVING references column `{c}`, which is neither grouped nor aggregated");
         }
+    }
+    Ok(expr)
+}
 
-        // plain column
-        cols_out.push(t.to_string());
+// Sources are qualified when they have an explicit alias or take part in a
+// join, in which case the alias defaults to the file stem.
+fn source_alias(path: &str, alias: Option<&str>, joining: bool) -> Option<String> {
+    match alias {
+        Some(a) => Some(a.to_string()),
+        None if joining => Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned()),
+        None => None,
     }
+}
 
-    (cols_out, aggs)
+/// Order a join key pair as (column of the tables joined so far, column of
+/// the table being joined), accepting either order in the query.
+fn orient_join_key(key: &JoinOn, left_aliases: &[String], right: &str) -> Result<(String, String)> {
+    let qualifier = |c: &str| c.split_once('.').map(|(q, _)| q.to_string());
+    let (l, r) = (qualifier(&key.left), qualifier(&key.right));
+    let on_left = |q: &Option<String>| q.as_ref().is_some_and(|q| left_aliases.contains(q));
+    let on_right = |q: &Option<String>| q.as_deref() == Some(right);
+
+    if on_left(&l) && on_right(&r) {
+        Ok((key.left.clone(), key.right.clone()))
+    } else if on_right(&l) && on_left(&r) {
+        Ok((key.right.clone(), key.left.clone()))
+    } else {
+        bail!(
+            "join condition `{} = {}` must compare a column of `{right}` with a column of {}",
+            key.left,
+            key.right,
+            left_aliases
+                .iter()
+                .map(|a| format!("`{a}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
 }
 
-pub fn build_logical_plan(q: &Query) -> LogicalPlan {
-    let (select_cols_out, aggs) = parse_select(&q.select);
+pub fn build_logical_plan(q: &Query, opts: &ScanOptions) -> Result<LogicalPlan> {
+    let (mut select_exprs, mut aggs) = parse_select(&q.select)?;
+
+    let having = match &q.having {
+        Some(h) => Some(plan_having(h, &q.group_by, &q.select, &mut aggs)?),
+        None => None,
+    };
+
+    let joining = !q.join.is_empty();
+    let from_alias = source_alias(&q.from, q.alias.as_deref(), joining);
+    let mut left_aliases: Vec<String> = from_alias.iter().cloned().collect();
 
     let mut plan = LogicalPlan::Scan {
         path: q.from.clone(),
+        alias: from_alias,
+        schema: Schema::infer_csv(&q.from, opts.infer_rows, &q.schema)?,
+        columns: None,
+        filter: None,
+        limit: None,
+        strict: opts.strict,
     };
 
-    if !q.r#where.is_empty() {
+    for j in &q.join {
+        let alias = source_alias(&j.from, j.alias.as_deref(), true).unwrap_or_default();
+        if left_aliases.contains(&alias) {
+            bail!("table alias `{alias}` is used more than once; give each source a distinct `as`");
+        }
+
+        let on =
+            j.on.iter()
+                .map(|k| orient_join_key(k, &left_aliases, &alias))
+                .collect::<Result<Vec<_>>>()?;
+        if on.is_empty() {
+            bail!("join with `{}` needs at least one `on` key pair", j.from);
+        }
+
+        plan = LogicalPlan::Join {
+            left: Box::new(plan),
+            right: Box::new(LogicalPlan::Scan {
+                path: j.from.clone(),
+                alias: Some(alias.clone()),
+                schema: Schema::infer_csv(&j.from, opts.infer_rows, &j.schema)?,
+                columns: None,
+                filter: None,
+                limit: None,
+                strict: opts.strict,
+            }),
+            kind: j.kind,
+            on,
+            build_left: None,
+        };
+        left_aliases.push(alias);
+    }
+
+    if let Some(pred) = &q.r#where {
         plan = LogicalPlan::Filter {
             input: Box::new(plan),
-            preds: q.r#where.clone(),
+            pred: pred.clone(),
         };
     }
 
@@ -87,11 +373,102 @@ pub fn build_logical_plan(q: &Query) -> LogicalPlan {
         };
     }
 
+    if let Some(pred) = having {
+        plan = LogicalPlan::Filter {
+            input: Box::new(plan),
+            pred,
+        };
+    }
+
+    // Without an explicit order, grouped results are sorted by the first
+    // group key so output is deterministic.
+    let sort_keys: Vec<OrderKey> = if !q.order_by.is_empty() {
+        q.order_by.clone()
+    } else {
+        q.group_by
+            .first()
+            .map(|k| OrderKey::asc(k))
+            .into_iter()
+            .collect()
+    };
+
+    // Keys that are all selected columns sort the projected rows. Otherwise
+    // the sort runs below the projection, where unselected columns (such as
+    // a group key left out of the select list) still exist; keys naming a
+    // selected column by its alias are mapped back to that column, and keys
+    // naming a computed one sort on a column computed just below the sort.
+    let sort_above = sort_keys
+        .iter()
+        .all(|k| select_exprs.iter().any(|e| e.name == k.col));
+    if !sort_keys.is_empty() && !sort_above {
+        let mut computed: Vec<NamedExpr> = Vec::new();
+        let keys: Vec<OrderKey> = sort_keys
+            .iter()
+            .cloned()
+            .map(|mut k| {
+                if let Some(e) = select_exprs.iter().find(|e| e.name == k.col) {
+                    k.col = match &e.expr {
+                        Expr::Column(c) => c.clone(),
+                        expr => {
+                            computed.push(NamedExpr {
+                                expr: expr.clone(),
+                                name: expr.to_string(),
+                            });
+                            expr.to_string()
+                        }
+                    };
+                }
+                k
+            })
+            .collect();
+
+        if !computed.is_empty() {
+            // Select the computed values by name above the sort, and carry
+            // every other column the projection and sort read alongside them.
+            for e in &mut select_exprs {
+                if let Some(c) = computed.iter().find(|c| c.expr == e.expr) {
+                    e.expr = Expr::Column(c.name.clone());
+                }
+            }
+            let mut exprs: Vec<NamedExpr> = Vec::new();
+            let read = select_exprs
+                .iter()
+                .flat_map(|e| e.expr.columns())
+                .chain(keys.iter().map(|k| k.col.as_str()));
+            for c in read {
+                let taken = |n: &str| exprs.iter().chain(&computed).any(|e| e.name == n);
+                if !taken(c) {
+                    exprs.push(NamedExpr {
+                        expr: Expr::Column(c.to_string()),
+                        name: c.to_string(),
+                    });
+                }
+            }
+            exprs.extend(computed);
+            plan = LogicalPlan::Project {
+                input: Box::new(plan),
+                exprs,
+            };
+        }
+
+        plan = LogicalPlan::Sort {
+            input: Box::new(plan),
+            keys,
+        };
+    }
+
     plan = LogicalPlan::Project {
         input: Box::new(plan),
-        cols: select_cols_out,
+        exprs: select_exprs,
     };
 
+    if !sort_keys.is_empty() && sort_above {
+        plan = LogicalPlan::Sort {
+            input: Box::new(plan),
+            keys: sort_keys,
+        };
+    }
+
     if let Some(n) = q.limit {
         plan = LogicalPlan::Limit {
             input: Box::new(plan),
@@ -99,5 +476,5 @@ pub fn build_logical_plan(q: &Query) -> LogicalPlan {
         };
     }
 
-    plan
+    Ok(plan)
 }
@@ -1,27 +1,57 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use std::cmp::Ordering;
+use clap::{Parser, Subcommand};
 use std::fs;
+use std::path::Path;
 
+mod analyzer;
 mod ast;
+mod batch;
+mod cost;
 mod exec;
 mod explain;
+mod expr;
+mod lexer;
 mod logical;
 mod optimizer;
 mod parser;
 mod physical;
+mod schema;
+mod sql;
+mod stats;
 mod value;
 
+use crate::analyzer::analyze;
+use crate::cost::CostModel;
+use crate::explain::{DotNode, ExplainFormat};
 use crate::logical::build_logical_plan;
-use crate::optimizer::optimize;
+use crate::optimizer::Optimizer;
 use crate::parser::parse_query;
-use crate::physical::to_physical_plan;
+use crate::physical::{ExecOptions, to_physical_plan};
+use crate::schema::{ScanOptions, Schema};
+use crate::sql::parse_sql;
+use crate::stats::{TableStats, sidecar_path};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(
+    author,
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// Path to query JSON file
-    query_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to a query file: `.sql` for SQL text, anything else for the JSON DSL
+    #[arg(required_unless_present = "sql")]
+    query_path: Option<String>,
+
+    /// Run a SQL query given inline instead of reading a file
+    #[arg(long, conflicts_with = "query_path")]
+    sql: Option<String>,
 
     /// Print optimized logical plan instead of running
     #[arg(long)]
@@ -31,11 +61,120 @@ struct Args {
     #[arg(long)]
     explain_both: bool,
 
+    /// Print the physical operator tree instead of running
+    #[arg(long)]
+    explain_physical: bool,
+
+    /// Run the query and print the physical plan with per-operator runtime
+    /// statistics instead of the results
+    #[arg(long)]
+    explain_analyze: bool,
+
+    /// Print the plan after each optimizer rule that changed it instead of running
+    #[arg(long)]
+    explain_rules: bool,
+
+    /// Skip an optimizer rule, e.g. pushdown_filter; may be repeated
+    #[arg(long, value_name = "RULE")]
+    disable_rule: Vec<String>,
+
+    /// Rendering of --explain, --explain-both, --explain-rules, --explain-physical and
+    /// --explain-analyze
+    #[arg(long, value_enum, default_value_t = ExplainFormat::Text)]
+    explain_format: ExplainFormat,
+
+    /// Number of CSV records sampled to infer column types
+    #[arg(long, default_value_t = 1000)]
+    infer_rows: usize,
+
+    /// Fail on cells that do not match their column type instead of reading them as null
+    #[arg(long)]
+    strict_schema: bool,
+
+    /// Threads to scan and aggregate with; each CSV is split into this many byte ranges
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    threads: u32,
+
+    /// Memory for operator state before it spills to temporary files, e.g. 512MB
+    #[arg(long, value_parser = parse_byte_size)]
+    memory_limit: Option<usize>,
+
+    /// Print per-operator execution statistics to stderr after running
+    #[arg(long)]
+    stats: bool,
+
     /// Output format: table|json
     #[arg(long, default_value = "table")]
     format: String,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Scan a CSV and save its column statistics next to it as
+    /// `<file>.stats.json`, for the planner's row estimates
+    Stats {
+        /// CSV file to summarize
+        path: String,
+
+        /// Number of CSV records sampled to infer column types
+        #[arg(long, default_value_t = 1000)]
+        infer_rows: usize,
+
+        /// Histogram buckets per numeric column
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        buckets: u32,
+    },
+}
+
+fn write_stats(path: &str, infer_rows: usize, buckets: usize) -> Result<()> {
+    let schema = Schema::infer_csv(path, infer_rows, &HashMap::new())?;
+    let stats = TableStats::collect(path, schema, buckets)?;
+    stats.write(path)?;
+    println!(
+        "Wrote {} ({} rows, {} columns)",
+        sidecar_path(path),
+        stats.rows,
+        stats.columns.len()
+    );
+    Ok(())
+}
+
+/// Parse a size such as `512MB`, `1GiB`, `64kb` or a plain byte count;
+/// units are powers of 1024.
+fn parse_byte_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(split);
+    let n: usize = digits
+        .parse()
+        .map_err(|_| format!("invalid size `{s}`; expected e.g. 512MB"))?;
+
+    let shift = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => {
+            return Err(format!(
+                "unknown size unit `{unit}`; use B, KB, MB, GB or TB"
+            ));
+        }
+    };
+    n.checked_mul(1 << shift)
+        .filter(|&b| b > 0)
+        .ok_or_else(|| format!("size `{s}` must be positive and fit in memory addresses"))
+}
+
+fn print_json(tree: &serde_json::Value) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(tree)?);
+    Ok(())
+}
+
+fn print_dot(tree: DotNode) {
+    print!("{}", explain::format_dot(&[("plan", tree)]));
+}
+
 fn json_cell_to_string(v: &serde_json::Value) -> String {
     match v {
         serde_json::Value::Null => "null".to_string(),
@@ -50,59 +189,153 @@ fn is_number(v: &serde_json::Value) -> bool {
     matches!(v, serde_json::Value::Number(_))
 }
 
-fn cmp_json_for_sort(a: Option<&serde_json::Value>, b: Option<&serde_json::Value>) -> Ordering {
-    match (a, b) {
-        (Some(serde_json::Value::String(sa)), Some(serde_json::Value::String(sb))) => sa.cmp(sb),
-
-        (Some(serde_json::Value::Number(na)), Some(serde_json::Value::Number(nb))) => {
-            let fa = na.as_f64().unwrap_or(0.0);
-            let fb = nb.as_f64().unwrap_or(0.0);
-            fa.partial_cmp(&fb).unwrap_or(Ordering::Equal)
-        }
-
-        (Some(serde_json::Value::Null), Some(serde_json::Value::Null)) => Ordering::Equal,
-        (None, None) => Ordering::Equal,
-
-        (None, _) | (Some(serde_json::Value::Null), _) => Ordering::Greater,
-        (_, None) | (_, Some(serde_json::Value::Null)) => Ordering::Less,
-
-        (Some(va), Some(vb)) => va.to_string().cmp(&vb.to_string()),
-    }
-}
-
 fn main() -> Result<()> {
     let args = Args::parse();
 
-    let raw = fs::read_to_string(&args.query_path)
-        .with_context(|| format!("Failed to read query file: {}", args.query_path))?;
+    if let Some(Command::Stats {
+        path,
+        infer_rows,
+        buckets,
+    }) = &args.command
+    {
+        return write_stats(path, *infer_rows, *buckets as usize);
+    }
 
-    let query = parse_query(&raw).context("Failed to parse query JSON")?;
+    let query = match (&args.sql, &args.query_path) {
+        (Some(sql), _) => parse_sql(sql).context("Failed to parse SQL")?,
+        (None, Some(path)) => {
+            let raw = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read query file: {path}"))?;
+
+            let is_sql = Path::new(path)
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("sql"));
+            if is_sql {
+                parse_sql(&raw).context("Failed to parse SQL")?
+            } else {
+                parse_query(&raw).context("Failed to parse query JSON")?
+            }
+        }
+        (None, None) => unreachable!("clap requires a query path or --sql"),
+    };
+
+    let scan_opts = ScanOptions {
+        infer_rows: args.infer_rows,
+        strict: args.strict_schema,
+    };
+    let logical = build_logical_plan(&query, &scan_opts)?;
+    analyze(&logical)?;
+    let cost = Arc::new(CostModel::load(&logical)?);
+    let mut optimizer = Optimizer::new(cost.clone());
+    for rule in &args.disable_rule {
+        optimizer.disable(rule)?;
+    }
 
-    let logical = build_logical_plan(&query);
-    let optimized = optimize(logical.clone());
+    if args.explain_rules {
+        let mut steps = vec![("original".to_string(), logical.clone())];
+        optimizer.optimize_traced(logical, |rule, pass, plan| {
+            steps.push((format!("{rule} (pass {pass})"), plan.clone()));
+        });
+        match args.explain_format {
+            ExplainFormat::Text => {
+                for (i, (label, plan)) in steps.iter().enumerate() {
+                    match i {
+                        0 => println!("--- ORIGINAL PLAN ---"),
+                        _ => println!("--- AFTER {label} ---"),
+                    }
+                    println!("{}", explain::format_plan(plan, &cost));
+                }
+            }
+            ExplainFormat::Json => {
+                let steps: Vec<_> = steps
+                    .iter()
+                    .map(|(label, plan)| {
+                        serde_json::json!({"step": label, "plan": explain::plan_json(plan, &cost)})
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&steps)?);
+            }
+            ExplainFormat::Dot => {
+                let trees: Vec<_> = steps
+                    .iter()
+                    .map(|(label, plan)| (label.as_str(), DotNode::logical(plan, &cost)))
+                    .collect();
+                print!("{}", explain::format_dot(&trees));
+            }
+        }
+        return Ok(());
+    }
+
+    let optimized = optimizer.optimize(logical.clone());
 
     if args.explain_both {
-        println!("--- ORIGINAL PLAN ---");
-        println!("{}", explain::format_plan(&logical));
-        println!("--- OPTIMIZED PLAN ---");
-        println!("{}", explain::format_plan(&optimized));
+        match args.explain_format {
+            ExplainFormat::Text => {
+                println!("--- ORIGINAL PLAN ---");
+                println!("{}", explain::format_plan(&logical, &cost));
+                println!("--- OPTIMIZED PLAN ---");
+                println!("{}", explain::format_plan(&optimized, &cost));
+            }
+            ExplainFormat::Json => {
+                let both = serde_json::json!({
+                    "original": explain::plan_json(&logical, &cost),
+                    "optimized": explain::plan_json(&optimized, &cost),
+                });
+                println!("{}", serde_json::to_string_pretty(&both)?);
+            }
+            ExplainFormat::Dot => print!(
+                "{}",
+                explain::format_dot(&[
+                    ("original", DotNode::logical(&logical, &cost)),
+                    ("optimized", DotNode::logical(&optimized, &cost)),
+                ])
+            ),
+        }
         return Ok(());
     }
 
     if args.explain {
-        println!("{}", explain::format_plan(&optimized));
+        match args.explain_format {
+            ExplainFormat::Text => println!("{}", explain::format_plan(&optimized, &cost)),
+            ExplainFormat::Json => print_json(&explain::plan_json(&optimized, &cost))?,
+            ExplainFormat::Dot => print_dot(DotNode::logical(&optimized, &cost)),
+        }
         return Ok(());
     }
 
-    let mut root = to_physical_plan(optimized)?;
+    let exec_opts = ExecOptions {
+        threads: args.threads as usize,
+        memory_limit: args.memory_limit,
+        analyze: args.explain_analyze || args.stats,
+    };
+    let mut root = to_physical_plan(optimized, &exec_opts)?;
+
+    if args.explain_physical {
+        match args.explain_format {
+            ExplainFormat::Text => print!("{}", explain::format_physical_plan(root.as_ref())),
+            ExplainFormat::Json => print_json(&explain::physical_json(root.as_ref()))?,
+            ExplainFormat::Dot => print_dot(DotNode::physical(root.as_ref())),
+        }
+        return Ok(());
+    }
+
+    if args.explain_analyze {
+        while root.next_batch()?.is_some() {}
+        match args.explain_format {
+            ExplainFormat::Text => print!("{}", explain::format_exec_stats(root.as_ref())),
+            ExplainFormat::Json => print_json(&explain::physical_json(root.as_ref()))?,
+            ExplainFormat::Dot => print_dot(DotNode::physical(root.as_ref())),
+        }
+        return Ok(());
+    }
 
     let mut rows = Vec::new();
-    while let Some(r) = root.next_row()? {
-        rows.push(r);
+    while let Some(batch) = root.next_batch()? {
+        rows.extend(batch.to_rows());
     }
 
-    if let Some(sort_key) = query.group_by.first() {
-        rows.sort_by(|ra, rb| cmp_json_for_sort(ra.get(sort_key), rb.get(sort_key)));
+    if args.stats {
+        eprint!("{}", explain::format_exec_stats(root.as_ref()));
     }
 
     match args.format.as_str() {
@@ -111,11 +344,7 @@ fn main() -> Result<()> {
             println!("{json}");
         }
         "table" => {
-            let headers = query
-                .select
-                .iter()
-                .map(|s| s.trim().to_string())
-                .collect::<Vec<_>>();
+            let headers = query.select.iter().map(|s| s.name()).collect::<Vec<_>>();
 
             let max_rows = 50usize;
             let display_rows = rows.iter().take(max_rows).collect::<Vec<_>>();
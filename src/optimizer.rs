@@ -1,102 +1,1088 @@
+use anyhow::{Result, bail};
+use serde_json::Value as JsonValue;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::ast::{Comparison, JoinKind, PredExpr, Predicate, flip};
+use crate::cost::CostModel;
+use crate::expr::{Expr, NamedExpr};
 use crate::logical::LogicalPlan;
+use crate::schema::{DataType, Field, Schema};
+use crate::value::cmp_json;
+
+/// A rewrite of the logical plan that keeps its results unchanged.
+pub trait OptimizerRule {
+    /// Name accepted by `--disable-rule` and shown by `--explain-rules`.
+    fn name(&self) -> &'static str;
+
+    fn rewrite(&self, plan: LogicalPlan) -> LogicalPlan;
+}
+
+/// A rule that rewrites one node at a time over the whole tree, bottom-up
+/// unless `top_down` is set.
+struct NodeRule {
+    name: &'static str,
+    top_down: bool,
+    rewrite: fn(LogicalPlan) -> LogicalPlan,
+}
+
+impl OptimizerRule for NodeRule {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn rewrite(&self, plan: LogicalPlan) -> LogicalPlan {
+        let mut f = self.rewrite;
+        if self.top_down {
+            plan.transform_down(&mut f)
+        } else {
+            plan.transform_up(&mut f)
+        }
+    }
+}
+
+struct PruneColumns;
+
+impl OptimizerRule for PruneColumns {
+    fn name(&self) -> &'static str {
+        "prune_columns"
+    }
+
+    fn rewrite(&self, plan: LogicalPlan) -> LogicalPlan {
+        prune_columns(plan, None)
+    }
+}
+
+/// Reorders the operands of ANDs and ORs in filters by their estimated
+/// selectivity.
+struct OrderPredicates {
+    cost: Arc<CostModel>,
+}
+
+impl OptimizerRule for OrderPredicates {
+    fn name(&self) -> &'static str {
+        "order_predicates"
+    }
 
-pub fn optimize(plan: LogicalPlan) -> LogicalPlan {
-    let plan = pushdown_filter(plan);
-    let plan = pushdown_project(plan);
-    plan
+    fn rewrite(&self, plan: LogicalPlan) -> LogicalPlan {
+        plan.transform_up(&mut |node| match node {
+            LogicalPlan::Filter { input, pred } => LogicalPlan::Filter {
+                pred: self.cost.order_predicate(&input, pred),
+                input,
+            },
+            mut scan @ LogicalPlan::Scan { .. } => {
+                // The scan's filter reads its columns before they are output.
+                let ordered = match &scan {
+                    LogicalPlan::Scan {
+                        filter: Some(f), ..
+                    } => Some(self.cost.order_predicate(&scan, f.clone())),
+                    _ => None,
+                };
+                if let LogicalPlan::Scan { filter, .. } = &mut scan
+                    && ordered.is_some()
+                {
+                    *filter = ordered;
+                }
+                scan
+            }
+            other => other,
+        })
+    }
+}
+
+/// Builds each join's hash table on the input estimated to have fewer rows.
+struct ChooseBuildSide {
+    cost: Arc<CostModel>,
+}
+
+impl OptimizerRule for ChooseBuildSide {
+    fn name(&self) -> &'static str {
+        "choose_build_side"
+    }
+
+    fn rewrite(&self, plan: LogicalPlan) -> LogicalPlan {
+        plan.transform_up(&mut |node| match node {
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                build_left,
+            } => {
+                let build_left = match (self.cost.rows(&left), self.cost.rows(&right)) {
+                    (Some(l), Some(r)) => Some(l < r),
+                    _ => build_left,
+                };
+                LogicalPlan::Join {
+                    left,
+                    right,
+                    kind,
+                    on,
+                    build_left,
+                }
+            }
+            other => other,
+        })
+    }
+}
+
+/// Upper bound on passes over the rule list, in case rules keep undoing
+/// each other.
+const MAX_PASSES: usize = 10;
+
+/// Runs its rules in order, over and over until a whole pass leaves the
+/// plan unchanged.
+pub struct Optimizer {
+    rules: Vec<Box<dyn OptimizerRule>>,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new(Arc::default())
+    }
+}
+
+impl Optimizer {
+    /// The standard rules; cost-based ones use the estimates of `cost`.
+    pub fn new(cost: Arc<CostModel>) -> Self {
+        let node = |name, top_down, rewrite| -> Box<dyn OptimizerRule> {
+            Box::new(NodeRule {
+                name,
+                top_down,
+                rewrite,
+            })
+        };
+        Self {
+            rules: vec![
+                node("pushdown_filter", false, pushdown_filter),
+                node("simplify", false, simplify),
+                node("pushdown_project", false, pushdown_project),
+                Box::new(PruneColumns),
+                node("fuse_topk", false, fuse_topk),
+                node("pushdown_limit", true, pushdown_limit),
+                Box::new(OrderPredicates { cost: cost.clone() }),
+                Box::new(ChooseBuildSide { cost }),
+            ],
+        }
+    }
+
+    /// Remove the rule called `name`; disabling a rule twice is allowed.
+    pub fn disable(&mut self, name: &str) -> Result<()> {
+        let names: Vec<_> = Optimizer::default()
+            .rules
+            .iter()
+            .map(|r| r.name())
+            .collect();
+        if !names.contains(&name) {
+            bail!(
+                "unknown optimizer rule `{name}`; rules are {}",
+                names.join(", ")
+            );
+        }
+        self.rules.retain(|r| r.name() != name);
+        Ok(())
+    }
+
+    pub fn optimize(&self, plan: LogicalPlan) -> LogicalPlan {
+        self.optimize_traced(plan, |_, _, _| {})
+    }
+
+    /// Optimize, calling `trace` with the rule name, the 1-based pass and
+    /// the new plan each time a rule changes the plan.
+    pub fn optimize_traced(
+        &self,
+        mut plan: LogicalPlan,
+        mut trace: impl FnMut(&str, usize, &LogicalPlan),
+    ) -> LogicalPlan {
+        for pass in 1..=MAX_PASSES {
+            let mut changed = false;
+            for rule in &self.rules {
+                let next = rule.rewrite(plan.clone());
+                if next != plan {
+                    trace(rule.name(), pass, &next);
+                    changed = true;
+                }
+                plan = next;
+            }
+            if !changed {
+                break;
+            }
+        }
+        plan
+    }
 }
 
 fn pushdown_filter(plan: LogicalPlan) -> LogicalPlan {
     match plan {
-        LogicalPlan::Filter { input, preds } => {
-            let input = pushdown_filter(*input);
-
-            match input {
-                LogicalPlan::Project { input: inner, cols } => LogicalPlan::Project {
-                    input: Box::new(LogicalPlan::Filter {
-                        input: inner,
-                        preds,
-                    }),
-                    cols,
+        LogicalPlan::Filter { input, pred } => {
+            match *input {
+                // Only safe when every referenced column passes through unchanged
+                LogicalPlan::Project {
+                    input: inner,
+                    exprs,
+                } if passes_through(&exprs, &pred.columns()) => LogicalPlan::Project {
+                    input: Box::new(pushdown_filter(LogicalPlan::Filter { input: inner, pred })),
+                    exprs,
                 },
-                LogicalPlan::Aggregate { .. } => {
-                    // Do not move filters across Aggregate in this simple version
-                    LogicalPlan::Filter {
-                        input: Box::new(input),
-                        preds,
+                LogicalPlan::Aggregate {
+                    input: inner,
+                    group_keys,
+                    aggs,
+                } => {
+                    // A conjunct over group keys alone keeps or drops whole
+                    // groups, so it can run on the input rows instead. One
+                    // without columns stays: a global aggregate emits a row
+                    // even for empty input.
+                    let (below, rest): (Vec<_>, Vec<_>) =
+                        conjuncts(pred).into_iter().partition(|p| {
+                            let cols = p.columns();
+                            !cols.is_empty()
+                                && cols.iter().all(|c| group_keys.iter().any(|k| k == c))
+                        });
+
+                    let agg = LogicalPlan::Aggregate {
+                        input: Box::new(with_filter(*inner, below)),
+                        group_keys,
+                        aggs,
+                    };
+                    match conjoin(rest) {
+                        Some(pred) => LogicalPlan::Filter {
+                            input: Box::new(agg),
+                            pred,
+                        },
+                        None => agg,
+                    }
+                }
+                // The scan checks the predicate before parsing each record,
+                // so it only takes conjuncts comparing columns with literals.
+                // Its limit counts the records that pass, so a limited scan
+                // cannot take on another predicate.
+                LogicalPlan::Scan {
+                    path,
+                    alias,
+                    schema,
+                    columns,
+                    filter,
+                    limit: None,
+                    strict,
+                } => {
+                    let mut preds = filter.map(conjuncts).unwrap_or_default();
+                    let (pushed, rest): (Vec<_>, Vec<_>) = conjuncts(pred)
+                        .into_iter()
+                        .partition(PredExpr::is_column_literal);
+                    preds.extend(pushed);
+                    let scan = LogicalPlan::Scan {
+                        path,
+                        alias,
+                        schema,
+                        columns,
+                        filter: conjoin(preds),
+                        limit: None,
+                        strict,
+                    };
+                    match conjoin(rest) {
+                        Some(pred) => LogicalPlan::Filter {
+                            input: Box::new(scan),
+                            pred,
+                        },
+                        None => scan,
+                    }
+                }
+                LogicalPlan::Join {
+                    left,
+                    right,
+                    kind,
+                    on,
+                    build_left,
+                } => {
+                    // A conjunct over one side's columns can run below the
+                    // join, unless that side's unmatched rows are null-padded.
+                    let to_left = matches!(kind, JoinKind::Inner | JoinKind::Left);
+                    let to_right = matches!(kind, JoinKind::Inner | JoinKind::Right);
+                    let (mut left_preds, mut right_preds, mut rest) = (vec![], vec![], vec![]);
+                    for p in conjuncts(pred) {
+                        let cols = p.columns();
+                        if to_left && cols.iter().all(|c| column_type(&left, c).is_some()) {
+                            left_preds.push(p);
+                        } else if to_right && cols.iter().all(|c| column_type(&right, c).is_some())
+                        {
+                            right_preds.push(p);
+                        } else {
+                            rest.push(p);
+                        }
+                    }
+
+                    let join = LogicalPlan::Join {
+                        left: Box::new(with_filter(*left, left_preds)),
+                        right: Box::new(with_filter(*right, right_preds)),
+                        kind,
+                        on,
+                        build_left,
+                    };
+                    match conjoin(rest) {
+                        Some(pred) => LogicalPlan::Filter {
+                            input: Box::new(join),
+                            pred,
+                        },
+                        None => join,
                     }
                 }
                 other => LogicalPlan::Filter {
                     input: Box::new(other),
-                    preds,
+                    pred,
                 },
             }
         }
-        LogicalPlan::Project { input, cols } => LogicalPlan::Project {
-            input: Box::new(pushdown_filter(*input)),
-            cols,
+        other => other,
+    }
+}
+
+/// The operands of a top-level AND, flattened; any other predicate is its
+/// own single conjunct.
+fn conjuncts(pred: PredExpr) -> Vec<PredExpr> {
+    match pred {
+        PredExpr::And { and } => and.into_iter().flat_map(conjuncts).collect(),
+        other => vec![other],
+    }
+}
+
+/// AND of `preds`, or `None` when there are none.
+fn conjoin(mut preds: Vec<PredExpr>) -> Option<PredExpr> {
+    match preds.len() {
+        0 => None,
+        1 => preds.pop(),
+        _ => Some(PredExpr::And { and: preds }),
+    }
+}
+
+// Filter `plan` by `preds` and push the filter as far down as it goes.
+fn with_filter(plan: LogicalPlan, preds: Vec<PredExpr>) -> LogicalPlan {
+    match conjoin(preds) {
+        Some(pred) => pushdown_filter(LogicalPlan::Filter {
+            input: Box::new(plan),
+            pred,
+        }),
+        None => plan,
+    }
+}
+
+/// Type of output column `col` of a scan, or of joins, filters and limits
+/// over scans; `None` if it is not one, or the plan is anything else.
+fn column_type(plan: &LogicalPlan, col: &str) -> Option<DataType> {
+    match plan {
+        LogicalPlan::Scan { alias, schema, .. } => {
+            let name = match alias {
+                Some(a) => col
+                    .strip_prefix(a.as_str())
+                    .and_then(|c| c.strip_prefix('.'))?,
+                None => col,
+            };
+            schema.field(name).map(|f| f.dtype)
+        }
+        LogicalPlan::Join { left, right, .. } => {
+            column_type(left, col).or_else(|| column_type(right, col))
+        }
+        LogicalPlan::Filter { input, .. } | LogicalPlan::Limit { input, .. } => {
+            column_type(input, col)
+        }
+        LogicalPlan::Empty { schema } => schema.field(col).map(|f| f.dtype),
+        _ => None,
+    }
+}
+
+/// Output columns of the same plans `column_type` understands.
+fn output_schema(plan: &LogicalPlan) -> Option<Schema> {
+    Some(match plan {
+        LogicalPlan::Scan {
+            alias,
+            schema,
+            columns,
+            ..
+        } => Schema {
+            fields: schema
+                .select(columns.as_deref())
+                .into_iter()
+                .map(|f| Field {
+                    name: match alias {
+                        Some(a) => format!("{a}.{}", f.name),
+                        None => f.name.clone(),
+                    },
+                    dtype: f.dtype,
+                })
+                .collect(),
+        },
+        LogicalPlan::Join { left, right, .. } => {
+            let mut fields = output_schema(left)?.fields;
+            fields.extend(output_schema(right)?.fields);
+            Schema { fields }
+        }
+        LogicalPlan::Filter { input, .. } | LogicalPlan::Limit { input, .. } => {
+            output_schema(input)?
+        }
+        LogicalPlan::Empty { schema } => schema.clone(),
+        _ => return None,
+    })
+}
+
+/// Fold constant expressions and simplify predicates: nested ANDs and ORs
+/// are flattened and deduplicated, stacked filters merged and tautologies
+/// dropped. A filter that can never pass replaces itself and everything
+/// below it with `Empty`, so nothing is scanned.
+fn simplify(plan: LogicalPlan) -> LogicalPlan {
+    match plan {
+        LogicalPlan::Filter { input, pred } => {
+            let (input, pred) = match *input {
+                LogicalPlan::Filter { input, pred: below } => (
+                    *input,
+                    PredExpr::And {
+                        and: vec![below, pred],
+                    },
+                ),
+                other => (other, pred),
+            };
+            let pred = simplify_pred(pred, &|c| column_type(&input, c));
+
+            if is_true(&pred) {
+                input
+            } else if let (true, Some(schema)) = (is_false(&pred), output_schema(&input)) {
+                LogicalPlan::Empty { schema }
+            } else {
+                LogicalPlan::Filter {
+                    input: Box::new(input),
+                    pred,
+                }
+            }
+        }
+        LogicalPlan::Scan {
+            path,
+            alias,
+            schema,
+            columns,
+            filter,
+            limit,
+            strict,
+        } => {
+            let mut scan = LogicalPlan::Scan {
+                path,
+                alias,
+                schema,
+                columns,
+                filter: None,
+                limit,
+                strict,
+            };
+            let Some(pred) = filter else {
+                return scan;
+            };
+
+            let pred = simplify_pred(pred, &|c| column_type(&scan, c));
+            if is_false(&pred) {
+                return LogicalPlan::Empty {
+                    schema: output_schema(&scan).unwrap_or_default(),
+                };
+            }
+            if let LogicalPlan::Scan { filter, .. } = &mut scan {
+                *filter = (!is_true(&pred)).then_some(pred);
+            }
+            scan
+        }
+        LogicalPlan::Project { input, exprs } => LogicalPlan::Project {
+            input,
+            exprs: exprs
+                .into_iter()
+                .map(|e| NamedExpr {
+                    expr: e.expr.fold(),
+                    name: e.name,
+                })
+                .collect(),
         },
         LogicalPlan::Aggregate {
             input,
             group_keys,
-            aggs,
-        } => LogicalPlan::Aggregate {
-            input: Box::new(pushdown_filter(*input)),
-            group_keys,
-            aggs,
+            mut aggs,
+        } => {
+            for a in &mut aggs {
+                a.arg = a.arg.as_ref().map(Expr::fold);
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_keys,
+                aggs,
+            }
+        }
+        other => other,
+    }
+}
+
+// An empty AND always passes and an empty OR never does; simplification
+// reduces constant predicates to these.
+fn is_true(pred: &PredExpr) -> bool {
+    matches!(pred, PredExpr::And { and } if and.is_empty())
+}
+
+fn is_false(pred: &PredExpr) -> bool {
+    matches!(pred, PredExpr::Or { or } if or.is_empty())
+}
+
+/// Simplify a predicate whose columns have the types `types` reports.
+/// Rows compare with two-valued logic (a null cell gives each comparison a
+/// fixed result), so `p OR NOT p` always passes and `p AND NOT p` never does.
+fn simplify_pred(pred: PredExpr, types: &dyn Fn(&str) -> Option<DataType>) -> PredExpr {
+    match pred {
+        PredExpr::Not { not } => match simplify_pred(*not, types) {
+            PredExpr::Not { not } => *not,
+            p if is_true(&p) => PredExpr::Or { or: vec![] },
+            p if is_false(&p) => PredExpr::And { and: vec![] },
+            p => PredExpr::Not { not: Box::new(p) },
         },
-        LogicalPlan::Limit { input, n } => LogicalPlan::Limit {
-            input: Box::new(pushdown_filter(*input)),
-            n,
+        PredExpr::And { and } => {
+            let mut items = Vec::new();
+            for p in and {
+                match simplify_pred(p, types) {
+                    PredExpr::And { and } => items.extend(and),
+                    p if is_false(&p) => return p,
+                    p => items.push(p),
+                }
+            }
+            dedup(&mut items);
+            if has_complement(&items) || !satisfiable(&items, types) {
+                return PredExpr::Or { or: vec![] };
+            }
+            match items.len() {
+                1 => items.remove(0),
+                _ => PredExpr::And { and: items },
+            }
+        }
+        PredExpr::Or { or } => {
+            let mut items = Vec::new();
+            for p in or {
+                match simplify_pred(p, types) {
+                    PredExpr::Or { or } => items.extend(or),
+                    p if is_true(&p) => return p,
+                    p => items.push(p),
+                }
+            }
+            dedup(&mut items);
+            if has_complement(&items) {
+                return PredExpr::And { and: vec![] };
+            }
+            match items.len() {
+                1 => items.remove(0),
+                _ => PredExpr::Or { or: items },
+            }
+        }
+        cmp @ PredExpr::Cmp(_) => cmp,
+        PredExpr::Compare(c) => simplify_compare(c),
+    }
+}
+
+/// Fold both sides of a comparison. One between literals becomes a
+/// constant, and one between a column and a literal becomes a `Predicate`.
+/// Identical sides always compare equal, nulls included (comparisons treat
+/// null as a value of its own), so `x == x` is dropped and `x != x` can
+/// never pass.
+fn simplify_compare(c: Comparison) -> PredExpr {
+    let (left, right) = (c.left.fold(), c.right.fold());
+    let constant = |pass: bool| {
+        if pass {
+            PredExpr::And { and: vec![] }
+        } else {
+            PredExpr::Or { or: vec![] }
+        }
+    };
+    match (left, right) {
+        (Expr::Literal(a), Expr::Literal(b)) => match cmp_json(&a, &c.op, &b) {
+            Ok(pass) => constant(pass),
+            // Unknown operators are left for execution to report.
+            Err(_) => PredExpr::Compare(Comparison {
+                left: Expr::Literal(a),
+                op: c.op,
+                right: Expr::Literal(b),
+            }),
         },
-        scan @ LogicalPlan::Scan { .. } => scan,
+        (Expr::Column(col), Expr::Literal(val)) => PredExpr::Cmp(Predicate { col, op: c.op, val }),
+        (Expr::Literal(val), Expr::Column(col)) => PredExpr::Cmp(Predicate {
+            col,
+            op: flip(&c.op).to_string(),
+            val,
+        }),
+        (left, right) if left == right && matches!(c.op.as_str(), "==" | "<=" | ">=") => {
+            constant(true)
+        }
+        (left, right) if left == right && matches!(c.op.as_str(), "!=" | "<" | ">") => {
+            constant(false)
+        }
+        (left, right) => PredExpr::Compare(Comparison {
+            left,
+            op: c.op,
+            right,
+        }),
     }
 }
 
+// Drop repeats, keeping the first of each.
+fn dedup(items: &mut Vec<PredExpr>) {
+    let mut seen: Vec<PredExpr> = Vec::new();
+    items.retain(|p| {
+        let new = !seen.contains(p);
+        if new {
+            seen.push(p.clone());
+        }
+        new
+    });
+}
+
+// Whether some item is the negation of another.
+fn has_complement(items: &[PredExpr]) -> bool {
+    items.iter().any(|p| match p {
+        PredExpr::Not { not } => items.contains(not),
+        _ => false,
+    })
+}
+
+/// Whether the comparisons in an AND can all hold for one row, judged one
+/// column at a time from the range of values each allows. `false` is only
+/// returned when no value, null included, passes every comparison on some
+/// column.
+fn satisfiable(items: &[PredExpr], types: &dyn Fn(&str) -> Option<DataType>) -> bool {
+    let cmps: Vec<&Predicate> = items
+        .iter()
+        .filter_map(|p| match p {
+            PredExpr::Cmp(c) => Some(c),
+            _ => None,
+        })
+        .collect();
+
+    let mut cols: Vec<&str> = cmps.iter().map(|c| c.col.as_str()).collect();
+    cols.sort_unstable();
+    cols.dedup();
+    cols.iter().all(|&col| {
+        let on_col = cmps.iter().filter(|c| c.col == col);
+        // Comparisons that could be judged, with what each gives for null.
+        let mut judged = Vec::new();
+        for c in on_col {
+            match cmp_json(&JsonValue::Null, &c.op, &c.val) {
+                Ok(if_null) => judged.push((*c, if_null)),
+                // Unknown operators are left for execution to report.
+                Err(_) => return true,
+            }
+        }
+        if judged.iter().all(|(_, if_null)| *if_null) {
+            return true;
+        }
+
+        // Same comparison rules as `predicate_mask` for each column type.
+        match types(col) {
+            Some(DataType::Int64 | DataType::Float64) => {
+                let bounds = judged.iter().filter_map(|(c, _)| {
+                    let v = c.val.as_f64()?;
+                    // Numeric equality allows an epsilon, so it is widened
+                    // into a range.
+                    Some(match c.op.as_str() {
+                        "==" => vec![
+                            (">=", v - 2.0 * f64::EPSILON),
+                            ("<=", v + 2.0 * f64::EPSILON),
+                        ],
+                        "!=" => vec![],
+                        op => vec![(op, v)],
+                    })
+                });
+                !empty_range(bounds.flatten())
+            }
+            Some(DataType::Utf8) => !empty_range(judged.iter().map(|(c, _)| {
+                let text = c
+                    .val
+                    .as_str()
+                    .map_or_else(|| c.val.to_string(), str::to_string);
+                (c.op.as_str(), text)
+            })),
+            _ => true,
+        }
+    })
+}
+
+/// Whether no value satisfies every `value op literal` comparison.
+fn empty_range<'a, T: PartialOrd + Clone>(cmps: impl Iterator<Item = (&'a str, T)>) -> bool {
+    // Tightest bounds so far, with whether they are inclusive.
+    let mut lo: Option<(T, bool)> = None;
+    let mut hi: Option<(T, bool)> = None;
+    let mut excluded = Vec::new();
+
+    let tighter = |cur: &Option<(T, bool)>, v: &T, inclusive: bool, below: bool| match cur {
+        None => true,
+        Some((c, c_incl)) => {
+            (if below { v > c } else { v < c }) || (v == c && *c_incl && !inclusive)
+        }
+    };
+    for (op, v) in cmps {
+        let (lower, upper) = match op {
+            ">" => (Some(false), None),
+            ">=" => (Some(true), None),
+            "<" => (None, Some(false)),
+            "<=" => (None, Some(true)),
+            "==" => (Some(true), Some(true)),
+            _ => {
+                excluded.push(v);
+                continue;
+            }
+        };
+        if let Some(incl) = upper
+            && tighter(&hi, &v, incl, false)
+        {
+            hi = Some((v.clone(), incl));
+        }
+        if let Some(incl) = lower
+            && tighter(&lo, &v, incl, true)
+        {
+            lo = Some((v, incl));
+        }
+    }
+    match (lo, hi) {
+        (Some((l, l_incl)), Some((h, h_incl))) => {
+            l > h || (l == h && (!l_incl || !h_incl || excluded.contains(&l)))
+        }
+        _ => false,
+    }
+}
+
+fn passes_through(exprs: &[NamedExpr], cols: &[&str]) -> bool {
+    cols.iter().all(|c| {
+        exprs
+            .iter()
+            .any(|e| e.name == *c && e.expr == Expr::Column(c.to_string()))
+    })
+}
+
+/// Merge a projection into the one below it by computing each outer
+/// expression straight from the inner projection's input. Projections stay
+/// apart when that would compute an inner expression more than once.
 fn pushdown_project(plan: LogicalPlan) -> LogicalPlan {
     match plan {
-        LogicalPlan::Project { input, cols } => {
-            let input = pushdown_project(*input);
+        LogicalPlan::Project { input, exprs } => match *input {
+            LogicalPlan::Project {
+                input: inner,
+                exprs: inner_exprs,
+            } => {
+                let lookup = |c: &str| {
+                    inner_exprs
+                        .iter()
+                        .find(|i| i.name == c)
+                        .map(|i| i.expr.clone())
+                };
+                // Columns and literals are free to copy into every outer
+                // reference; anything else would be evaluated once per copy.
+                let references = |name: &str| {
+                    exprs
+                        .iter()
+                        .flat_map(|e| e.expr.columns())
+                        .filter(|&c| c == name)
+                        .count()
+                };
+                let duplicates = inner_exprs.iter().any(|i| {
+                    !matches!(i.expr, Expr::Column(_) | Expr::Literal(_)) && references(&i.name) > 1
+                });
+                let merged: Option<Vec<NamedExpr>> = if duplicates {
+                    None
+                } else {
+                    exprs
+                        .iter()
+                        .map(|e| {
+                            Some(NamedExpr {
+                                expr: e.expr.substitute(&lookup)?,
+                                name: e.name.clone(),
+                            })
+                        })
+                        .collect()
+                };
 
-            match input {
-                LogicalPlan::Project {
-                    input: inner,
-                    cols: inner_cols,
-                } => {
-                    let mut keep = Vec::new();
-                    for c in cols {
-                        if inner_cols.contains(&c) {
-                            keep.push(c);
-                        }
-                    }
-                    LogicalPlan::Project {
+                // An outer column the inner projection does not produce
+                // would fail at execution; leave both for it to report.
+                match merged {
+                    Some(exprs) => LogicalPlan::Project {
                         input: inner,
-                        cols: keep,
-                    }
+                        exprs,
+                    },
+                    None => LogicalPlan::Project {
+                        input: Box::new(LogicalPlan::Project {
+                            input: inner,
+                            exprs: inner_exprs,
+                        }),
+                        exprs,
+                    },
                 }
-                other => LogicalPlan::Project {
-                    input: Box::new(other),
-                    cols,
-                },
             }
-        }
-        LogicalPlan::Filter { input, preds } => LogicalPlan::Filter {
-            input: Box::new(pushdown_project(*input)),
-            preds,
+            other => LogicalPlan::Project {
+                input: Box::new(other),
+                exprs,
+            },
         },
+        other => other,
+    }
+}
+
+/// Push the set of columns each node's consumers need down to the scans,
+/// so they only parse those. `required` is `None` when every column of
+/// `plan` is needed.
+fn prune_columns(plan: LogicalPlan, required: Option<&HashSet<String>>) -> LogicalPlan {
+    // `required` plus the columns a node itself reads.
+    let with = |cols: Vec<&str>| {
+        required.map(|r| {
+            let mut r = r.clone();
+            r.extend(cols.into_iter().map(String::from));
+            r
+        })
+    };
+
+    match plan {
+        LogicalPlan::Scan {
+            path,
+            alias,
+            schema,
+            columns,
+            filter,
+            limit,
+            strict,
+        } => {
+            // Columns only the filter reads come from the raw record and need
+            // not be output.
+            let columns = match required {
+                Some(r) => Some(
+                    schema
+                        .fields
+                        .iter()
+                        .filter(|f| {
+                            let name = match &alias {
+                                Some(a) => format!("{a}.{}", f.name),
+                                None => f.name.clone(),
+                            };
+                            r.contains(&name)
+                        })
+                        .map(|f| f.name.clone())
+                        .collect::<Vec<_>>(),
+                )
+                .filter(|c| c.len() < schema.fields.len()),
+                None => columns,
+            };
+            LogicalPlan::Scan {
+                path,
+                alias,
+                schema,
+                columns,
+                filter,
+                limit,
+                strict,
+            }
+        }
+        LogicalPlan::Project { input, exprs } => {
+            // Output columns nobody needs are still computed; only their
+            // inputs matter here.
+            let needed: HashSet<String> = exprs
+                .iter()
+                .flat_map(|e| e.expr.columns())
+                .map(String::from)
+                .collect();
+            LogicalPlan::Project {
+                input: Box::new(prune_columns(*input, Some(&needed))),
+                exprs,
+            }
+        }
+        LogicalPlan::Filter { input, pred } => {
+            let needed = with(pred.columns());
+            LogicalPlan::Filter {
+                input: Box::new(prune_columns(*input, needed.as_ref())),
+                pred,
+            }
+        }
         LogicalPlan::Aggregate {
             input,
             group_keys,
             aggs,
-        } => LogicalPlan::Aggregate {
-            input: Box::new(pushdown_project(*input)),
-            group_keys,
-            aggs,
-        },
+        } => {
+            let needed: HashSet<String> = group_keys
+                .iter()
+                .map(String::as_str)
+                .chain(
+                    aggs.iter()
+                        .filter_map(|a| a.arg.as_ref())
+                        .flat_map(|e| e.columns()),
+                )
+                .map(String::from)
+                .collect();
+            LogicalPlan::Aggregate {
+                input: Box::new(prune_columns(*input, Some(&needed))),
+                group_keys,
+                aggs,
+            }
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            kind,
+            on,
+            build_left,
+        } => {
+            let keys = on
+                .iter()
+                .flat_map(|(l, r)| [l.as_str(), r.as_str()])
+                .collect();
+            let needed = with(keys);
+            LogicalPlan::Join {
+                left: Box::new(prune_columns(*left, needed.as_ref())),
+                right: Box::new(prune_columns(*right, needed.as_ref())),
+                kind,
+                on,
+                build_left,
+            }
+        }
+        LogicalPlan::Sort { input, keys } => {
+            let needed = with(keys.iter().map(|k| k.col.as_str()).collect());
+            LogicalPlan::Sort {
+                input: Box::new(prune_columns(*input, needed.as_ref())),
+                keys,
+            }
+        }
         LogicalPlan::Limit { input, n } => LogicalPlan::Limit {
-            input: Box::new(pushdown_project(*input)),
+            input: Box::new(prune_columns(*input, required)),
             n,
         },
-        scan @ LogicalPlan::Scan { .. } => scan,
+        LogicalPlan::TopK { input, keys, n } => {
+            let needed = with(keys.iter().map(|k| k.col.as_str()).collect());
+            LogicalPlan::TopK {
+                input: Box::new(prune_columns(*input, needed.as_ref())),
+                keys,
+                n,
+            }
+        }
+        LogicalPlan::Empty { mut schema } => {
+            if let Some(r) = required {
+                schema.fields.retain(|f| r.contains(&f.name));
+            }
+            LogicalPlan::Empty { schema }
+        }
+    }
+}
+
+fn fuse_topk(plan: LogicalPlan) -> LogicalPlan {
+    match plan {
+        LogicalPlan::Limit { input, n } => match *input {
+            LogicalPlan::Sort { input: inner, keys } => LogicalPlan::TopK {
+                input: inner,
+                keys,
+                n,
+            },
+            other => LogicalPlan::Limit {
+                input: Box::new(other),
+                n,
+            },
+        },
+        other => other,
+    }
+}
+
+/// Move limits below row-for-row projections and into the scan, which
+/// then stops reading after that many records.
+fn pushdown_limit(plan: LogicalPlan) -> LogicalPlan {
+    match plan {
+        LogicalPlan::Limit { input, n } => match *input {
+            LogicalPlan::Project {
+                input: inner,
+                exprs,
+            } => LogicalPlan::Project {
+                input: Box::new(LogicalPlan::Limit { input: inner, n }),
+                exprs,
+            },
+            LogicalPlan::Limit { input: inner, n: m } => pushdown_limit(LogicalPlan::Limit {
+                input: inner,
+                n: n.min(m),
+            }),
+            LogicalPlan::Scan {
+                path,
+                alias,
+                schema,
+                columns,
+                filter,
+                limit,
+                strict,
+            } => LogicalPlan::Scan {
+                path,
+                alias,
+                schema,
+                columns,
+                filter,
+                limit: Some(limit.map_or(n, |l| l.min(n))),
+                strict,
+            },
+            other => LogicalPlan::Limit {
+                input: Box::new(other),
+                n,
+            },
+        },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `SELECT <outer> FROM (SELECT amount * 2 AS d, user_id FROM ..)`
+    fn stacked(outer: &[(&str, &str)]) -> LogicalPlan {
+        let named = |expr: &str, name: &str| NamedExpr {
+            expr: Expr::parse(expr).unwrap(),
+            name: name.to_string(),
+        };
+        let input = LogicalPlan::Empty {
+            schema: Schema {
+                fields: vec![
+                    Field {
+                        name: "user_id".into(),
+                        dtype: DataType::Utf8,
+                    },
+                    Field {
+                        name: "amount".into(),
+                        dtype: DataType::Int64,
+                    },
+                ],
+            },
+        };
+        let inner = LogicalPlan::Project {
+            input: Box::new(input),
+            exprs: vec![named("amount * 2", "d"), named("user_id", "user_id")],
+        };
+        LogicalPlan::Project {
+            input: Box::new(inner),
+            exprs: outer.iter().map(|(e, n)| named(e, n)).collect(),
+        }
+    }
+
+    fn project_exprs(plan: &LogicalPlan) -> Vec<String> {
+        match plan {
+            LogicalPlan::Project { exprs, .. } => exprs.iter().map(|e| e.to_string()).collect(),
+            other => panic!("expected a projection, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn stacked_projections_merge_through_aliases() {
+        let plan = pushdown_project(stacked(&[("d + 1", "e"), ("user_id", "u")]));
+
+        assert_eq!(
+            project_exprs(&plan),
+            ["amount * 2 + 1 AS e", "user_id AS u"]
+        );
+        let LogicalPlan::Project { input, .. } = &plan else {
+            unreachable!()
+        };
+        assert!(matches!(**input, LogicalPlan::Empty { .. }), "{plan:?}");
+    }
+
+    #[test]
+    fn stacked_projections_stay_apart_rather_than_repeat_work() {
+        let plan = stacked(&[("d + d", "e")]);
+        assert_eq!(pushdown_project(plan.clone()), plan);
+
+        // Column references may be copied freely.
+        let merged = pushdown_project(stacked(&[("user_id", "a"), ("user_id", "b")]));
+        assert_eq!(project_exprs(&merged), ["user_id AS a", "user_id AS b"]);
     }
 }
@@ -1,15 +1,95 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
+use std::fs;
+use std::ops::Range;
 
-use crate::exec::{CsvScan, ExecNode, FilterExec, HashAggregateExec, LimitExec, ProjectExec};
+use crate::exec::{
+    CsvScan, EmptyExec, ExecNode, FilterExec, GatherExec, HashAggregateExec, HashJoinExec,
+    InstrumentedExec, LimitExec, PartitionFn, ProjectExec, SortExec, TopKExec, split_ranges,
+};
 use crate::logical::LogicalPlan;
 
-pub fn to_physical_plan(plan: LogicalPlan) -> Result<Box<dyn ExecNode>> {
+/// Settings that shape physical execution.
+#[derive(Debug, Clone, Copy)]
+pub struct ExecOptions {
+    /// Byte ranges each scan is split into, each read on its own thread.
+    pub threads: usize,
+    /// Bytes of operator state kept in memory before spilling to disk.
+    pub memory_limit: Option<usize>,
+    /// Wrap every operator to collect runtime statistics.
+    pub analyze: bool,
+}
+
+impl Default for ExecOptions {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            memory_limit: None,
+            analyze: false,
+        }
+    }
+}
+
+pub fn to_physical_plan(plan: LogicalPlan, opts: &ExecOptions) -> Result<Box<dyn ExecNode>> {
+    let node = build_node(plan, opts)?;
+    Ok(if opts.analyze {
+        Box::new(InstrumentedExec::new(node))
+    } else {
+        node
+    })
+}
+
+fn build_node(plan: LogicalPlan, opts: &ExecOptions) -> Result<Box<dyn ExecNode>> {
+    if opts.threads > 1 && is_partitionable(&plan) {
+        let (template, parts) = partitions(&plan, opts)?;
+        return Ok(Box::new(GatherExec::new(template, parts)));
+    }
+
     Ok(match plan {
-        LogicalPlan::Scan { path } => Box::new(CsvScan::new(path)?),
+        LogicalPlan::Scan {
+            path,
+            alias,
+            schema,
+            columns,
+            filter,
+            limit,
+            strict,
+        } => Box::new(
+            CsvScan::new(path, alias, schema, columns, filter, strict, None)?.with_limit(limit),
+        ),
 
-        LogicalPlan::Filter { input, preds } => {
-            let child = to_physical_plan(*input)?;
-            Box::new(FilterExec::new(child, preds))
+        LogicalPlan::Join {
+            left,
+            right,
+            kind,
+            on,
+            build_left,
+        } => {
+            // Without an estimate from the optimizer, build the hash table
+            // on the side with less input data.
+            let build_left = build_left.unwrap_or_else(|| input_bytes(&left) < input_bytes(&right));
+            let left = to_physical_plan(*left, opts)?;
+            let right = to_physical_plan(*right, opts)?;
+            Box::new(HashJoinExec::new(left, right, kind, on, build_left))
+        }
+
+        LogicalPlan::Filter { input, pred } => {
+            let child = to_physical_plan(*input, opts)?;
+            Box::new(FilterExec::new(child, pred))
+        }
+
+        LogicalPlan::Aggregate {
+            input,
+            group_keys,
+            aggs,
+        } if opts.threads > 1 && is_partitionable(&input) => {
+            let (template, parts) = partitions(&input, opts)?;
+            Box::new(HashAggregateExec::partitioned(
+                parts,
+                template,
+                group_keys,
+                aggs,
+                opts.memory_limit,
+            )?)
         }
 
         LogicalPlan::Aggregate {
@@ -17,18 +97,136 @@ pub fn to_physical_plan(plan: LogicalPlan) -> Result<Box<dyn ExecNode>> {
             group_keys,
             aggs,
         } => {
-            let child = to_physical_plan(*input)?;
-            Box::new(HashAggregateExec::new(child, group_keys, aggs))
+            let child = to_physical_plan(*input, opts)?;
+            Box::new(HashAggregateExec::new(
+                child,
+                group_keys,
+                aggs,
+                opts.memory_limit,
+            )?)
         }
 
-        LogicalPlan::Project { input, cols } => {
-            let child = to_physical_plan(*input)?;
-            Box::new(ProjectExec::new(child, cols))
+        LogicalPlan::Project { input, exprs } => {
+            let child = to_physical_plan(*input, opts)?;
+            Box::new(ProjectExec::new(child, exprs)?)
+        }
+
+        LogicalPlan::Sort { input, keys } => {
+            let child = to_physical_plan(*input, opts)?;
+            Box::new(SortExec::new(child, keys, opts.memory_limit))
         }
 
         LogicalPlan::Limit { input, n } => {
-            let child = to_physical_plan(*input)?;
+            let child = to_physical_plan(*input, opts)?;
             Box::new(LimitExec::new(child, n))
         }
+
+        LogicalPlan::TopK { input, keys, n } => {
+            let child = to_physical_plan(*input, opts)?;
+            Box::new(TopKExec::new(child, keys, n))
+        }
+
+        LogicalPlan::Empty { schema } => Box::new(EmptyExec::new(schema)),
     })
 }
+
+/// A scan with only filters and projections above it, which can run
+/// independently over each byte range of the file.
+fn is_partitionable(plan: &LogicalPlan) -> bool {
+    match plan {
+        // Only a serial scan knows which records come first.
+        LogicalPlan::Scan { limit, .. } => limit.is_none(),
+        LogicalPlan::Filter { input, .. } | LogicalPlan::Project { input, .. } => {
+            is_partitionable(input)
+        }
+        _ => false,
+    }
+}
+
+/// One pipeline builder per byte range of the scanned file, plus an
+/// unexecuted whole-file pipeline that describes them. When analyzing, each
+/// node of every partition pipeline adds its statistics to the matching
+/// template node as the partition finishes.
+fn partitions(
+    plan: &LogicalPlan,
+    opts: &ExecOptions,
+) -> Result<(Box<dyn ExecNode>, Vec<PartitionFn>)> {
+    let mut sinks = Vec::new();
+    let template = build_partition(plan.clone(), None, &mut |node| {
+        if !opts.analyze {
+            return node;
+        }
+        let node = InstrumentedExec::new(node);
+        sinks.push(node.partition_stats());
+        Box::new(node)
+    })?;
+
+    let parts = split_ranges(scan_path(plan), opts.threads)?
+        .into_iter()
+        .map(|range| {
+            let plan = plan.clone();
+            let mut sinks = sinks.clone().into_iter();
+            Box::new(move || {
+                build_partition(plan, Some(range), &mut |node| match sinks.next() {
+                    Some(s) => Box::new(InstrumentedExec::reporting_to(node, s)),
+                    None => node,
+                })
+            }) as PartitionFn
+        })
+        .collect();
+    Ok((template, parts))
+}
+
+/// Build a partition pipeline, passing each node through `wrap` once its
+/// inputs are built.
+fn build_partition(
+    plan: LogicalPlan,
+    range: Option<Range<u64>>,
+    wrap: &mut dyn FnMut(Box<dyn ExecNode>) -> Box<dyn ExecNode>,
+) -> Result<Box<dyn ExecNode>> {
+    let node: Box<dyn ExecNode> = match plan {
+        LogicalPlan::Scan {
+            path,
+            alias,
+            schema,
+            columns,
+            filter,
+            limit,
+            strict,
+        } => Box::new(
+            CsvScan::new(path, alias, schema, columns, filter, strict, range)?.with_limit(limit),
+        ),
+        LogicalPlan::Filter { input, pred } => {
+            Box::new(FilterExec::new(build_partition(*input, range, wrap)?, pred))
+        }
+        LogicalPlan::Project { input, exprs } => Box::new(ProjectExec::new(
+            build_partition(*input, range, wrap)?,
+            exprs,
+        )?),
+        other => bail!("cannot partition plan node {other:?}"),
+    };
+    Ok(wrap(node))
+}
+
+fn scan_path(plan: &LogicalPlan) -> &str {
+    match plan {
+        LogicalPlan::Scan { path, .. } => path,
+        LogicalPlan::Filter { input, .. } | LogicalPlan::Project { input, .. } => scan_path(input),
+        _ => "",
+    }
+}
+
+/// Total size of the CSV files feeding a plan, as a rough input-size estimate.
+fn input_bytes(plan: &LogicalPlan) -> u64 {
+    match plan {
+        LogicalPlan::Scan { path, .. } => fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        LogicalPlan::Join { left, right, .. } => input_bytes(left) + input_bytes(right),
+        LogicalPlan::Filter { input, .. }
+        | LogicalPlan::Aggregate { input, .. }
+        | LogicalPlan::Project { input, .. }
+        | LogicalPlan::Sort { input, .. }
+        | LogicalPlan::Limit { input, .. }
+        | LogicalPlan::TopK { input, .. } => input_bytes(input),
+        LogicalPlan::Empty { .. } => 0,
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataType {
+    #[serde(alias = "int", alias = "integer")]
+    Int64,
+    #[serde(alias = "float", alias = "double")]
+    Float64,
+    #[serde(alias = "boolean")]
+    Bool,
+    #[serde(alias = "string", alias = "text")]
+    Utf8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub dtype: DataType,
+}
+
+/// Column names and types of a CSV source, in file order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schema {
+    pub fields: Vec<Field>,
+}
+
+pub type SchemaRef = Arc<Schema>;
+
+/// How a scan derives and enforces its schema.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanOptions {
+    /// Records sampled to infer column types.
+    pub infer_rows: usize,
+    /// Fail on cells that do not fit the column type instead of reading null.
+    pub strict: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            infer_rows: 1000,
+            strict: false,
+        }
+    }
+}
+
+impl DataType {
+    /// Narrowest type that can represent a single non-empty cell.
+    fn of_cell(t: &str) -> DataType {
+        // Leading zeros (zip codes, ids) would be lost as numbers.
+        let digits = t.strip_prefix('-').unwrap_or(t);
+        if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+            return DataType::Utf8;
+        }
+
+        if t.parse::<i64>().is_ok() {
+            DataType::Int64
+        } else if t.parse::<f64>().is_ok() {
+            DataType::Float64
+        } else if t.eq_ignore_ascii_case("true") || t.eq_ignore_ascii_case("false") {
+            DataType::Bool
+        } else {
+            DataType::Utf8
+        }
+    }
+
+    /// Smallest type covering both `self` and `other`.
+    fn widen(self, other: DataType) -> DataType {
+        use DataType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Int64, Float64) | (Float64, Int64) => Float64,
+            _ => Utf8,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::Int64 => write!(f, "int64"),
+            DataType::Float64 => write!(f, "float64"),
+            DataType::Bool => write!(f, "bool"),
+            DataType::Utf8 => write!(f, "utf8"),
+        }
+    }
+}
+
+impl Schema {
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f.name == name)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Fields named in `columns`, in schema order; all of them for `None`.
+    pub fn select(&self, columns: Option<&[String]>) -> Vec<&Field> {
+        self.fields
+            .iter()
+            .filter(|f| columns.is_none_or(|c| c.contains(&f.name)))
+            .collect()
+    }
+
+    /// Read the header and up to `sample_rows` records of a CSV and infer a
+    /// type per column. Columns in `explicit` take the given type instead;
+    /// columns with no non-empty sampled value default to utf8.
+    pub fn infer_csv(
+        path: &str,
+        sample_rows: usize,
+        explicit: &HashMap<String, DataType>,
+    ) -> Result<Schema> {
+        let file = File::open(path).with_context(|| format!("Failed to open CSV: {path}"))?;
+        let mut rdr = csv::Reader::from_reader(file);
+
+        let headers: Vec<String> = rdr
+            .headers()
+            .context("CSV missing headers row")?
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        for name in explicit.keys() {
+            if !headers.contains(name) {
+                bail!("schema names column `{name}`, which is not in {path}");
+            }
+        }
+
+        let mut seen: Vec<Option<DataType>> = vec![None; headers.len()];
+        let needs_sampling = headers.iter().any(|h| !explicit.contains_key(h));
+        if needs_sampling {
+            for rec in rdr.records().take(sample_rows) {
+                let rec = rec.with_context(|| format!("Failed to read CSV: {path}"))?;
+                for (i, cell) in rec.iter().enumerate().take(headers.len()) {
+                    let t = cell.trim();
+                    if t.is_empty() {
+                        continue;
+                    }
+                    let dt = DataType::of_cell(t);
+                    seen[i] = Some(seen[i].map_or(dt, |cur| cur.widen(dt)));
+                }
+            }
+        }
+
+        let fields = headers
+            .into_iter()
+            .zip(seen)
+            .map(|(name, inferred)| Field {
+                dtype: explicit
+                    .get(&name)
+                    .copied()
+                    .or(inferred)
+                    .unwrap_or(DataType::Utf8),
+                name,
+            })
+            .collect();
+
+        Ok(Schema { fields })
+    }
+}
@@ -0,0 +1,313 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::ast::{
+    Comparison, JoinClause, JoinKind, JoinOn, NullsOrder, OrderKey, PredExpr, Predicate, Query,
+    SortDir, flip,
+};
+use crate::expr::{Expr, is_reserved, parse_column_name, parse_expr, parse_select_item};
+use crate::lexer::{TokenKind, TokenStream};
+
+/// Parse a SQL query into the same `Query` the JSON DSL produces:
+///
+/// ```sql
+/// SELECT item [, ...] FROM 'path.csv' [[AS] alias]
+/// [[INNER | LEFT | RIGHT | FULL [OUTER]] JOIN 'path.csv' [[AS] alias]
+///     ON a.col = b.col [AND ...]] ...
+/// [WHERE cond] [GROUP BY col [, ...]] [HAVING cond]
+/// [ORDER BY expr [ASC|DESC] [NULLS FIRST|LAST] [, ...]] [LIMIT n]
+/// ```
+///
+/// Conditions combine `expr op expr` comparisons with AND, OR, NOT and
+/// parentheses. Errors carry the line and column of the offending token.
+pub fn parse_sql(src: &str) -> Result<Query> {
+    let mut ts = TokenStream::new(src)?;
+
+    ts.expect_keyword("select")?;
+    let mut select = vec![parse_select_item(&mut ts)?];
+    while ts.eat_symbol(",") {
+        select.push(parse_select_item(&mut ts)?);
+    }
+
+    ts.expect_keyword("from")?;
+    let (from, alias) = parse_source(&mut ts)?;
+
+    let mut join = Vec::new();
+    while let Some(kind) = parse_join_kind(&mut ts)? {
+        let (from, alias) = parse_source(&mut ts)?;
+        ts.expect_keyword("on")?;
+
+        let mut on = vec![parse_join_on(&mut ts)?];
+        while ts.eat_keyword("and") {
+            on.push(parse_join_on(&mut ts)?);
+        }
+
+        join.push(JoinClause {
+            from,
+            alias,
+            schema: HashMap::new(),
+            kind,
+            on,
+        });
+    }
+
+    let r#where = if ts.eat_keyword("where") {
+        Some(parse_or(&mut ts, Clause::Where)?)
+    } else {
+        None
+    };
+
+    let mut group_by = Vec::new();
+    if ts.eat_keyword("group") {
+        ts.expect_keyword("by")?;
+        group_by.push(parse_column_name(&mut ts)?);
+        while ts.eat_symbol(",") {
+            group_by.push(parse_column_name(&mut ts)?);
+        }
+    }
+
+    let having = if ts.eat_keyword("having") {
+        Some(parse_or(&mut ts, Clause::Having)?)
+    } else {
+        None
+    };
+
+    let mut order_by = Vec::new();
+    if ts.eat_keyword("order") {
+        ts.expect_keyword("by")?;
+        order_by.push(parse_order_key(&mut ts)?);
+        while ts.eat_symbol(",") {
+            order_by.push(parse_order_key(&mut ts)?);
+        }
+    }
+
+    let limit = if ts.eat_keyword("limit") {
+        match ts.peek_kind().clone() {
+            TokenKind::Int(n) if n >= 0 => {
+                ts.advance();
+                Some(n as usize)
+            }
+            other => return Err(ts.error(&format!("expected a row count, found {other}"))),
+        }
+    } else {
+        None
+    };
+
+    ts.eat_symbol(";");
+    ts.expect_eof()?;
+
+    Ok(Query {
+        from,
+        alias,
+        schema: HashMap::new(),
+        join,
+        select,
+        r#where,
+        group_by,
+        having,
+        order_by,
+        limit,
+    })
+}
+
+// CSV path followed by an optional `[AS] alias`.
+fn parse_source(ts: &mut TokenStream) -> Result<(String, Option<String>)> {
+    let path = match ts.peek_kind().clone() {
+        TokenKind::Str(s) | TokenKind::QuotedIdent(s) => {
+            ts.advance();
+            s
+        }
+        other => return Err(ts.error(&format!("expected a quoted CSV path, found {other}"))),
+    };
+
+    let bare_alias = matches!(
+        ts.peek_kind(),
+        TokenKind::Ident(s) if !is_reserved(&s.to_ascii_lowercase())
+    );
+    let alias = if ts.eat_keyword("as") || bare_alias {
+        Some(ts.expect_ident()?)
+    } else {
+        None
+    };
+
+    Ok((path, alias))
+}
+
+fn parse_join_kind(ts: &mut TokenStream) -> Result<Option<JoinKind>> {
+    let kind = if ts.eat_keyword("join") {
+        return Ok(Some(JoinKind::Inner));
+    } else if ts.eat_keyword("inner") {
+        JoinKind::Inner
+    } else if ts.eat_keyword("left") {
+        JoinKind::Left
+    } else if ts.eat_keyword("right") {
+        JoinKind::Right
+    } else if ts.eat_keyword("full") {
+        JoinKind::Full
+    } else {
+        return Ok(None);
+    };
+
+    if kind != JoinKind::Inner {
+        ts.eat_keyword("outer");
+    }
+    ts.expect_keyword("join")?;
+    Ok(Some(kind))
+}
+
+fn parse_join_on(ts: &mut TokenStream) -> Result<JoinOn> {
+    let left = parse_column_name(ts)?;
+    if !ts.eat_symbol("=") {
+        ts.expect_symbol("==")?;
+    }
+    let right = parse_column_name(ts)?;
+    Ok(JoinOn { left, right })
+}
+
+fn parse_order_key(ts: &mut TokenStream) -> Result<OrderKey> {
+    let col = column_name(parse_expr(ts)?);
+
+    let dir = if ts.eat_keyword("desc") {
+        SortDir::Desc
+    } else {
+        ts.eat_keyword("asc");
+        SortDir::Asc
+    };
+
+    let nulls = if ts.eat_keyword("nulls") {
+        if ts.eat_keyword("first") {
+            Some(NullsOrder::First)
+        } else {
+            ts.expect_keyword("last")?;
+            Some(NullsOrder::Last)
+        }
+    } else {
+        None
+    };
+
+    Ok(OrderKey { col, dir, nulls })
+}
+
+// Output column an expression refers to: plain columns by name, anything
+// else by its expression text (which is how unaliased select items are named).
+fn column_name(e: Expr) -> String {
+    match e {
+        Expr::Column(c) => c,
+        other => other.to_string(),
+    }
+}
+
+/// Which clause a condition belongs to; only HAVING may compare aggregates.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Clause {
+    Where,
+    Having,
+}
+
+fn parse_or(ts: &mut TokenStream, clause: Clause) -> Result<PredExpr> {
+    let mut items = vec![parse_and(ts, clause)?];
+    while ts.eat_keyword("or") {
+        items.push(parse_and(ts, clause)?);
+    }
+    Ok(if items.len() == 1 {
+        items.remove(0)
+    } else {
+        PredExpr::Or { or: items }
+    })
+}
+
+fn parse_and(ts: &mut TokenStream, clause: Clause) -> Result<PredExpr> {
+    let mut items = vec![parse_not(ts, clause)?];
+    while ts.eat_keyword("and") {
+        items.push(parse_not(ts, clause)?);
+    }
+    Ok(if items.len() == 1 {
+        items.remove(0)
+    } else {
+        PredExpr::And { and: items }
+    })
+}
+
+fn parse_not(ts: &mut TokenStream, clause: Clause) -> Result<PredExpr> {
+    if ts.eat_keyword("not") {
+        return Ok(PredExpr::Not {
+            not: Box::new(parse_not(ts, clause)?),
+        });
+    }
+
+    if ts.peek_kind() != &TokenKind::Symbol("(") {
+        return parse_comparison(ts, clause);
+    }
+
+    // `(` opens either a nested condition or a parenthesized operand such as
+    // `(amount + 1) > 10`. Try both and report whichever got further.
+    let start = ts.pos();
+    ts.advance();
+    let nested_err = match parse_or(ts, clause).and_then(|p| ts.expect_symbol(")").map(|_| p)) {
+        Ok(p) => return Ok(p),
+        Err(e) => e,
+    };
+    let nested_end = ts.pos();
+
+    ts.reset(start);
+    parse_comparison(ts, clause).map_err(|e| if ts.pos() > nested_end { e } else { nested_err })
+}
+
+fn parse_comparison(ts: &mut TokenStream, clause: Clause) -> Result<PredExpr> {
+    let lhs_tok = ts.peek().clone();
+    let lhs = parse_expr(ts)?;
+
+    let op_tok = ts.peek().clone();
+    let op = match op_tok.kind {
+        TokenKind::Symbol("=") | TokenKind::Symbol("==") => "==",
+        TokenKind::Symbol("!=") | TokenKind::Symbol("<>") => "!=",
+        TokenKind::Symbol("<") => "<",
+        TokenKind::Symbol("<=") => "<=",
+        TokenKind::Symbol(">") => ">",
+        TokenKind::Symbol(">=") => ">=",
+        other => {
+            return Err(ts.error(&format!("expected a comparison operator, found {other}")));
+        }
+    };
+    ts.advance();
+
+    let rhs_tok = ts.peek().clone();
+    let rhs = parse_expr(ts)?;
+
+    if clause == Clause::Where {
+        for (e, tok) in [(&lhs, &lhs_tok), (&rhs, &rhs_tok)] {
+            if e.contains_agg() {
+                return Err(ts.error_at(
+                    tok,
+                    &format!("cannot compare `{e}` here; aggregates belong in HAVING"),
+                ));
+            }
+        }
+    }
+
+    // A column (or, in HAVING, an aggregate) against a literal is a plain
+    // predicate; `literal op column` is flipped. Anything else is kept as
+    // written, for the optimizer's `simplify` rule to fold.
+    let column = |e: &Expr| match e {
+        Expr::Column(c) => Some(c.clone()),
+        Expr::Agg { .. } => Some(e.to_string()),
+        _ => None,
+    };
+    let col_lit = match (&lhs, &rhs) {
+        (l, Expr::Literal(v)) => column(l).map(|c| (c, op, v.clone())),
+        (Expr::Literal(v), r) => column(r).map(|c| (c, flip(op), v.clone())),
+        _ => None,
+    };
+    Ok(match col_lit {
+        Some((col, op, val)) => PredExpr::Cmp(Predicate {
+            col,
+            op: op.to_string(),
+            val,
+        }),
+        None => PredExpr::Compare(Comparison {
+            left: lhs,
+            op: op.to_string(),
+            right: rhs,
+        }),
+    })
+}
@@ -0,0 +1,303 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::fs;
+
+use crate::batch::{Column, ColumnData, Scalar};
+use crate::exec::{CsvScan, ExecNode};
+use crate::schema::{DataType, Schema};
+
+/// Statistics of a CSV file, stored next to it in `<file>.stats.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableStats {
+    /// Size of the file when it was scanned; statistics for a file whose
+    /// size has since changed are ignored.
+    pub bytes: u64,
+    pub rows: u64,
+    pub columns: Vec<ColumnStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnStats {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub dtype: DataType,
+    /// Smallest and largest non-null values; null when every cell is.
+    pub min: JsonValue,
+    pub max: JsonValue,
+    pub nulls: u64,
+    /// Distinct non-null values, estimated with HyperLogLog; typically
+    /// within a few percent, and exact or off by one for small columns.
+    pub distinct: u64,
+    /// Equi-depth buckets over the non-null values of numeric columns, in
+    /// ascending order; each holds the values above the previous bucket's
+    /// `upper` (or from `min`) up to its own. Past 10,000 values the buckets are
+    /// drawn from a uniform sample, with row counts scaled to the column.
+    #[serde(default)]
+    pub histogram: Vec<Bucket>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bucket {
+    pub upper: f64,
+    pub rows: u64,
+}
+
+/// Where the statistics of the CSV at `path` are kept.
+pub fn sidecar_path(path: &str) -> String {
+    format!("{path}.stats.json")
+}
+
+impl TableStats {
+    /// Scan the whole CSV at `path`, reading cells as `schema` types them,
+    /// and summarize each column with up to `buckets` histogram buckets.
+    pub fn collect(path: &str, schema: Schema, buckets: usize) -> Result<TableStats> {
+        let bytes = fs::metadata(path)
+            .with_context(|| format!("Failed to open CSV: {path}"))?
+            .len();
+        let mut acc: Vec<ColumnAcc> = schema.fields.iter().map(|_| ColumnAcc::default()).collect();
+        let mut rows = 0;
+
+        let mut scan = CsvScan::new(
+            path.to_string(),
+            None,
+            schema.clone(),
+            None,
+            None,
+            false,
+            None,
+        )?;
+        while let Some(batch) = scan.next_batch()? {
+            rows += batch.num_rows() as u64;
+            for (a, col) in acc.iter_mut().zip(batch.columns()) {
+                a.add(col);
+            }
+        }
+
+        let columns = schema
+            .fields
+            .iter()
+            .zip(acc)
+            .map(|(f, a)| a.finish(&f.name, f.dtype, buckets))
+            .collect();
+        Ok(TableStats {
+            bytes,
+            rows,
+            columns,
+        })
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnStats> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let out = sidecar_path(path);
+        fs::write(&out, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write statistics: {out}"))
+    }
+
+    /// Statistics saved for the CSV at `path`, if there are any and the
+    /// file has not changed size since.
+    pub fn load(path: &str) -> Result<Option<TableStats>> {
+        let side = sidecar_path(path);
+        let Ok(raw) = fs::read_to_string(&side) else {
+            return Ok(None);
+        };
+        let stats: TableStats = serde_json::from_str(&raw)
+            .with_context(|| format!("Failed to parse statistics: {side}"))?;
+        let bytes = fs::metadata(path).map(|m| m.len()).ok();
+        Ok((bytes == Some(stats.bytes)).then_some(stats))
+    }
+}
+
+// HyperLogLog registers are indexed by this many bits of each hash.
+const HLL_BITS: u32 = 12;
+const HLL_REGISTERS: usize = 1 << HLL_BITS;
+
+/// Hash of a value for the distinct count: FNV-1a over a type tag and the
+/// value's bytes, then `mix` to spread it into the high bits HyperLogLog
+/// reads. Unlike the standard library's hashers it is fixed, so the same
+/// file gives the same statistics with any build.
+fn stable_hash(tag: u8, bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xCBF2_9CE4_8422_2325;
+    for &b in std::iter::once(&tag).chain(bytes) {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    mix(h)
+}
+
+// The splitmix64 finalizer.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Numeric values kept for the histogram; larger columns are sampled.
+const SAMPLE_SIZE: usize = 10_000;
+
+/// Running summary of one column, in memory that does not grow with the
+/// file: a HyperLogLog sketch for distinct values and a reservoir sample for
+/// the histogram.
+struct ColumnAcc {
+    nulls: u64,
+    min: Option<Scalar>,
+    max: Option<Scalar>,
+    registers: Vec<u8>,
+    // Numeric values seen, and a uniform sample of at most SAMPLE_SIZE.
+    numbers_seen: u64,
+    sample: Vec<f64>,
+    rng: u64,
+}
+
+impl Default for ColumnAcc {
+    fn default() -> Self {
+        Self {
+            nulls: 0,
+            min: None,
+            max: None,
+            registers: vec![0; HLL_REGISTERS],
+            numbers_seen: 0,
+            sample: Vec::new(),
+            // Fixed seed, so the same file always gives the same statistics.
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+}
+
+impl ColumnAcc {
+    fn add(&mut self, col: &Column) {
+        for i in 0..col.len() {
+            if !col.is_valid(i) {
+                self.nulls += 1;
+                continue;
+            }
+            let hash = match &col.data {
+                ColumnData::Int64(v) => {
+                    self.add_number(v[i] as f64);
+                    stable_hash(0, &v[i].to_le_bytes())
+                }
+                ColumnData::Float64(v) => {
+                    self.add_number(v[i]);
+                    stable_hash(1, &v[i].to_bits().to_le_bytes())
+                }
+                ColumnData::Bool(v) => stable_hash(2, &[v[i] as u8]),
+                ColumnData::Utf8(v) => stable_hash(3, v[i].as_bytes()),
+            };
+            self.add_hash(hash);
+
+            let v = col.value(i);
+            if self.min.as_ref().is_none_or(|m| v.cmp_value(m).is_lt()) {
+                self.min = Some(v.clone());
+            }
+            if self.max.as_ref().is_none_or(|m| v.cmp_value(m).is_gt()) {
+                self.max = Some(v);
+            }
+        }
+    }
+
+    fn add_hash(&mut self, hash: u64) {
+        let register = (hash >> (64 - HLL_BITS)) as usize;
+        // Position of the first set bit in the rest of the hash.
+        let rank = ((hash << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    /// Reservoir sampling: the n-th value replaces a random kept one with
+    /// probability SAMPLE_SIZE / n.
+    fn add_number(&mut self, v: f64) {
+        self.numbers_seen += 1;
+        if self.sample.len() < SAMPLE_SIZE {
+            self.sample.push(v);
+            return;
+        }
+        let slot = self.next_random() % self.numbers_seen;
+        if let Some(kept) = self.sample.get_mut(slot as usize) {
+            *kept = v;
+        }
+    }
+
+    // splitmix64
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.rng)
+    }
+
+    fn distinct(&self) -> u64 {
+        let m = HLL_REGISTERS as f64;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        // Small cardinalities leave registers empty; linear counting is far
+        // more accurate there.
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+
+    fn finish(mut self, name: &str, dtype: DataType, buckets: usize) -> ColumnStats {
+        let distinct = self.distinct();
+        self.sample.sort_by(f64::total_cmp);
+        let n = self.sample.len();
+        // Sample positions map to rows of the whole column.
+        let rows_before = |pos: usize| (pos as u64 * self.numbers_seen).div_ceil(n.max(1) as u64);
+        let mut histogram = Vec::new();
+        let mut start = 0;
+        for b in 1..=buckets.min(n) {
+            if start == n {
+                break;
+            }
+            // Equal values stay in one bucket, so bounds strictly increase.
+            let mut end = (b * n / buckets.min(n)).max(start + 1);
+            while end < n && self.sample[end] == self.sample[end - 1] {
+                end += 1;
+            }
+            histogram.push(Bucket {
+                upper: self.sample[end - 1],
+                rows: rows_before(end) - rows_before(start),
+            });
+            start = end;
+        }
+
+        ColumnStats {
+            name: name.to_string(),
+            dtype,
+            min: self.min.map_or(JsonValue::Null, |v| v.to_json()),
+            max: self.max.map_or(JsonValue::Null, |v| v.to_json()),
+            nulls: self.nulls,
+            distinct,
+            histogram,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_hashes_are_pinned() {
+        // Changing these changes the distinct counts saved in existing
+        // statistics files.
+        let hashes = [
+            stable_hash(0, &42i64.to_le_bytes()),
+            stable_hash(1, &42f64.to_bits().to_le_bytes()),
+            stable_hash(2, &[1]),
+            stable_hash(3, b"SF"),
+        ];
+        assert_eq!(
+            hashes,
+            [
+                16_267_352_461_814_258_211,
+                18_330_213_170_224_273_833,
+                13_670_887_579_485_949_403,
+                5_909_029_765_084_533_404,
+            ]
+        );
+    }
+}
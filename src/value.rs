@@ -1,39 +1,26 @@
 use anyhow::{Result, anyhow};
 use serde_json::Value as JsonValue;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 pub type Row = HashMap<String, JsonValue>;
 
-pub fn parse_cell(s: &str) -> JsonValue {
-    let t = s.trim();
-
-    if t.is_empty() {
-        return JsonValue::Null;
-    }
-    if let Ok(i) = t.parse::<i64>() {
-        return JsonValue::from(i);
-    }
-    if let Ok(f) = t.parse::<f64>() {
-        return JsonValue::from(f);
-    }
-    if t.eq_ignore_ascii_case("true") {
-        return JsonValue::from(true);
-    }
-    if t.eq_ignore_ascii_case("false") {
-        return JsonValue::from(false);
-    }
-
-    JsonValue::from(t.to_string())
+/// Approximate bytes a row holds, for operators that buffer rows.
+pub fn row_bytes(row: &Row) -> usize {
+    row.iter()
+        .map(|(k, v)| {
+            let text = match v {
+                JsonValue::String(s) => s.len(),
+                _ => 0,
+            };
+            std::mem::size_of::<(String, JsonValue)>() + k.len() + text
+        })
+        .sum()
 }
 
 pub fn cmp_json(lhs: &JsonValue, op: &str, rhs: &JsonValue) -> Result<bool> {
     // numeric compare if both can be numbers
-    let ln = lhs.as_f64();
-    let rn = rhs.as_f64();
-
-    if ln.is_some() && rn.is_some() {
-        let a = ln.unwrap();
-        let b = rn.unwrap();
+    if let (Some(a), Some(b)) = (lhs.as_f64(), rhs.as_f64()) {
         return Ok(match op {
             ">" => a > b,
             ">=" => a >= b,
@@ -65,3 +52,18 @@ pub fn cmp_json(lhs: &JsonValue, op: &str, rhs: &JsonValue) -> Result<bool> {
         _ => return Err(anyhow!("Unsupported operator: {op}")),
     })
 }
+
+/// Total order used for sorting non-null values: numbers compare
+/// numerically, strings lexically, anything else by its JSON text.
+pub fn cmp_values(a: &JsonValue, b: &JsonValue) -> Ordering {
+    match (a, b) {
+        (JsonValue::String(sa), JsonValue::String(sb)) => sa.cmp(sb),
+        (JsonValue::Number(na), JsonValue::Number(nb)) => {
+            let fa = na.as_f64().unwrap_or(0.0);
+            let fb = nb.as_f64().unwrap_or(0.0);
+            fa.partial_cmp(&fb).unwrap_or(Ordering::Equal)
+        }
+        (JsonValue::Bool(ba), JsonValue::Bool(bb)) => ba.cmp(bb),
+        (va, vb) => va.to_string().cmp(&vb.to_string()),
+    }
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::run_json;
+use serde_json::json;
+
+#[test]
+fn aggregates_ignore_nulls_and_keep_types() {
+    let rows = run_json(&["queries/q11_aggregate_functions.json"]);
+
+    assert_eq!(
+        rows,
+        vec![
+            json!({
+                "category": "x",
+                "count(*)": 3,
+                "count(rating)": 2,
+                "sum(rating)": 8.0,
+                "avg(rating)": 4.0,
+                "min(rating)": 3,
+                "max(rating)": 5
+            }),
+            json!({
+                "category": "y",
+                "count(*)": 2,
+                "count(rating)": 1,
+                "sum(rating)": 4.0,
+                "avg(rating)": 4.0,
+                "min(rating)": 4,
+                "max(rating)": 4
+            }),
+        ]
+    );
+}
+
+#[test]
+fn count_distinct_and_string_min_max() {
+    let rows = run_json(&["queries/q12_global_aggregates.json"]);
+
+    assert_eq!(
+        rows,
+        vec![json!({"n": 5, "categories": 2, "min(item)": "a", "max(item)": "e"})]
+    );
+}
+
+#[test]
+fn global_aggregate_over_no_rows_returns_one_row() {
+    let rows = run_json(&["queries/q13_empty_aggregate.json"]);
+
+    assert_eq!(
+        rows,
+        vec![json!({
+            "count(*)": 0,
+            "sum(rating)": null,
+            "avg(rating)": null,
+            "max(rating)": null
+        })]
+    );
+}
@@ -0,0 +1,39 @@
+#![allow(dead_code)]
+
+use std::process::Command;
+
+// Run the compiled binary directly
+pub fn run_bin(args: &[&str]) -> (String, String, i32) {
+    let exe = env!("CARGO_BIN_EXE_mini_query_engine");
+
+    let output = Command::new(exe)
+        .args(args)
+        .output()
+        .expect("failed to execute mini_query_engine binary");
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let code = output.status.code().unwrap_or(-1);
+
+    (stdout, stderr, code)
+}
+
+pub fn run_all(args: &[&str]) -> String {
+    let (out, err, code) = run_bin(args);
+    assert_eq!(code, 0, "process failed.\nSTDOUT:\n{out}\nSTDERR:\n{err}");
+    format!("{out}{err}")
+}
+
+// Run a query with `--format json` and return the result rows
+pub fn run_json(args: &[&str]) -> Vec<serde_json::Value> {
+    let mut full = vec!["--format", "json"];
+    full.extend_from_slice(args);
+
+    let (out, err, code) = run_bin(&full);
+    assert_eq!(code, 0, "process failed.\nSTDOUT:\n{out}\nSTDERR:\n{err}");
+
+    match serde_json::from_str(&out) {
+        Ok(serde_json::Value::Array(rows)) => rows,
+        other => panic!("expected a JSON array of rows, got {other:?}\n{out}"),
+    }
+}
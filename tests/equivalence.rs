@@ -0,0 +1,64 @@
+mod common;
+
+use common::run_json;
+use std::fs;
+
+const RULES: [&str; 8] = [
+    "pushdown_filter",
+    "simplify",
+    "pushdown_project",
+    "prune_columns",
+    "fuse_topk",
+    "pushdown_limit",
+    "order_predicates",
+    "choose_build_side",
+];
+
+// Every query that should run: the files directly in `queries/`, leaving
+// out the deliberately broken ones under `queries/bad/`.
+fn query_files() -> Vec<String> {
+    let mut paths: Vec<String> = fs::read_dir("queries")
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.is_file())
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    paths
+}
+
+fn run_without(rules: &[&str], query: &str) -> Vec<serde_json::Value> {
+    let mut args = Vec::new();
+    for rule in rules {
+        args.extend(["--disable-rule", rule]);
+    }
+    args.push(query);
+    run_json(&args)
+}
+
+#[test]
+fn optimizer_does_not_change_results() {
+    for query in query_files() {
+        let optimized = run_json(&[&query]);
+        assert_eq!(
+            run_without(&RULES, &query),
+            optimized,
+            "{query}: unoptimized plan gives different rows"
+        );
+    }
+}
+
+#[test]
+fn each_rule_alone_can_be_skipped_without_changing_results() {
+    for query in query_files() {
+        let optimized = run_json(&[&query]);
+        for rule in RULES {
+            assert_eq!(
+                run_without(&[rule], &query),
+                optimized,
+                "{query}: skipping {rule} gives different rows"
+            );
+        }
+    }
+}
@@ -0,0 +1,291 @@
+mod common;
+
+use common::{run_bin, run_json};
+use serde_json::json;
+
+fn run_ok(args: &[&str]) -> String {
+    let (out, err, code) = run_bin(args);
+    assert_eq!(code, 0, "process failed.\nSTDOUT:\n{out}\nSTDERR:\n{err}");
+    out
+}
+
+// The line for the first operator named `name`, indentation removed.
+fn node_line<'a>(out: &'a str, name: &str) -> &'a str {
+    out.lines()
+        .map(str::trim_start)
+        .find(|l| l.starts_with(&format!("{name}(")))
+        .unwrap_or_else(|| panic!("no {name} in:\n{out}"))
+}
+
+#[test]
+fn explain_analyze_annotates_each_operator() {
+    let out = run_ok(&["--explain-analyze", "queries/q4_filtered_grouped.json"]);
+
+    // Runs the query but prints only the annotated plan.
+    assert!(!out.contains("user_id  "), "{out}");
+    for name in ["Sort", "Project", "HashAggregate", "CsvScan"] {
+        let line = node_line(&out, name);
+        for stat in ["rows_out=", "time=", "peak_mem="] {
+            assert!(line.contains(stat), "{name} lacks {stat}: {line}");
+        }
+    }
+
+    // The WHERE clause runs in the scan, which counts what it rejects.
+    let scan = node_line(&out, "CsvScan");
+    assert!(scan.contains("CsvScan(rejected=3)"), "{scan}");
+    assert!(scan.contains("rows_out=3"), "{scan}");
+    assert!(!scan.contains("rows_in="), "{scan}");
+
+    let agg = node_line(&out, "HashAggregate");
+    assert!(agg.contains("rows_in=3"), "{agg}");
+}
+
+#[test]
+fn explain_analyze_reports_groups_and_partitioned_input() {
+    let out = run_ok(&[
+        "--explain-analyze",
+        "--threads",
+        "2",
+        "queries/q3_sum_and_count.json",
+    ]);
+
+    let agg = node_line(&out, "HashAggregate");
+    assert!(agg.contains("groups=4"), "{agg}");
+    assert!(agg.contains("rows_in=6, rows_out=4"), "{agg}");
+}
+
+#[test]
+fn explain_analyze_combines_partition_statistics() {
+    for threads in ["1", "2"] {
+        let out = run_ok(&[
+            "--explain-analyze",
+            "--threads",
+            threads,
+            "queries/q4_filtered_grouped.json",
+        ]);
+
+        // Under threads, the scan's counts come from every partition.
+        let scan = node_line(&out, "CsvScan");
+        assert!(scan.contains("CsvScan(rejected=3)"), "{out}");
+        assert!(scan.contains("rows_out=3"), "{out}");
+        assert!(scan.contains("peak_mem=0B"), "{out}");
+    }
+}
+
+#[test]
+fn explain_physical_describes_each_operator() {
+    let out = run_ok(&["--explain-physical", "queries/q16_join_spend_by_tier.json"]);
+
+    assert!(
+        node_line(&out, "HashAggregate")
+            .contains("group_keys=[u.tier], aggs=[count(*), sum(t.amount)]"),
+        "{out}"
+    );
+    assert!(
+        node_line(&out, "HashJoin").contains("type=inner, on=[t.user_id = u.user_id], build="),
+        "{out}"
+    );
+    assert!(
+        out.contains("CsvScan(path=\"data/users.csv\", as=u, columns=[user_id: utf8, tier: utf8], strict=false)"),
+        "{out}"
+    );
+    // Nothing runs, so no runtime statistics.
+    assert!(!out.contains("rows_out="), "{out}");
+}
+
+#[test]
+fn explain_physical_shows_predicates_projections_and_options() {
+    let out = run_ok(&[
+        "--explain-physical",
+        "--threads",
+        "3",
+        "--memory-limit",
+        "2MB",
+        "--sql",
+        "SELECT user_id, amount * 2 AS doubled FROM 'data/transactions.csv' \
+         WHERE amount > 10 ORDER BY doubled LIMIT 2",
+    ]);
+
+    assert!(
+        node_line(&out, "TopK").contains("n=2, keys=[doubled ASC NULLS LAST]"),
+        "{out}"
+    );
+    assert!(node_line(&out, "Gather").contains("threads=3"), "{out}");
+    assert!(
+        node_line(&out, "Project").contains("exprs=[user_id, amount * 2 AS doubled]"),
+        "{out}"
+    );
+    assert!(
+        node_line(&out, "CsvScan").contains("filter=amount > 10"),
+        "{out}"
+    );
+
+    let agg = run_ok(&[
+        "--explain-physical",
+        "--memory-limit",
+        "2MB",
+        "queries/q3_sum_and_count.json",
+    ]);
+    assert!(
+        node_line(&agg, "HashAggregate").contains("memory_limit=2.0MB, spill_partitions=16"),
+        "{agg}"
+    );
+}
+
+fn explain_json(args: &[&str]) -> serde_json::Value {
+    let out = run_ok(args);
+    serde_json::from_str(&out).unwrap_or_else(|e| panic!("invalid JSON ({e}):\n{out}"))
+}
+
+// Nodes from the root down the first-input chain.
+fn node_chain(mut node: &serde_json::Value) -> Vec<&serde_json::Value> {
+    let mut chain = vec![node];
+    while let Some(first) = node["inputs"].get(0) {
+        chain.push(first);
+        node = first;
+    }
+    chain
+}
+
+#[test]
+fn explain_json_serializes_the_whole_plan() {
+    let plan = explain_json(&[
+        "--explain",
+        "--explain-format",
+        "json",
+        "queries/q14_having.json",
+    ]);
+
+    let chain = node_chain(&plan);
+    let names: Vec<_> = chain.iter().map(|n| n["node"].as_str().unwrap()).collect();
+    assert_eq!(names, ["Sort", "Project", "Filter", "Aggregate", "Scan"]);
+
+    // HAVING keeps its structure, as in the query DSL.
+    let having = &chain[2]["predicate"]["or"];
+    assert_eq!(having[0]["col"], "sum(amount)");
+    assert_eq!(having[0]["op"], ">");
+
+    let aggs = chain[3]["aggs"].as_array().unwrap();
+    assert!(
+        aggs.iter()
+            .any(|a| a["func"] == "count" && a["arg"].is_null())
+    );
+    assert!(
+        aggs.iter()
+            .any(|a| a["func"] == "sum" && a["arg"] == json!({"col": "amount"}))
+    );
+    assert_eq!(
+        chain[1]["exprs"][1],
+        json!({"name": "total", "expr": {"col": "sum(amount)"}})
+    );
+
+    let scan = chain[4];
+    assert_eq!(scan["path"], "data/transactions.csv");
+    assert_eq!(scan["columns"][1]["name"], "amount");
+    assert_eq!(scan["columns"][1]["type"], "int64");
+}
+
+#[test]
+fn explain_both_json_has_original_and_optimized_plans() {
+    let plans = explain_json(&[
+        "--explain-both",
+        "--explain-format",
+        "json",
+        "queries/q6_top_users.json",
+    ]);
+
+    assert_eq!(plans["original"]["node"], "Limit");
+    assert_eq!(plans["optimized"]["node"], "TopK");
+    assert_eq!(plans["optimized"]["keys"][0]["dir"], "desc");
+}
+
+#[test]
+fn explain_physical_json_adds_stats_only_when_analyzed() {
+    let args = ["--explain-format", "json", "queries/q3_sum_and_count.json"];
+
+    let planned = explain_json(&[&["--explain-physical"][..], &args].concat());
+    let agg = node_chain(&planned)[2];
+    assert_eq!(agg["node"], "HashAggregate");
+    assert_eq!(agg["group_keys"], json!(["user_id"]));
+    assert_eq!(agg["memory_limit"], json!(null));
+    assert!(agg.get("stats").is_none(), "{agg}");
+
+    // Settings keep their JSON types rather than display text.
+    let scan = node_chain(&planned)[3];
+    assert_eq!(scan["path"], "data/transactions.csv");
+    assert_eq!(scan["strict"], false);
+    assert_eq!(
+        scan["columns"][0],
+        json!({"name": "user_id", "type": "utf8"})
+    );
+
+    let analyzed = explain_json(&[&["--explain-analyze"][..], &args].concat());
+    let agg = node_chain(&analyzed)[2];
+    assert_eq!(agg["stats"]["rows_in"], 6);
+    assert_eq!(agg["metrics"]["groups"], 4);
+}
+
+#[test]
+fn explain_dot_draws_one_box_per_node() {
+    let dot = run_ok(&[
+        "--explain",
+        "--explain-format",
+        "dot",
+        "queries/q16_join_spend_by_tier.json",
+    ]);
+
+    assert!(dot.starts_with("digraph plan {"), "{dot}");
+    assert!(dot.trim_end().ends_with('}'), "{dot}");
+    let nodes = dot.lines().filter(|l| l.contains("[label=")).count();
+    let edges = dot.lines().filter(|l| l.contains(" -> ")).count();
+    assert_eq!((nodes, edges), (6, 5), "{dot}");
+    assert!(
+        dot.contains(r"Join\ltype=inner\lon=[t.user_id = u.user_id]\l"),
+        "{dot}"
+    );
+
+    let both = run_ok(&[
+        "--explain-both",
+        "--explain-format",
+        "dot",
+        "queries/q6_top_users.json",
+    ]);
+    assert!(both.contains("label=\"original\""), "{both}");
+    assert!(both.contains("label=\"optimized\""), "{both}");
+}
+
+#[test]
+fn rejects_unknown_explain_formats() {
+    let (_, err, code) = run_bin(&[
+        "--explain",
+        "--explain-format",
+        "yaml",
+        "queries/q1_sum_by_user.json",
+    ]);
+    assert_ne!(code, 0);
+    assert!(err.contains("possible values: text, json, dot"), "{err}");
+}
+
+#[test]
+fn scans_read_only_referenced_columns() {
+    let out = run_ok(&["--explain", "queries/q4_filtered_grouped.json"]);
+    assert!(
+        out.contains("Scan(path=\"data/transactions.csv\", columns=[user_id, amount], filter="),
+        "{out}"
+    );
+
+    // Every column is needed, so none are listed.
+    let out = run_ok(&[
+        "--explain",
+        "--sql",
+        "SELECT user_id, name, tier FROM 'data/users.csv'",
+    ]);
+    assert!(out.contains("Scan(path=\"data/users.csv\")"), "{out}");
+
+    // count(*) reads no column at all but still counts every record.
+    let sql = "SELECT count(*) FROM 'data/transactions.csv'";
+    let out = run_ok(&["--explain", "--sql", sql]);
+    assert!(out.contains("columns=[]"), "{out}");
+    let rows = run_json(&["--sql", sql]);
+    assert_eq!(rows, vec![json!({"count(*)": 6})]);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::{run_all, run_bin, run_json};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn arithmetic_select_items_with_aliases() {
+    let rows = run_json(&["queries/q9_select_expressions.json"]);
+
+    assert_eq!(
+        rows,
+        vec![
+            json!({"user_id": "u1", "doubled": 240, "adjusted": 27.5, "amount % 7": 1}),
+            json!({"user_id": "u4", "doubled": 400, "adjusted": 47.5, "amount % 7": 4}),
+        ]
+    );
+}
+
+#[test]
+fn expressions_inside_and_around_aggregates() {
+    let rows = run_json(&["queries/q10_aggregate_expressions.json"]);
+
+    assert_eq!(rows[0]["user_id"], "u1");
+    assert_eq!(rows[0]["double_total"], 260.0);
+    assert_eq!(rows[0]["mean"], 65.0);
+    assert_eq!(rows[0]["count(*)"], 2);
+}
+
+#[test]
+fn table_headers_use_aliases() {
+    let out = run_all(&["queries/q9_select_expressions.json"]);
+    let header = out.lines().next().unwrap();
+
+    assert!(header.contains("doubled"), "{out}");
+    assert!(header.contains("adjusted"), "{out}");
+    assert!(header.contains("amount % 7"), "{out}");
+}
+
+#[test]
+fn explain_shows_projection_expressions() {
+    let all = run_all(&["--explain", "queries/q10_aggregate_expressions.json"]);
+
+    assert!(
+        all.contains(r#""\"sum(amount * 2)\" AS double_total""#),
+        "{all}"
+    );
+    assert!(
+        all.contains(r#""\"sum(amount)\" / \"count(*)\" AS mean""#),
+        "{all}"
+    );
+}
+
+#[test]
+fn bad_select_expression_reports_position() {
+    let (_out, err, code) = run_bin(&["queries/bad/select_syntax.json"]);
+
+    assert_ne!(code, 0);
+    assert!(err.contains("line 1, column 10"), "{err}");
+}
+
+#[test]
+fn integer_overflow_yields_null() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("overflow.csv");
+    fs::write(&path, "id,big\n1,9223372036854775807\n2,5\n").unwrap();
+    let sql = format!(
+        "SELECT id, big + 1 AS bumped, -(big + 2) AS neg FROM '{}'",
+        path.display()
+    );
+
+    let rows = run_json(&["--sql", &sql]);
+    assert_eq!(
+        rows,
+        vec![
+            json!({"id": 1, "bumped": null, "neg": null}),
+            json!({"id": 2, "bumped": 6, "neg": -7}),
+        ]
+    );
+}
+
+#[test]
+fn overflow_result_type_does_not_depend_on_partitions() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("overflow_late.csv");
+    let mut csv = String::from("id,big\n");
+    for id in 0..5000 {
+        let big = if id == 4500 { i64::MAX } else { id };
+        csv.push_str(&format!("{id},{big}\n"));
+    }
+    fs::write(&path, csv).unwrap();
+    let sql = format!(
+        "SELECT id, big + 1 AS b FROM '{}' WHERE id < 3 OR id = 4500",
+        path.display()
+    );
+
+    let expected = run_all(&["--threads", "1", "--sql", &sql]);
+    assert_eq!(run_all(&["--threads", "4", "--sql", &sql]), expected);
+    let rows = run_json(&["--threads", "4", "--sql", &sql]);
+    let b: Vec<_> = rows.iter().map(|r| r["b"].clone()).collect();
+    assert_eq!(b, vec![json!(1), json!(2), json!(3), json!(null)]);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::{run_all, run_bin, run_json};
+use serde_json::json;
+
+#[test]
+fn having_filters_groups_on_aliases_keys_and_hidden_aggregates() {
+    let rows = run_json(&["queries/q14_having.json"]);
+
+    assert_eq!(
+        rows,
+        vec![
+            json!({"user_id": "u1", "total": 130.0}),
+            json!({"user_id": "u4", "total": 200.0}),
+        ]
+    );
+}
+
+#[test]
+fn having_is_planned_above_aggregate() {
+    let all = run_all(&["--explain", "queries/q14_having.json"]);
+
+    let filter = all
+        .find("Filter(sum(amount) > 150")
+        .expect("missing Filter");
+    let agg = all.find("Aggregate(").expect("missing Aggregate");
+    assert!(filter < agg, "{all}");
+}
+
+#[test]
+fn having_rejects_ungrouped_raw_columns() {
+    let (_out, err, code) = run_bin(&["queries/bad/having_raw_column.json"]);
+
+    assert_ne!(code, 0);
+    assert!(
+        err.contains("`city`, which is neither grouped nor aggregated"),
+        "{err}"
+    );
+}
+
+#[test]
+fn having_on_group_keys_runs_before_aggregation() {
+    let sql = |clauses: &str| {
+        format!("SELECT user_id, count(*), sum(amount) FROM 'data/transactions.csv' {clauses}")
+    };
+    let having = sql("GROUP BY user_id HAVING user_id != 'u4' AND count(*) > 1");
+
+    let all = run_all(&["--explain", "--sql", &having]);
+    assert!(all.contains("filter=user_id != \"u4\")"), "{all}");
+    let filter = all.find("Filter(count(*) > 1)").expect("missing Filter");
+    let agg = all.find("Aggregate(").expect("missing Aggregate");
+    assert!(filter < agg, "{all}");
+
+    // Same groups as filtering the rows up front.
+    let rows = run_json(&["--sql", &having]);
+    assert_eq!(
+        rows,
+        run_json(&[
+            "--sql",
+            &sql("WHERE user_id != 'u4' GROUP BY user_id HAVING count(*) > 1"),
+        ])
+    );
+    assert_eq!(
+        rows,
+        vec![
+            json!({"user_id": "u1", "count(*)": 2, "sum(amount)": 130.0}),
+            json!({"user_id": "u2", "count(*)": 2, "sum(amount)": 95.0}),
+        ]
+    );
+}
+
+#[test]
+fn having_mixing_keys_and_aggregates_stays_above_aggregate() {
+    // The key test sits under an OR with an aggregate, so it cannot move.
+    let all = run_all(&["--explain", "queries/q14_having.json"]);
+    assert!(
+        all.contains("Scan(path=\"data/transactions.csv\", columns="),
+        "{all}"
+    );
+    assert!(!all.contains("filter="), "{all}");
+}
+
+#[test]
+fn having_compares_aggregates_with_each_other() {
+    let rows = run_json(&[
+        "--sql",
+        "SELECT city, sum(amount) AS t FROM 'data/transactions.csv' \
+         GROUP BY city HAVING t > count(*) * 50",
+    ]);
+    assert_eq!(
+        rows,
+        vec![
+            json!({"city": "SF", "t": 330.0}),
+            json!({"city": "SJ", "t": 55.0}),
+        ]
+    );
+
+    let (_out, err, code) = run_bin(&[
+        "--sql",
+        "SELECT city, count(*) FROM 'data/transactions.csv' \
+         GROUP BY city HAVING amount > count(*)",
+    ]);
+    assert_ne!(code, 0);
+    assert!(
+        err.contains("`amount`, which is neither grouped nor aggregated"),
+        "{err}"
+    );
+}
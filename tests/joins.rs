@@ -0,0 +1,110 @@
+mod common;
+
+use common::{run_all, run_bin, run_json};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn inner_join_then_aggregate() {
+    let rows = run_json(&["queries/q16_join_spend_by_tier.json"]);
+
+    assert_eq!(
+        rows,
+        vec![
+            json!({"u.tier": "gold", "count(*)": 3, "sum(t.amount)": 185.0}),
+            json!({"u.tier": "silver", "count(*)": 2, "sum(t.amount)": 95.0}),
+        ]
+    );
+}
+
+#[test]
+fn full_outer_join_keeps_both_unmatched_sides() {
+    let rows = run_json(&["queries/q17_full_outer_join.sql"]);
+
+    assert_eq!(rows.len(), 7);
+    assert_eq!(
+        rows[0],
+        json!({"t.user_id": null, "u.user_id": "u5", "u.name": "Eve", "t.amount": null})
+    );
+    assert_eq!(
+        rows[6],
+        json!({"t.user_id": "u4", "u.user_id": null, "u.name": null, "t.amount": 200})
+    );
+}
+
+#[test]
+fn left_and_right_joins_preserve_their_side() {
+    let sql = |kind: &str| {
+        format!(
+            "SELECT transactions.user_id, users.name FROM 'data/transactions.csv' \
+             {kind} JOIN 'data/users.csv' ON transactions.user_id = users.user_id \
+             ORDER BY users.name NULLS FIRST"
+        )
+    };
+
+    let left = run_json(&["--sql", &sql("LEFT")]);
+    assert_eq!(left.len(), 6);
+    assert_eq!(
+        left[0],
+        json!({"transactions.user_id": "u4", "users.name": null})
+    );
+
+    let right = run_json(&["--sql", &sql("RIGHT")]);
+    assert_eq!(right.len(), 6);
+    assert_eq!(
+        right[5],
+        json!({"transactions.user_id": null, "users.name": "Eve"})
+    );
+}
+
+#[test]
+fn explain_shows_join_with_qualified_scans() {
+    let all = run_all(&["--explain", "queries/q16_join_spend_by_tier.json"]);
+
+    assert!(
+        all.contains("Join(type=inner, on=[t.user_id = u.user_id])"),
+        "{all}"
+    );
+    assert!(
+        all.contains("Scan(path=\"data/users.csv\", as=u, columns=[user_id, tier])"),
+        "{all}"
+    );
+}
+
+#[test]
+fn join_keys_must_reference_both_sides() {
+    let (_out, err, code) = run_bin(&[
+        "--sql",
+        "SELECT t.user_id FROM 'data/transactions.csv' t \
+         JOIN 'data/users.csv' u ON t.user_id = t.city",
+    ]);
+
+    assert_ne!(code, 0);
+    assert!(err.contains("must compare a column of `u`"), "{err}");
+}
+
+#[test]
+fn large_integer_keys_match_exactly() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("join_keys");
+    fs::create_dir_all(&dir).unwrap();
+    // 2^53 and 2^53 + 1 are the same number as floats.
+    let (a, b, c) = (dir.join("a.csv"), dir.join("b.csv"), dir.join("c.csv"));
+    fs::write(&a, "id,name\n9007199254740993,x\n1,one\n").unwrap();
+    fs::write(&b, "id,tag\n9007199254740992,y\n1,first\n").unwrap();
+    fs::write(&c, "id,score\n1.0,0.5\n2.5,0.7\n").unwrap();
+    let (a, b, c) = (a.display(), b.display(), c.display());
+
+    let rows = run_json(&[
+        "--sql",
+        &format!("SELECT a.name, b.tag FROM '{a}' a JOIN '{b}' b ON a.id = b.id"),
+    ]);
+    assert_eq!(rows, vec![json!({"a.name": "one", "b.tag": "first"})]);
+
+    // Against a float column, integers still match equal floats.
+    let rows = run_json(&[
+        "--sql",
+        &format!("SELECT a.name, c.score FROM '{a}' a JOIN '{c}' c ON a.id = c.id"),
+    ]);
+    assert_eq!(rows, vec![json!({"a.name": "one", "c.score": 0.5})]);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::{run_bin, run_json};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+// A large CSV whose second record has too few fields, so any reader that
+// gets past the first record fails.
+fn write_trap_csv(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let mut out = String::from("id,name,score\n0,first,10\n1,broken\n");
+    for i in 2..200_000 {
+        out.push_str(&format!("{i},row{i},{}\n", i % 100));
+    }
+    fs::write(&path, out).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn limit_one_reads_only_the_first_record() {
+    let csv = write_trap_csv("limit_trap.csv");
+    let sql = format!("SELECT name, score * 2 AS doubled FROM '{csv}'");
+
+    // One record is sampled for types, so inference stays off the broken one.
+    let rows = run_json(&["--infer-rows", "1", "--sql", &format!("{sql} LIMIT 1")]);
+    assert_eq!(rows, vec![json!({"name": "first", "doubled": 20})]);
+
+    let (_, err, code) = run_bin(&["--infer-rows", "1", "--sql", &sql]);
+    assert_ne!(code, 0, "the full scan should reach the broken record");
+    assert!(err.contains("found record with 2 fields"), "{err}");
+}
+
+#[test]
+fn limit_moves_through_project_into_the_scan() {
+    let (out, err, code) = run_bin(&[
+        "--explain",
+        "--sql",
+        "SELECT user_id, amount * 2 AS doubled FROM 'data/transactions.csv' \
+         WHERE city = 'SF' LIMIT 2",
+    ]);
+    assert_eq!(code, 0, "{err}");
+    assert!(!out.contains("Limit("), "{out}");
+    assert!(out.contains("filter=city == \"SF\", limit=2)"), "{out}");
+
+    // The limit counts records that pass the filter.
+    let rows = run_json(&[
+        "--sql",
+        "SELECT user_id, amount FROM 'data/transactions.csv' WHERE city = 'SF' LIMIT 2",
+    ]);
+    assert_eq!(
+        rows,
+        vec![
+            json!({"user_id": "u1", "amount": 10}),
+            json!({"user_id": "u1", "amount": 120}),
+        ]
+    );
+}
+
+#[test]
+fn limit_stays_above_aggregates() {
+    let (out, _, code) = run_bin(&[
+        "--explain",
+        "--sql",
+        "SELECT user_id, count(*) FROM 'data/transactions.csv' GROUP BY user_id LIMIT 1",
+    ]);
+    assert_eq!(code, 0);
+    assert!(!out.contains("limit="), "{out}");
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::{run_all, run_json};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn order_by_desc_runs_before_limit() {
+    let rows = run_json(&["queries/q6_top_users.json"]);
+
+    let users: Vec<&str> = rows
+        .iter()
+        .map(|r| r["user_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(users, vec!["u4", "u1"]);
+}
+
+#[test]
+fn multiple_keys_with_null_placement() {
+    let rows = run_json(&["queries/q7_order_nulls.json"]);
+
+    let items: Vec<&str> = rows.iter().map(|r| r["item"].as_str().unwrap()).collect();
+    assert_eq!(items, vec!["b", "e", "d", "c", "a"]);
+}
+
+#[test]
+fn explain_shows_sort_below_limit() {
+    let all = run_all(&["--explain-both", "queries/q6_top_users.json"]);
+
+    let limit = all.find("Limit(n=2)").expect("missing Limit");
+    let sort = all
+        .find("Sort(keys=[sum(amount) DESC NULLS FIRST])")
+        .expect("missing Sort");
+    assert!(limit < sort, "{all}");
+}
+
+#[test]
+fn grouped_results_default_to_first_key_order() {
+    let rows = run_json(&["queries/q2_group_sum.json"]);
+
+    let users: Vec<&str> = rows
+        .iter()
+        .map(|r| r["user_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(users, vec!["u1", "u2", "u3", "u4"]);
+}
+
+#[test]
+fn limit_over_sort_becomes_topk() {
+    let all = run_all(&["--explain", "queries/q6_top_users.json"]);
+    assert!(
+        all.contains("TopK(n=2, keys=[sum(amount) DESC NULLS FIRST])"),
+        "{all}"
+    );
+    assert!(!all.contains("Limit("), "{all}");
+}
+
+#[test]
+fn topk_keeps_ties_in_input_order() {
+    // Rows tie on category; TopK must pick the same rows as a stable sort.
+    let rows = run_json(&["queries/q8_topk_ties.json"]);
+
+    let items: Vec<&str> = rows.iter().map(|r| r["item"].as_str().unwrap()).collect();
+    assert_eq!(items, vec!["a", "c"]);
+}
+
+#[test]
+fn grouped_results_sort_by_unselected_first_key() {
+    let rows = run_json(&[
+        "--sql",
+        "SELECT sum(amount) AS total FROM 'data/transactions.csv' GROUP BY user_id",
+    ]);
+
+    let totals: Vec<f64> = rows.iter().map(|r| r["total"].as_f64().unwrap()).collect();
+    assert_eq!(totals, vec![130.0, 95.0, 55.0, 200.0]);
+}
+
+#[test]
+fn order_by_unselected_column() {
+    let rows = run_json(&[
+        "--sql",
+        "SELECT item FROM 'data/ratings.csv' WHERE category = 'x' ORDER BY rating DESC",
+    ]);
+
+    let items: Vec<&str> = rows.iter().map(|r| r["item"].as_str().unwrap()).collect();
+    assert_eq!(items, vec!["d", "a", "c"]);
+}
+
+#[test]
+fn huge_limit_over_small_file() {
+    let sql =
+        "SELECT user_id, amount FROM 'data/transactions.csv' ORDER BY amount LIMIT 100000000000";
+    let query = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("huge_limit.json");
+    let json = json!({
+        "from": "data/transactions.csv",
+        "select": ["user_id", "amount"],
+        "order_by": [{"col": "amount", "dir": "asc"}],
+        "limit": usize::MAX,
+    });
+    fs::write(&query, json.to_string()).unwrap();
+
+    for rows in [
+        run_json(&["--sql", sql]),
+        run_json(&[query.to_str().unwrap()]),
+    ] {
+        let amounts: Vec<i64> = rows.iter().map(|r| r["amount"].as_i64().unwrap()).collect();
+        assert_eq!(amounts, vec![10, 15, 55, 80, 120, 200]);
+    }
+}
+
+#[test]
+fn order_by_computed_alias_and_unselected_column() {
+    let rows = run_json(&[
+        "--sql",
+        "SELECT user_id, amount*2 AS d FROM 'data/transactions.csv' ORDER BY d, city",
+    ]);
+
+    let d: Vec<i64> = rows.iter().map(|r| r["d"].as_i64().unwrap()).collect();
+    assert_eq!(d, vec![20, 30, 110, 160, 240, 400]);
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::{run_bin, run_json};
+use std::fs;
+use std::path::PathBuf;
+
+// Writes a CSV with enough rows to give every thread a few batches. Row
+// `bad_line` (1-based file line), if any, gets a non-numeric amount.
+fn write_csv(name: &str, rows: usize, bad_line: Option<usize>) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let mut out = String::from("id,user_id,amount,price,category\n");
+    for i in 0..rows {
+        let amount = if bad_line == Some(i + 2) {
+            "oops".to_string()
+        } else if i % 97 == 0 {
+            String::new()
+        } else {
+            ((i * 7919) % 500).to_string()
+        };
+        // Fractions like 0.1 make float sums sensitive to addition order.
+        let price = format!("{}.{}", i % 13, i % 10);
+        out.push_str(&format!(
+            "{i},u{},{amount},{price},{}\n",
+            (i * 31) % 1000,
+            ["food", "rent", "fun"][i % 3]
+        ));
+    }
+    fs::write(&path, out).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn with_threads(threads: &str, sql: &str) -> Vec<serde_json::Value> {
+    run_json(&["--threads", threads, "--sql", sql])
+}
+
+#[test]
+fn grouped_results_do_not_depend_on_thread_count() {
+    let csv = write_csv("parallel_groups.csv", 50_000, None);
+    let sql = format!(
+        "SELECT user_id, count(*), count(amount), sum(price), avg(price * 1.1), \
+         min(amount), max(amount), count(DISTINCT category) \
+         FROM '{csv}' WHERE category != 'rent' GROUP BY user_id"
+    );
+
+    let serial = with_threads("1", &sql);
+    assert_eq!(serial.len(), 1000);
+    for threads in ["2", "3", "8"] {
+        assert_eq!(with_threads(threads, &sql), serial, "threads = {threads}");
+    }
+}
+
+#[test]
+fn global_float_sums_are_exact() {
+    let csv = write_csv("parallel_sums.csv", 50_000, None);
+    let sql = format!("SELECT sum(price), avg(price) FROM '{csv}'");
+
+    let serial = with_threads("1", &sql);
+    for threads in ["2", "5", "16"] {
+        assert_eq!(with_threads(threads, &sql), serial, "threads = {threads}");
+    }
+}
+
+#[test]
+fn parallel_scan_keeps_file_order() {
+    let csv = write_csv("parallel_order.csv", 30_000, None);
+    let sql = format!("SELECT id, amount * 2 AS doubled FROM '{csv}' WHERE category = 'fun'");
+
+    let rows = with_threads("4", &sql);
+    assert_eq!(rows, with_threads("1", &sql));
+    assert_eq!(rows.len(), 10_000);
+    assert!(
+        rows.windows(2)
+            .all(|w| w[0]["id"].as_i64() < w[1]["id"].as_i64())
+    );
+
+    let limited = with_threads("4", &format!("{sql} LIMIT 3"));
+    let ids: Vec<_> = limited.iter().map(|r| r["id"].clone()).collect();
+    assert_eq!(ids, vec![2, 5, 8]);
+}
+
+#[test]
+fn quoted_line_breaks_do_not_split_records() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("parallel_multiline.csv");
+    let mut out = String::from("id,note,amount\n");
+    for i in 0..2000 {
+        out.push_str(&format!("{i},\"line one\nline \"\"two\"\"\",{}\n", i % 10));
+    }
+    fs::write(&path, out).unwrap();
+    let sql = format!(
+        "SELECT count(*), count(note), sum(amount) FROM '{}'",
+        path.display()
+    );
+
+    let serial = with_threads("1", &sql);
+    assert_eq!(serial[0]["count(*)"], 2000);
+    assert_eq!(serial[0]["count(note)"], 2000);
+    for threads in ["2", "4", "7"] {
+        assert_eq!(with_threads(threads, &sql), serial, "threads = {threads}");
+    }
+}
+
+#[test]
+fn split_inside_an_unresolvable_quoted_field_is_reported() {
+    // One field spans most of the file with nothing near the split point
+    // to tell it is quoted, so the split is a guess the scan must catch.
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("parallel_long_field.csv");
+    let long = format!("{}\n", "x".repeat(99)).repeat(3000);
+    fs::write(&path, format!("id,note\n1,\"\n{long}\"\n2,short\n")).unwrap();
+    let sql = format!("SELECT count(*) FROM '{}'", path.display());
+
+    assert_eq!(with_threads("1", &sql)[0]["count(*)"], 2);
+    let (_, err, code) = run_bin(&["--threads", "2", "--sql", &sql]);
+    assert_ne!(code, 0);
+    assert!(
+        err.contains("inside a quoted field; rerun with --threads 1"),
+        "{err}"
+    );
+}
+
+#[test]
+fn strict_errors_report_file_lines_from_any_range() {
+    let csv = write_csv("parallel_strict.csv", 30_000, Some(25_000));
+    let sql = format!("SELECT count(amount) FROM '{csv}'");
+
+    for threads in ["1", "4"] {
+        let (_, err, code) = run_bin(&[
+            "--strict-schema",
+            "--infer-rows",
+            "100",
+            "--threads",
+            threads,
+            "--sql",
+            &sql,
+        ]);
+        assert_ne!(code, 0);
+        assert!(
+            err.contains(&format!(
+                "{csv}:25000: column `amount` expects int64, got \"oops\""
+            )),
+            "threads = {threads}: {err}"
+        );
+    }
+}
+
+#[test]
+fn more_threads_than_records() {
+    let rows = run_json(&[
+        "--threads",
+        "8",
+        "--sql",
+        "SELECT name FROM 'data/users.csv' ORDER BY name",
+    ]);
+    let names: Vec<_> = rows.iter().map(|r| r["name"].clone()).collect();
+    assert_eq!(names, vec!["Alice", "Bob", "Cara", "Eve"]);
+}
+
+#[test]
+fn zero_threads_is_rejected() {
+    let (_, _, code) = run_bin(&["--threads", "0", "queries/q1_sum_by_user.json"]);
+    assert_ne!(code, 0);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::{run_all, run_json};
+use serde_json::json;
+
+#[test]
+fn or_and_not_predicates_filter_rows() {
+    let rows = run_json(&["queries/q5_or_predicate.json"]);
+
+    let amounts: Vec<i64> = rows.iter().map(|r| r["amount"].as_i64().unwrap()).collect();
+    assert_eq!(amounts, vec![80, 120, 15]);
+}
+
+#[test]
+fn flat_where_list_is_implicit_and() {
+    let all = run_all(&["--explain", "queries/q4_filtered_grouped.json"]);
+    assert!(all.contains("filter=city == \"SF\")"), "{all}");
+
+    let rows = run_json(&["queries/q1_sum_by_user.json"]);
+    assert_eq!(rows.len(), 4);
+}
+
+#[test]
+fn explain_shows_predicate_tree() {
+    let all = run_all(&["--explain", "queries/q5_or_predicate.json"]);
+    assert!(
+        all.contains("filter=city == \"NY\" OR (amount > 100 AND NOT user_id == \"u4\"))"),
+        "{all}"
+    );
+}
+
+#[test]
+fn scan_filters_treat_nulls_like_parsed_rows() {
+    // With `population` typed int64, the empty and "n/a" cells are null, and
+    // null != 35000 holds just as it does after parsing.
+    let rows = run_json(&[
+        "--infer-rows",
+        "1",
+        "--sql",
+        "SELECT city FROM 'data/zips.csv' WHERE population != 35000 AND NOT active = true",
+    ]);
+
+    assert_eq!(
+        rows,
+        vec![json!({"city": "San Francisco"}), json!({"city": "Chicago"})]
+    );
+}
+
+#[test]
+fn join_filters_move_into_scans_that_keep_their_rows() {
+    let sql = |kind: &str| {
+        format!(
+            "SELECT t.user_id, u.name FROM 'data/transactions.csv' t \
+             {kind} JOIN 'data/users.csv' u ON t.user_id = u.user_id \
+             WHERE u.name != 'Bob' AND t.amount >= 20"
+        )
+    };
+
+    let inner = run_all(&["--explain", "--sql", &sql("INNER")]);
+    assert!(
+        inner.contains("as=t, columns=[user_id], filter=t.amount >= 20)"),
+        "{inner}"
+    );
+    assert!(inner.contains("filter=u.name != \"Bob\")"), "{inner}");
+    assert!(!inner.contains("Filter("), "{inner}");
+
+    // Unmatched left rows have a null `u.name`, so that test stays above
+    // the join.
+    let left = run_all(&["--explain", "--sql", &sql("LEFT")]);
+    assert!(left.contains("Filter(u.name != \"Bob\")"), "{left}");
+    assert!(left.contains("filter=t.amount >= 20)"), "{left}");
+
+    let rows = run_json(&["--sql", &sql("LEFT")]);
+    assert_eq!(
+        rows,
+        vec![
+            json!({"t.user_id": "u1", "u.name": "Alice"}),
+            json!({"t.user_id": "u3", "u.name": "Cara"}),
+            json!({"t.user_id": "u4", "u.name": null}),
+        ]
+    );
+}
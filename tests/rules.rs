@@ -0,0 +1,104 @@
+mod common;
+
+use common::{run_all, run_bin, run_json};
+use serde_json::Value;
+
+const TOPK: &str = "SELECT user_id, amount FROM 'data/transactions.csv' \
+                    WHERE amount > 10 AND amount > 10 ORDER BY amount LIMIT 2";
+
+#[test]
+fn explain_rules_prints_the_plan_after_each_change() {
+    let all = run_all(&["--explain-rules", "--sql", TOPK]);
+
+    let headers: Vec<&str> = all.lines().filter(|l| l.starts_with("---")).collect();
+    assert_eq!(
+        headers,
+        vec![
+            "--- ORIGINAL PLAN ---",
+            "--- AFTER pushdown_filter (pass 1) ---",
+            "--- AFTER simplify (pass 1) ---",
+            "--- AFTER prune_columns (pass 1) ---",
+            "--- AFTER fuse_topk (pass 1) ---",
+        ],
+        "{all}"
+    );
+    let last = all.rsplit("---\n").next().unwrap();
+    assert!(last.starts_with("TopK(n=2"), "{all}");
+    assert!(last.contains("filter=amount > 10)"), "{all}");
+}
+
+#[test]
+fn rules_rerun_until_the_plan_stops_changing() {
+    // Deduplicating the OR leaves a group-key conjunct that only the next
+    // pass of predicate pushdown can move into the scan.
+    let sql = "SELECT user_id, count(*) FROM 'data/transactions.csv' GROUP BY user_id \
+               HAVING (user_id = 'u1' AND count(*) > 1) OR (user_id = 'u1' AND count(*) > 1)";
+    let all = run_all(&["--explain-rules", "--sql", sql]);
+    assert!(
+        all.contains("--- AFTER pushdown_filter (pass 2) ---"),
+        "{all}"
+    );
+
+    let explained = run_all(&["--explain", "--sql", sql]);
+    assert!(explained.contains("Filter(count(*) > 1)"), "{explained}");
+    assert!(
+        explained.contains("filter=user_id == \"u1\")"),
+        "{explained}"
+    );
+}
+
+#[test]
+fn explain_rules_json_lists_steps() {
+    let (out, err, code) = run_bin(&["--explain-rules", "--explain-format", "json", "--sql", TOPK]);
+    assert_eq!(code, 0, "{err}");
+
+    let steps: Vec<Value> = serde_json::from_str(&out).unwrap();
+    let names: Vec<&str> = steps.iter().map(|s| s["step"].as_str().unwrap()).collect();
+    assert_eq!(names[0], "original");
+    assert_eq!(names.last(), Some(&"fuse_topk (pass 1)"));
+    assert_eq!(steps.last().unwrap()["plan"]["node"], "TopK");
+}
+
+#[test]
+fn disabled_rules_are_skipped() {
+    let all = run_all(&[
+        "--explain",
+        "--disable-rule",
+        "pushdown_filter",
+        "--disable-rule",
+        "fuse_topk",
+        "--sql",
+        TOPK,
+    ]);
+    assert!(all.contains("Filter(amount > 10)"), "{all}");
+    assert!(all.contains("Sort("), "{all}");
+    assert!(!all.contains("TopK("), "{all}");
+
+    // Naming a rule twice is the same as naming it once.
+    let twice = run_all(&[
+        "--explain",
+        "--disable-rule",
+        "fuse_topk",
+        "--disable-rule",
+        "fuse_topk",
+        "--sql",
+        TOPK,
+    ]);
+    assert!(!twice.contains("TopK("), "{twice}");
+
+    // Results do not depend on which rules ran.
+    assert_eq!(
+        run_json(&["--disable-rule", "pushdown_filter", "--sql", TOPK]),
+        run_json(&["--sql", TOPK])
+    );
+}
+
+#[test]
+fn unknown_rules_are_rejected() {
+    let (_, err, code) = run_bin(&["--disable-rule", "pushdown_everything", "--sql", TOPK]);
+    assert_ne!(code, 0);
+    assert!(
+        err.contains("unknown optimizer rule `pushdown_everything`; rules are pushdown_filter"),
+        "{err}"
+    );
+}
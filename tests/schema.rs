@@ -0,0 +1,117 @@
+mod common;
+
+use common::{run_bin, run_json};
+use serde_json::json;
+
+#[test]
+fn leading_zeros_keep_a_column_textual() {
+    let rows = run_json(&[
+        "--sql",
+        "SELECT zip, city FROM 'data/zips.csv' WHERE zip = '02134'",
+    ]);
+
+    assert_eq!(rows, vec![json!({"zip": "02134", "city": "Boston"})]);
+}
+
+#[test]
+fn explicit_schema_reads_bad_cells_as_null() {
+    let rows = run_json(&["queries/q18_typed_schema.json"]);
+
+    assert_eq!(
+        rows,
+        vec![
+            json!({"zip": "02134", "city": "Boston", "population": 35000}),
+            json!({"zip": "10001", "city": "New York", "population": 21102}),
+        ]
+    );
+}
+
+#[test]
+fn strict_schema_rejects_bad_cells() {
+    // The WHERE clause keeps the Chicago record, whose population is "n/a".
+    let (out, err, code) = run_bin(&[
+        "--strict-schema",
+        "--infer-rows",
+        "1",
+        "--sql",
+        "SELECT zip, population FROM 'data/zips.csv' WHERE active = false",
+    ]);
+
+    assert_ne!(code, 0, "expected failure.\nSTDOUT:\n{out}");
+    assert!(
+        err.contains("data/zips.csv:5: column `population` expects int64, got \"n/a\""),
+        "{err}"
+    );
+
+    // Here the scan's filter rejects that record before parsing it.
+    let (_, err, code) = run_bin(&["--strict-schema", "queries/q18_typed_schema.json"]);
+    assert_eq!(code, 0, "{err}");
+}
+
+#[test]
+fn strict_schema_checks_filtered_columns() {
+    let (_, err, code) = run_bin(&[
+        "--strict-schema",
+        "--infer-rows",
+        "1",
+        "--sql",
+        "SELECT city FROM 'data/zips.csv' WHERE population > 30000",
+    ]);
+
+    assert_ne!(code, 0);
+    assert!(
+        err.contains("data/zips.csv:5: column `population` expects int64, got \"n/a\""),
+        "{err}"
+    );
+}
+
+#[test]
+fn inference_only_samples_the_first_rows() {
+    // The first record types `population` as int64, so later text reads as null.
+    let sql = "SELECT city, population FROM 'data/zips.csv' WHERE city = 'Chicago'";
+
+    let rows = run_json(&["--infer-rows", "1", "--sql", sql]);
+    assert_eq!(rows, vec![json!({"city": "Chicago", "population": null})]);
+
+    let rows = run_json(&["--sql", sql]);
+    assert_eq!(rows, vec![json!({"city": "Chicago", "population": "n/a"})]);
+
+    let rows = run_json(&[
+        "--infer-rows",
+        "3",
+        "--sql",
+        "SELECT sum(population), count(population) FROM 'data/zips.csv'",
+    ]);
+    assert_eq!(
+        rows,
+        vec![json!({"sum(population)": 56102.0, "count(population)": 2})]
+    );
+}
+
+#[test]
+fn schema_must_name_existing_columns() {
+    let (_, err, code) = run_bin(&["--format", "json", "queries/bad/schema_unknown_column.json"]);
+
+    assert_ne!(code, 0);
+    assert!(
+        err.contains("schema names column `pop`, which is not in data/zips.csv"),
+        "{err}"
+    );
+}
+
+#[test]
+fn strict_schema_skips_unread_columns() {
+    // `population` has a bad cell, but the query never reads it.
+    let rows = run_json(&[
+        "--strict-schema",
+        "--infer-rows",
+        "1",
+        "--sql",
+        "SELECT city FROM 'data/zips.csv' WHERE active = false",
+    ]);
+
+    assert_eq!(
+        rows,
+        vec![json!({"city": "San Francisco"}), json!({"city": "Chicago"})]
+    );
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use common::{run_all, run_json};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+fn explain_sql(sql: &str) -> String {
+    run_all(&["--explain", "--sql", sql])
+}
+
+#[test]
+fn contradictions_replace_the_scan_with_empty() {
+    let sql = "SELECT count(*), sum(amount) FROM 'data/transactions.csv' \
+               WHERE amount > 10 AND amount < 5";
+    let all = explain_sql(sql);
+    assert!(all.contains("Empty(columns=[amount])"), "{all}");
+    assert!(!all.contains("Scan("), "{all}");
+
+    // A global aggregate over no rows still yields its one row.
+    let rows = run_json(&["--sql", sql]);
+    assert_eq!(rows, vec![json!({"count(*)": 0, "sum(amount)": null})]);
+
+    let rows = run_json(&[
+        "--sql",
+        "SELECT user_id FROM 'data/transactions.csv' WHERE city = 'SF' AND city = 'NY'",
+    ]);
+    assert!(rows.is_empty(), "{rows:?}");
+}
+
+#[test]
+fn repeated_and_tautological_conditions_are_dropped() {
+    let all = explain_sql(
+        "SELECT user_id FROM 'data/transactions.csv' \
+         WHERE amount > 10 AND amount > 10 AND (city = 'SF' OR NOT city = 'SF')",
+    );
+    assert!(all.contains("filter=amount > 10)"), "{all}");
+
+    let all = explain_sql("SELECT user_id FROM 'data/transactions.csv' WHERE NOT NOT city = 'SF'");
+    assert!(all.contains("filter=city == \"SF\")"), "{all}");
+}
+
+#[test]
+fn constant_expressions_are_folded() {
+    let sql = "SELECT user_id, amount FROM 'data/transactions.csv' WHERE amount >= 2 * 50 + 20";
+    let all = explain_sql(sql);
+    assert!(all.contains("filter=amount >= 120)"), "{all}");
+
+    let rows = run_json(&["--sql", sql]);
+    assert_eq!(
+        rows,
+        vec![
+            json!({"user_id": "u1", "amount": 120}),
+            json!({"user_id": "u4", "amount": 200}),
+        ]
+    );
+}
+
+#[test]
+fn folding_is_left_to_the_simplify_rule() {
+    let sql = "SELECT user_id, amount FROM 'data/transactions.csv' WHERE amount > 5 + 95";
+    let all = run_all(&["--explain-both", "--sql", sql]);
+    assert!(all.contains("Filter(amount > 5 + 95)"), "{all}");
+    assert!(all.contains("filter=amount > 100)"), "{all}");
+
+    // Without the rule the comparison is evaluated per row, to the same rows.
+    let unfolded = run_all(&["--explain", "--disable-rule", "simplify", "--sql", sql]);
+    assert!(unfolded.contains("Filter(amount > 5 + 95)"), "{unfolded}");
+    let expected = vec![
+        json!({"user_id": "u1", "amount": 120}),
+        json!({"user_id": "u4", "amount": 200}),
+    ];
+    assert_eq!(run_json(&["--sql", sql]), expected);
+    assert_eq!(
+        run_json(&["--disable-rule", "simplify", "--sql", sql]),
+        expected
+    );
+}
+
+#[test]
+fn comparing_a_column_with_itself_is_decided_statically() {
+    let all = explain_sql(
+        "SELECT user_id FROM 'data/transactions.csv' WHERE amount = amount AND city <= city",
+    );
+    assert!(!all.contains("Filter("), "{all}");
+    assert!(!all.contains("filter="), "{all}");
+    let rows = run_json(&[
+        "--sql",
+        "SELECT user_id FROM 'data/transactions.csv' WHERE amount = amount",
+    ]);
+    assert_eq!(rows.len(), 6);
+
+    let sql = "SELECT user_id FROM 'data/transactions.csv' WHERE amount != amount";
+    let all = explain_sql(sql);
+    assert!(all.contains("Empty(columns=[user_id])"), "{all}");
+    assert!(run_json(&["--sql", sql]).is_empty());
+}
+
+#[test]
+fn comparisons_between_expressions_filter_above_the_scan() {
+    let query = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("expr_comparison.json");
+    let json = json!({
+        "from": "data/transactions.csv",
+        "select": ["user_id", "amount"],
+        "where": {"left": "amount * 2", "op": ">", "right": "amount + 50"},
+    });
+    fs::write(&query, json.to_string()).unwrap();
+    let query = query.to_str().unwrap();
+
+    let all = run_all(&["--explain", query]);
+    assert!(all.contains("Filter(amount * 2 > amount + 50)"), "{all}");
+    assert!(!all.contains("filter="), "{all}");
+
+    let amounts: Vec<i64> = run_json(&[query])
+        .iter()
+        .map(|r| r["amount"].as_i64().unwrap())
+        .collect();
+    assert_eq!(amounts, vec![80, 120, 55, 200]);
+
+    let rows = run_json(&[
+        "--sql",
+        "SELECT user_id, amount FROM 'data/transactions.csv' WHERE amount + 50 < amount * 2",
+    ]);
+    assert_eq!(rows.len(), 4);
+}
+
+#[test]
+fn stacked_filters_merge_into_one() {
+    // `having` on a group key joins the `where` condition in the scan.
+    let all = run_all(&[
+        "--explain",
+        "--sql",
+        "SELECT user_id, count(*) FROM 'data/transactions.csv' \
+         WHERE amount > 10 GROUP BY user_id HAVING user_id != 'u4'",
+    ]);
+    assert!(
+        all.contains("filter=amount > 10 AND user_id != \"u4\")"),
+        "{all}"
+    );
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use common::{run_bin, run_json};
+use std::fs;
+use std::path::PathBuf;
+
+// Many more sessions than fit in a small memory limit, visited in a
+// scattered order so every spill run holds a mix of them.
+fn write_sessions(name: &str, rows: usize) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let mut out = String::from("session,user,amount,tag\n");
+    for i in 0..rows {
+        let amount = if i % 41 == 0 {
+            String::new()
+        } else {
+            format!("{}.{}", i % 113, i % 10)
+        };
+        out.push_str(&format!(
+            "s{},u{},{amount},{}\n",
+            (i * 7919) % 5000,
+            i % 50,
+            ["a", "b", "c"][i % 3]
+        ));
+    }
+    fs::write(&path, out).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn session_query(csv: &str) -> String {
+    format!(
+        "SELECT session, count(*), sum(amount), avg(amount), min(tag), max(amount), \
+         count(DISTINCT user) FROM '{csv}' GROUP BY session"
+    )
+}
+
+#[test]
+fn spilled_aggregation_matches_in_memory() {
+    let csv = write_sessions("spill_groups.csv", 40_000);
+    let sql = session_query(&csv);
+
+    let in_memory = run_json(&["--sql", &sql]);
+    assert_eq!(in_memory.len(), 5000);
+    assert_eq!(
+        run_json(&["--memory-limit", "64KB", "--sql", &sql]),
+        in_memory
+    );
+    assert_eq!(
+        run_json(&["--memory-limit", "64KB", "--threads", "3", "--sql", &sql]),
+        in_memory
+    );
+}
+
+#[test]
+fn spilled_groups_keep_first_seen_order_among_ties() {
+    // Groups tie on the first key, which is all the default order sorts by,
+    // so the row order shows whether first-seen order survived the spill.
+    let csv = write_sessions("spill_ties.csv", 40_000);
+    let sql = format!("SELECT user, session, count(*) FROM '{csv}' GROUP BY user, session");
+    let in_memory = run_json(&["--sql", &sql]);
+    assert_eq!(in_memory.len(), 5000);
+    for threads in ["1", "3"] {
+        let spilled = run_json(&[
+            "--memory-limit",
+            "64KB",
+            "--threads",
+            threads,
+            "--sql",
+            &sql,
+        ]);
+        assert_eq!(spilled, in_memory, "threads = {threads}");
+    }
+
+    let sql = "SELECT user_id, category, count(*) FROM 'data/transactions.csv' \
+               GROUP BY user_id, category";
+    let in_memory = run_json(&["--sql", sql]);
+    assert_eq!(run_json(&["--memory-limit", "1", "--sql", sql]), in_memory);
+}
+
+#[test]
+fn stats_report_spill_counts() {
+    let csv = write_sessions("spill_stats.csv", 20_000);
+    let sql = session_query(&csv);
+
+    let (_, err, code) = run_bin(&["--stats", "--memory-limit", "64KB", "--sql", &sql]);
+    assert_eq!(code, 0, "{err}");
+    let agg = err
+        .lines()
+        .find(|l| l.trim_start().starts_with("HashAggregate("))
+        .unwrap_or_else(|| panic!("no aggregate in stats:\n{err}"));
+    assert!(agg.contains("groups=5000"), "{agg}");
+    assert!(agg.contains("spill_runs="), "{agg}");
+    assert!(agg.contains("spill_bytes="), "{agg}");
+
+    let (_, err, code) = run_bin(&["--stats", "--sql", &sql]);
+    assert_eq!(code, 0, "{err}");
+    assert!(err.contains("HashAggregate(groups=5000)"), "{err}");
+}
+
+#[test]
+fn aggregate_memory_stays_near_the_limit() {
+    let csv = write_sessions("spill_peak.csv", 20_000);
+    let sql = session_query(&csv);
+
+    for threads in ["1", "3"] {
+        let (out, err, code) = run_bin(&[
+            "--explain-analyze",
+            "--memory-limit",
+            "64KB",
+            "--threads",
+            threads,
+            "--sql",
+            &sql,
+        ]);
+        assert_eq!(code, 0, "{err}");
+        let agg = out
+            .lines()
+            .find(|l| l.trim_start().starts_with("HashAggregate("))
+            .unwrap_or_else(|| panic!("no aggregate in plan:\n{out}"));
+        // Merging any one of the 16 partitions back whole would need more
+        // than the limit; those are split again instead.
+        let peak = agg
+            .split("peak_mem=")
+            .nth(1)
+            .and_then(|p| p.strip_suffix("KB]"))
+            .and_then(|p| p.parse::<f64>().ok())
+            .unwrap_or_else(|| panic!("no peak_mem in KB: {agg}"));
+        assert!(peak < 80.0, "threads = {threads}: {agg}");
+    }
+}
+
+#[test]
+fn rejects_malformed_memory_limits() {
+    for bad in ["lots", "512XB", "0MB"] {
+        let (_, err, code) = run_bin(&["--memory-limit", bad, "--sql", "SELECT 1 FROM 'x.csv'"]);
+        assert_ne!(code, 0, "{bad} was accepted");
+        assert!(err.contains("--memory-limit"), "{err}");
+    }
+}
+
+#[test]
+fn external_sort_matches_in_memory() {
+    let csv = write_sessions("spill_sort.csv", 30_000);
+    let sql = format!(
+        "SELECT session, user, amount, tag FROM '{csv}' ORDER BY amount DESC NULLS LAST, tag"
+    );
+
+    let in_memory = run_json(&["--sql", &sql]);
+    assert_eq!(in_memory.len(), 30_000);
+    // 64KB forces one run per batch and merges two runs at a time.
+    for limit in ["64KB", "1MB"] {
+        assert_eq!(
+            run_json(&["--memory-limit", limit, "--sql", &sql]),
+            in_memory,
+            "limit = {limit}"
+        );
+    }
+}
+
+#[test]
+fn stats_report_sort_runs_and_merge_passes() {
+    let csv = write_sessions("spill_sort_stats.csv", 20_000);
+    let sql = format!("SELECT session, amount FROM '{csv}' ORDER BY session");
+
+    let (_, err, code) = run_bin(&["--stats", "--memory-limit", "64KB", "--sql", &sql]);
+    assert_eq!(code, 0, "{err}");
+    let sort = err
+        .lines()
+        .find(|l| l.starts_with("Sort("))
+        .unwrap_or_else(|| panic!("no sort in stats:\n{err}"));
+    // 20,000 rows arrive in 5 batches, each spilled as its own run; a
+    // fan-in of 2 then needs three merge passes.
+    assert!(sort.contains("runs=5"), "{sort}");
+    assert!(sort.contains("max_run_rows=4096"), "{sort}");
+    assert!(sort.contains("merge_passes=3"), "{sort}");
+}
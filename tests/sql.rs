@@ -0,0 +1,65 @@
+mod common;
+
+use common::{run_bin, run_json};
+use serde_json::json;
+
+#[test]
+fn sql_file_runs_full_clause_set() {
+    let rows = run_json(&["queries/q15_sql_report.sql"]);
+
+    assert_eq!(
+        rows,
+        vec![
+            json!({"user_id": "u4", "n": 1, "total": 200.0}),
+            json!({"user_id": "u1", "n": 2, "total": 130.0}),
+        ]
+    );
+}
+
+#[test]
+fn inline_sql_matches_json_dsl() {
+    let from_json = run_json(&["queries/q4_filtered_grouped.json"]);
+    let from_sql = run_json(&[
+        "--sql",
+        "select user_id, count(*), sum(amount) from 'data/transactions.csv' \
+         where city = 'SF' group by user_id",
+    ]);
+
+    assert_eq!(from_json, from_sql);
+}
+
+#[test]
+fn literal_on_left_is_flipped() {
+    let rows = run_json(&[
+        "--sql",
+        "SELECT user_id, amount FROM 'data/transactions.csv' WHERE 100 < amount",
+    ]);
+
+    assert_eq!(
+        rows,
+        vec![
+            json!({"user_id": "u1", "amount": 120}),
+            json!({"user_id": "u4", "amount": 200}),
+        ]
+    );
+}
+
+#[test]
+fn sql_errors_point_at_the_offending_token() {
+    let (_out, err, code) = run_bin(&[
+        "--sql",
+        "SELECT user_id\nFROM 'data/transactions.csv'\nWHERE amount > 10 AND city\nLIMIT 3",
+    ]);
+
+    assert_ne!(code, 0);
+    assert!(err.contains("expected a comparison operator"), "{err}");
+    assert!(err.contains("line 4, column 1"), "{err}");
+}
+
+#[test]
+fn sql_rejects_keywords_as_columns() {
+    let (_out, err, code) = run_bin(&["--sql", "SELECT FROM 'data/transactions.csv'"]);
+
+    assert_ne!(code, 0);
+    assert!(err.contains("found `FROM` at line 1, column 8"), "{err}");
+}
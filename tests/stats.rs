@@ -0,0 +1,214 @@
+mod common;
+
+use common::{run_all, run_bin, run_json};
+use serde_json::{Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// A fresh copy of the sample data, so sidecar files stay out of `data/`.
+fn data_copy(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for file in ["transactions.csv", "users.csv", "zips.csv"] {
+        fs::copy(format!("data/{file}"), dir.join(file)).unwrap();
+    }
+    dir
+}
+
+fn collect(path: &Path, extra: &[&str]) -> Value {
+    let path = path.to_str().unwrap();
+    let mut args = vec!["stats", path];
+    args.extend_from_slice(extra);
+    let out = run_all(&args);
+    assert!(out.contains(&format!("Wrote {path}.stats.json")), "{out}");
+    serde_json::from_str(&fs::read_to_string(format!("{path}.stats.json")).unwrap()).unwrap()
+}
+
+fn column<'a>(stats: &'a Value, name: &str) -> &'a Value {
+    stats["columns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == name)
+        .unwrap()
+}
+
+#[test]
+fn stats_summarize_each_column() {
+    let dir = data_copy("stats_summary");
+    let stats = collect(&dir.join("transactions.csv"), &["--buckets", "3"]);
+    assert_eq!(stats["rows"], 6);
+
+    let amount = column(&stats, "amount");
+    assert_eq!(amount["type"], "int64");
+    assert_eq!((&amount["min"], &amount["max"]), (&json!(10), &json!(200)));
+    assert_eq!(amount["nulls"], 0);
+    assert_eq!(amount["distinct"], 6);
+    assert_eq!(
+        amount["histogram"],
+        json!([
+            {"upper": 15.0, "rows": 2},
+            {"upper": 80.0, "rows": 2},
+            {"upper": 200.0, "rows": 2},
+        ])
+    );
+
+    let city = column(&stats, "city");
+    assert_eq!((&city["min"], &city["max"]), (&json!("NY"), &json!("SJ")));
+    assert_eq!(city["distinct"], 3);
+    assert_eq!(city["histogram"], json!([]));
+
+    // Empty and unparseable cells count as nulls.
+    let stats = collect(&dir.join("zips.csv"), &["--infer-rows", "1"]);
+    let population = column(&stats, "population");
+    assert_eq!(population["nulls"], 2);
+    assert_eq!(population["distinct"], 2);
+}
+
+#[test]
+fn large_columns_are_summarized_by_sketch_and_sample() {
+    let dir = data_copy("stats_large");
+    let path = dir.join("big.csv");
+    let mut csv = String::from("id,bucket\n");
+    for i in 0..50_000 {
+        csv.push_str(&format!("{i},{}\n", i % 100));
+    }
+    fs::write(&path, csv).unwrap();
+    let stats = collect(&path, &["--buckets", "4"]);
+
+    let id = column(&stats, "id");
+    let distinct = id["distinct"].as_f64().unwrap();
+    assert!((distinct - 50_000.0).abs() < 2_500.0, "{distinct}");
+    let histogram = id["histogram"].as_array().unwrap();
+    assert_eq!(histogram.len(), 4);
+    let rows: u64 = histogram.iter().map(|b| b["rows"].as_u64().unwrap()).sum();
+    assert_eq!(rows, 50_000);
+    let middle = histogram[1]["upper"].as_f64().unwrap();
+    assert!((middle - 25_000.0).abs() < 2_500.0, "{middle}");
+
+    let buckets = column(&stats, "bucket")["distinct"].as_f64().unwrap();
+    assert!((buckets - 100.0).abs() <= 2.0, "{buckets}");
+}
+
+#[test]
+fn explain_shows_estimated_rows() {
+    let dir = data_copy("stats_explain");
+    let csv = dir.join("transactions.csv");
+    let sql = format!(
+        "SELECT user_id, count(*) FROM '{}' WHERE amount >= 55 GROUP BY user_id",
+        csv.display()
+    );
+
+    let before = run_all(&["--explain", "--sql", &sql]);
+    assert!(!before.contains("est_rows"), "{before}");
+
+    collect(&csv, &[]);
+    let all = run_all(&["--explain", "--sql", &sql]);
+    assert!(all.contains("filter=amount >= 55) [est_rows=4]"), "{all}");
+    assert!(
+        all.contains("Aggregate(group_keys=[\"user_id\"], aggs=1) [est_rows=4]"),
+        "{all}"
+    );
+
+    let (out, _, code) = run_bin(&["--explain", "--explain-format", "json", "--sql", &sql]);
+    assert_eq!(code, 0);
+    let plan: Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(plan["est_rows"], 4);
+
+    // A file that changed since its statistics were taken gets no estimate.
+    let mut data = fs::read_to_string(&csv).unwrap();
+    data.push_str("u5,70,food,LA\n");
+    fs::write(&csv, data).unwrap();
+    let stale = run_all(&["--explain", "--sql", &sql]);
+    assert!(!stale.contains("est_rows"), "{stale}");
+}
+
+#[test]
+fn range_estimates_leave_out_rows_equal_to_a_bucket_bound() {
+    let dir = data_copy("stats_bounds");
+    let path = dir.join("levels.csv");
+    let mut csv = String::from("id,level\n");
+    for i in 0..100 {
+        csv.push_str(&format!("{i},{}\n", i % 4 + 1));
+    }
+    fs::write(&path, csv).unwrap();
+    let stats = collect(&path, &["--buckets", "4"]);
+    assert_eq!(
+        column(&stats, "level")["histogram"],
+        json!([
+            {"upper": 1.0, "rows": 25},
+            {"upper": 2.0, "rows": 25},
+            {"upper": 3.0, "rows": 25},
+            {"upper": 4.0, "rows": 25},
+        ])
+    );
+
+    let path = path.display();
+    for (cond, rows) in [
+        ("level < 3", 50),
+        ("level <= 3", 75),
+        ("level > 2", 50),
+        ("level >= 2", 75),
+    ] {
+        let sql = format!("SELECT id FROM '{path}' WHERE {cond}");
+        let all = run_all(&["--explain", "--sql", &sql]);
+        assert!(all.contains(&format!("[est_rows={rows}]")), "{cond}: {all}");
+        assert_eq!(run_json(&["--sql", &sql]).len(), rows, "{cond}");
+    }
+}
+
+#[test]
+fn predicates_run_most_selective_first() {
+    let dir = data_copy("stats_order");
+    let csv = dir.join("transactions.csv");
+    let sql = format!(
+        "SELECT user_id FROM '{}' WHERE city != 'LA' AND city = 'SF' AND amount > 150",
+        csv.display()
+    );
+
+    // Without statistics the written order is kept.
+    let all = run_all(&["--explain", "--sql", &sql]);
+    assert!(
+        all.contains("filter=city != \"LA\" AND city == \"SF\" AND amount > 150)"),
+        "{all}"
+    );
+
+    collect(&csv, &[]);
+    let all = run_all(&["--explain", "--sql", &sql]);
+    assert!(
+        all.contains("filter=amount > 150 AND city == \"SF\" AND city != \"LA\")"),
+        "{all}"
+    );
+    assert_eq!(run_json(&["--sql", &sql]), vec![json!({"user_id": "u4"})]);
+}
+
+#[test]
+fn joins_build_on_the_side_with_fewer_estimated_rows() {
+    let dir = data_copy("stats_join");
+    let (t, u) = (dir.join("transactions.csv"), dir.join("users.csv"));
+    collect(&t, &[]);
+    collect(&u, &[]);
+    let (t, u) = (t.display(), u.display());
+    let t_join_u =
+        format!("SELECT t.user_id, u.tier FROM '{t}' t JOIN '{u}' u ON t.user_id = u.user_id");
+    let u_join_t =
+        format!("SELECT t.user_id, u.tier FROM '{u}' u JOIN '{t}' t ON u.user_id = t.user_id");
+
+    let all = run_all(&["--explain", "--sql", &t_join_u]);
+    assert!(all.contains("build=right)"), "{all}");
+    let all = run_all(&["--explain", "--sql", &u_join_t]);
+    assert!(all.contains("build=left)"), "{all}");
+
+    // A selective filter makes transactions the smaller side.
+    let filtered = format!("{t_join_u} WHERE t.amount > 100");
+    let all = run_all(&["--explain", "--sql", &filtered]);
+    assert!(all.contains("build=left)"), "{all}");
+    let physical = run_all(&["--explain-physical", "--sql", &filtered]);
+    assert!(physical.contains("build=left"), "{physical}");
+
+    assert_eq!(
+        run_json(&["--sql", &filtered]),
+        vec![json!({"t.user_id": "u1", "u.tier": "gold"})]
+    );
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::run_bin;
+
+// Run a query that must fail at plan time and return its stderr
+fn plan_error(args: &[&str]) -> String {
+    let (out, err, code) = run_bin(args);
+    assert_ne!(code, 0, "expected failure.\nSTDOUT:\n{out}");
+    err
+}
+
+#[test]
+fn misspelled_column_suggests_closest_match() {
+    let err = plan_error(&["queries/bad/unknown_column.json"]);
+    assert!(
+        err.contains("unknown column `amout` in `sum(amout)`; did you mean `amount`?"),
+        "{err}"
+    );
+
+    let err = plan_error(&[
+        "--sql",
+        "SELECT user_id FROM 'data/transactions.csv' WHERE catgory = 'food'",
+    ]);
+    assert!(
+        err.contains("unknown column `catgory` in WHERE; did you mean `category`?"),
+        "{err}"
+    );
+}
+
+#[test]
+fn unqualified_join_column_suggests_qualified_name() {
+    let err = plan_error(&[
+        "--sql",
+        "SELECT t.user_id, name FROM 'data/transactions.csv' t \
+         JOIN 'data/users.csv' u ON t.user_id = u.user_id",
+    ]);
+    assert!(
+        err.contains("unknown column `name` in the select list; did you mean `u.name`?"),
+        "{err}"
+    );
+}
+
+#[test]
+fn unrelated_name_lists_available_columns() {
+    let err = plan_error(&[
+        "--sql",
+        "SELECT item FROM 'data/ratings.csv' ORDER BY price",
+    ]);
+    assert!(
+        err.contains(
+            "unknown column `price` in ORDER BY; available columns: `item`, `rating`, `category`"
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn ungrouped_select_column_is_rejected() {
+    let err = plan_error(&[
+        "--sql",
+        "SELECT user_id, city, sum(amount) FROM 'data/transactions.csv' GROUP BY user_id",
+    ]);
+    assert!(
+        err.contains(
+            "column `city` in the select list must appear in group_by or be used in an aggregate function"
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn validation_runs_before_explain() {
+    let err = plan_error(&["--explain", "queries/bad/unknown_column.json"]);
+    assert!(err.contains("did you mean `amount`?"), "{err}");
+}